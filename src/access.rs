@@ -0,0 +1,89 @@
+use std::{io, ops::BitOr, path::Path};
+
+/// Which permissions to check in `PathExt::real_access`, which may be combined with `|`.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct AccessMode {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl AccessMode {
+    pub const READ: AccessMode = AccessMode {
+        read: true,
+        write: false,
+        execute: false,
+    };
+    pub const WRITE: AccessMode = AccessMode {
+        read: false,
+        write: true,
+        execute: false,
+    };
+    pub const EXECUTE: AccessMode = AccessMode {
+        read: false,
+        write: false,
+        execute: true,
+    };
+}
+
+impl BitOr for AccessMode {
+    type Output = AccessMode;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        AccessMode {
+            read: self.read || rhs.read,
+            write: self.write || rhs.write,
+            execute: self.execute || rhs.execute,
+        }
+    }
+}
+
+// whether the current process has the given access to path, where lack of permission is not an error
+#[cfg(target_family = "unix")]
+pub(crate) fn has_access(path: &Path, mode: AccessMode) -> io::Result<bool> {
+    use rustix::{
+        fs::{accessat, Access, AtFlags, CWD},
+        io::Errno,
+    };
+
+    let mut access = Access::EXISTS;
+    if mode.read {
+        access |= Access::READ_OK;
+    }
+    if mode.write {
+        access |= Access::WRITE_OK;
+    }
+    if mode.execute {
+        access |= Access::EXEC_OK;
+    }
+
+    match accessat(CWD, path, access, AtFlags::empty()) {
+        Ok(()) => Ok(true),
+        Err(Errno::ACCESS | Errno::ROFS | Errno::TXTBSY) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Windows has no equivalent of `access`, so this is a best-effort check of the read-only attribute,
+// with only directories and the usual executable extensions deemed executable.
+#[cfg(not(target_family = "unix"))]
+pub(crate) fn has_access(path: &Path, mode: AccessMode) -> io::Result<bool> {
+    const EXECUTABLE_EXTENSIONS: [&str; 4] = ["exe", "com", "bat", "cmd"];
+
+    let metadata = path.metadata()?;
+    if mode.write && metadata.permissions().readonly() {
+        return Ok(false);
+    }
+    if mode.execute && !metadata.is_dir() {
+        let is_executable = path.extension().is_some_and(|extension| {
+            EXECUTABLE_EXTENSIONS
+                .iter()
+                .any(|executable| extension.eq_ignore_ascii_case(executable))
+        });
+        if !is_executable {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,81 @@
+use std::{
+    borrow::BorrowMut,
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::{empty_to_dot, Error, Resolver};
+
+/// Iterator over the real ancestors of a path, starting with the path itself, and ending with the root directory,
+/// or the resolver's virtual root, or some path satisfying its root predicate, if that is reached first.
+///
+/// Each ancestor is the real parent of the previous one, so a relative path ascends via `..` once it is exhausted.
+/// If the resolver has a stop marker, iteration ends instead at the first ancestor containing it.
+/// Iteration ends after the first error.
+pub struct RealAncestors<R> {
+    resolver: R,
+    next: Option<PathBuf>,
+    started: bool,
+    should_continue: Option<Box<dyn Fn() -> bool>>,
+}
+
+impl<R> RealAncestors<R>
+where
+    R: BorrowMut<Resolver>,
+{
+    pub(crate) fn new(resolver: R, path: &Path) -> Self {
+        let next = empty_to_dot(resolver.borrow().based(path).into_owned());
+
+        Self {
+            resolver,
+            next: Some(next),
+            started: false,
+            should_continue: None,
+        }
+    }
+
+    pub(crate) fn cancellable<F>(self, should_continue: F) -> Self
+    where
+        F: Fn() -> bool + 'static,
+    {
+        Self {
+            should_continue: Some(Box::new(should_continue)),
+            ..self
+        }
+    }
+
+    fn ascend(&mut self, path: &Path) -> io::Result<Option<PathBuf>> {
+        if self.should_continue.as_ref().is_some_and(|f| !f()) {
+            return Err(Error::Cancelled.into());
+        }
+
+        if self.resolver.borrow().is_marker_root(path)? || self.resolver.borrow().is_root(path)? {
+            Ok(None)
+        } else {
+            self.resolver.borrow_mut().parent(path).map(Some)
+        }
+    }
+}
+
+impl<R> Iterator for RealAncestors<R>
+where
+    R: BorrowMut<Resolver>,
+{
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            return self.next.clone().map(Ok);
+        }
+
+        let current = self.next.take()?;
+        match self.ascend(&current) {
+            Ok(next) => {
+                self.next = next.clone();
+                next.map(Ok)
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
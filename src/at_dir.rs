@@ -0,0 +1,133 @@
+use std::{
+    ffi::OsString,
+    io,
+    os::{
+        fd::{AsFd, OwnedFd},
+        unix::ffi::OsStringExt,
+    },
+    path::{Path, PathBuf},
+};
+
+#[cfg(target_os = "linux")]
+use rustix::fs::{fstat, openat2, Mode, OFlags, ResolveFlags};
+use rustix::fs::{readlinkat, statat, AtFlags, FileType, Stat};
+
+#[cfg(target_os = "linux")]
+use super::Error;
+use super::{EntryKind, FileIdentity, FileSystem};
+
+/// The real filesystem, with relative paths resolved with respect to an open directory rather than the current directory,
+/// as per `openat`.
+///
+/// This avoids races where the current directory, or an ancestor of the directory, is renamed during resolution.
+#[derive(Debug)]
+pub struct AtDirFileSystem {
+    dir: OwnedFd,
+    #[cfg(target_os = "linux")]
+    beneath: bool,
+}
+
+impl AtDirFileSystem {
+    /// Resolve with respect to the open directory `dir`, which is duplicated, so need not outlive this.
+    pub fn new<F>(dir: F) -> io::Result<Self>
+    where
+        F: AsFd,
+    {
+        Ok(Self {
+            dir: dir.as_fd().try_clone_to_owned()?,
+            #[cfg(target_os = "linux")]
+            beneath: false,
+        })
+    }
+
+    /// Confine every path beneath the directory, as per `openat2` with `RESOLVE_BENEATH`,
+    /// so that no symlink, including any intermediate component, nor `..`, can escape it.
+    /// An attempted escape is an error of kind `PermissionDenied`.
+    #[cfg(target_os = "linux")]
+    pub fn beneath(self) -> Self {
+        Self {
+            beneath: true,
+            ..self
+        }
+    }
+
+    // open path itself with no access, so that it may be examined without the kernel resolving it afresh
+    #[cfg(target_os = "linux")]
+    fn open_beneath(&self, path: &Path, flags: AtFlags) -> io::Result<OwnedFd> {
+        let oflags = if flags.contains(AtFlags::SYMLINK_NOFOLLOW) {
+            OFlags::PATH | OFlags::NOFOLLOW | OFlags::CLOEXEC
+        } else {
+            OFlags::PATH | OFlags::CLOEXEC
+        };
+
+        openat2(
+            &self.dir,
+            path,
+            oflags,
+            Mode::empty(),
+            ResolveFlags::BENEATH | ResolveFlags::NO_MAGICLINKS,
+        )
+        .map_err(|e| {
+            if e == rustix::io::Errno::XDEV {
+                Error::Escape(path.to_path_buf()).into()
+            } else {
+                e.into()
+            }
+        })
+    }
+
+    fn stat(&self, path: &Path, flags: AtFlags) -> io::Result<Stat> {
+        #[cfg(target_os = "linux")]
+        if self.beneath {
+            return Ok(fstat(self.open_beneath(path, flags)?)?);
+        }
+
+        Ok(statat(&self.dir, path, flags)?)
+    }
+
+    fn kind(&self, path: &Path, flags: AtFlags) -> io::Result<EntryKind> {
+        let stat = self.stat(path, flags)?;
+
+        Ok(match FileType::from_raw_mode(stat.st_mode) {
+            FileType::Symlink => EntryKind::Symlink,
+            FileType::Directory => EntryKind::Dir,
+            _ => EntryKind::File,
+        })
+    }
+}
+
+impl FileSystem for AtDirFileSystem {
+    fn entry_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        self.kind(path, AtFlags::SYMLINK_NOFOLLOW)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        #[cfg(target_os = "linux")]
+        let target = if self.beneath {
+            // an empty path reads the symlink opened without following it
+            readlinkat(
+                self.open_beneath(path, AtFlags::SYMLINK_NOFOLLOW)?,
+                "",
+                Vec::new(),
+            )?
+        } else {
+            readlinkat(&self.dir, path, Vec::new())?
+        };
+        #[cfg(not(target_os = "linux"))]
+        let target = readlinkat(&self.dir, path, Vec::new())?;
+        Ok(OsString::from_vec(target.into_bytes()).into())
+    }
+
+    fn target_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        self.kind(path, AtFlags::empty())
+    }
+
+    fn dir_identity(&self, path: &Path) -> io::Result<Option<FileIdentity>> {
+        let stat = self.stat(path, AtFlags::empty())?;
+
+        Ok(
+            (FileType::from_raw_mode(stat.st_mode) == FileType::Directory)
+                .then(|| FileIdentity::new(stat.st_dev, stat.st_ino)),
+        )
+    }
+}
@@ -0,0 +1,90 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use super::FileIdentity;
+
+/// Cache of real parents, keyed both by path and by the identity of directories,
+/// so that once any alias of a directory has been resolved, other aliases reuse the result.
+#[derive(Default, Debug)]
+pub(crate) struct ParentCache {
+    // with the modification time of the path when resolved, if validation is required
+    by_path: HashMap<PathBuf, (PathBuf, Option<SystemTime>)>,
+    // absolute and relative results are kept apart, since a relative result is no use for an absolute path
+    by_identity: HashMap<(FileIdentity, bool), PathBuf>,
+    path_hits: usize,
+    identity_hits: usize,
+    misses: usize,
+}
+
+/// Statistics for the parent cache of a `Resolver`.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CacheStats {
+    /// Number of lookups which found a result for the same path.
+    pub path_hits: usize,
+    /// Number of lookups which found a result for another path to the same directory.
+    pub identity_hits: usize,
+    /// Number of lookups which found nothing, including any result invalidated by modification of the path.
+    pub misses: usize,
+}
+
+impl ParentCache {
+    // `modified` is the current modification time of the path, where a result cached with any other is stale
+    pub(crate) fn get(
+        &mut self,
+        path: &Path,
+        identity: Option<FileIdentity>,
+        modified: Option<SystemTime>,
+    ) -> Option<PathBuf> {
+        match self.by_path.get(path) {
+            Some((parent, cached_modified)) if *cached_modified == modified => {
+                self.path_hits += 1;
+                return Some(parent.clone());
+            }
+            Some(_) => {
+                // the path has since been modified, perhaps retargeted, but its current identity may yet be found
+                self.by_path.remove(path);
+            }
+            None => (),
+        }
+
+        if let Some(parent) =
+            identity.and_then(|id| self.by_identity.get(&(id, path.is_absolute())))
+        {
+            self.identity_hits += 1;
+            let parent = parent.clone();
+            self.by_path
+                .insert(path.to_path_buf(), (parent.clone(), modified));
+            return Some(parent);
+        }
+
+        self.misses += 1;
+        None
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        path: &Path,
+        identity: Option<FileIdentity>,
+        modified: Option<SystemTime>,
+        parent: &Path,
+    ) {
+        self.by_path
+            .insert(path.to_path_buf(), (parent.to_path_buf(), modified));
+
+        if let Some(id) = identity {
+            self.by_identity
+                .insert((id, path.is_absolute()), parent.to_path_buf());
+        }
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            path_hits: self.path_hits,
+            identity_hits: self.identity_hits,
+            misses: self.misses,
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    ffi::OsString,
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use super::Error;
+
+/// Expand environment variable references in each component of `path`, looking up variables with `lookup`.
+///
+/// References are `$VAR` or `${VAR}` on Unix, and `%VAR%` on Windows.
+/// Anything which is not a well-formed reference is left unchanged, as are components which are not valid Unicode.
+/// Values are spliced in as text would be, so an absolute value other than at the start of `path` does not replace what precedes it.
+/// This is purely lexical, and does not touch the filesystem.
+pub fn expand_env_with<F>(path: &Path, lookup: F) -> io::Result<PathBuf>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    expand(path, lookup).map_err(io::Error::from)
+}
+
+pub(crate) fn expand<F>(path: &Path, lookup: F) -> Result<PathBuf, Error>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    let mut expanded = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => match name.to_str() {
+                Some(name) => splice(&mut expanded, expand_component(name, &lookup)?.as_ref()),
+                None => expanded.push(name),
+            },
+            _ => expanded.push(component),
+        }
+    }
+
+    Ok(expanded)
+}
+
+// push the components of `value` as if its text were spliced into the path,
+// so that where it is absolute, its root does not discard what precedes it
+fn splice(expanded: &mut PathBuf, value: &Path) {
+    if expanded.as_os_str().is_empty() {
+        expanded.push(value);
+    } else {
+        for component in value.components() {
+            if !matches!(component, Component::Prefix(_) | Component::RootDir) {
+                expanded.push(component);
+            }
+        }
+    }
+}
+
+// the environment variable defining the user's home directory
+const HOME: &str = if cfg!(target_family = "windows") {
+    "USERPROFILE"
+} else {
+    "HOME"
+};
+
+// the user's home directory, as looked up with `lookup`
+pub(crate) fn home_dir<F>(lookup: F) -> Result<PathBuf, Error>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    lookup_or_error(HOME, &lookup).map(PathBuf::from)
+}
+
+// the path with a leading `~` component replaced by the home directory, as looked up with `lookup`
+pub(crate) fn expand_tilde<F>(path: &Path, lookup: F) -> Result<PathBuf, Error>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    let mut components = path.components();
+    match components.next() {
+        Some(Component::Normal(name)) if name == "~" => {
+            let mut expanded = home_dir(lookup)?;
+            expanded.push(components.as_path());
+            Ok(expanded)
+        }
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+fn lookup_or_error<F>(name: &str, lookup: &F) -> Result<OsString, Error>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    lookup(name).ok_or_else(|| Error::UndefinedVar {
+        name: name.to_string(),
+    })
+}
+
+#[cfg(not(target_family = "windows"))]
+fn expand_component<F>(s: &str, lookup: &F) -> Result<OsString, Error>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    let mut expanded = OsString::new();
+    let mut rest = s;
+
+    while let Some(i) = rest.find('$') {
+        expanded.push(&rest[..i]);
+        let after = &rest[i + 1..];
+
+        if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(j) if is_var_name(&braced[..j]) => {
+                    expanded.push(lookup_or_error(&braced[..j], lookup)?);
+                    rest = &braced[j + 1..];
+                }
+                _ => {
+                    expanded.push("$");
+                    rest = after;
+                }
+            }
+        } else {
+            let len = var_name_len(after);
+            if len > 0 {
+                expanded.push(lookup_or_error(&after[..len], lookup)?);
+            } else {
+                expanded.push("$");
+            }
+            rest = &after[len..];
+        }
+    }
+    expanded.push(rest);
+
+    Ok(expanded)
+}
+
+#[cfg(target_family = "windows")]
+fn expand_component<F>(s: &str, lookup: &F) -> Result<OsString, Error>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    let mut expanded = OsString::new();
+    let mut rest = s;
+
+    while let Some(i) = rest.find('%') {
+        expanded.push(&rest[..i]);
+        let after = &rest[i + 1..];
+
+        match after.find('%') {
+            Some(j) if is_var_name(&after[..j]) => {
+                expanded.push(lookup_or_error(&after[..j], lookup)?);
+                rest = &after[j + 1..];
+            }
+            _ => {
+                expanded.push("%");
+                rest = after;
+            }
+        }
+    }
+    expanded.push(rest);
+
+    Ok(expanded)
+}
+
+// length of the variable name at the start of `s`, if any
+#[cfg(not(target_family = "windows"))]
+fn var_name_len(s: &str) -> usize {
+    if s.starts_with(|c: char| c.is_ascii_digit()) {
+        return 0;
+    }
+
+    s.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(s.len())
+}
+
+fn is_var_name(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,569 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    fs, io,
+    path::{Component, Path, PathBuf},
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// The kind of a filesystem entry, as far as resolution is concerned.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+/// The identity of a file or directory, independent of the path by which it is reached, such as device and inode number on Unix.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct FileIdentity {
+    device: u64,
+    index: u64,
+}
+
+impl FileIdentity {
+    pub fn new(device: u64, index: u64) -> Self {
+        Self { device, index }
+    }
+
+    /// The device containing the file or directory.
+    pub fn device(&self) -> u64 {
+        self.device
+    }
+}
+
+/// The filesystem operations required for resolution, so that paths may be resolved
+/// other than on the real filesystem, for example the contents of an archive.
+pub trait FileSystem: Debug + Send + Sync {
+    /// The kind of entry at `path`, without following a trailing symlink, as per `Path::symlink_metadata`.
+    fn entry_kind(&self, path: &Path) -> io::Result<EntryKind>;
+
+    /// The target of the symlink at `path`, as per `Path::read_link`.
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// The identity of the directory at `path`, following symlinks, or `None` if it is not a directory,
+    /// or identities are not supported, which is the default.
+    fn dir_identity(&self, _path: &Path) -> io::Result<Option<FileIdentity>> {
+        Ok(None)
+    }
+
+    /// Whether `path` is the root directory, following symlinks, that is, a directory which is its own parent.
+    ///
+    /// The default implementation compares the `dir_identity` of `path` and its `..` where identities are supported,
+    /// and otherwise considers the path lexically, so that only a path which ascends to its root directory is the root.
+    fn is_root(&self, path: &Path) -> io::Result<bool> {
+        let path = if path.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            path
+        };
+
+        match self.dir_identity(path)? {
+            Some(identity) => Ok(self.dir_identity(&path.join(".."))? == Some(identity)),
+            None => Ok(is_lexical_root(path)),
+        }
+    }
+
+    /// The modification time of the entry at `path`, without following a trailing symlink,
+    /// or `None` if modification times are not supported, which is the default.
+    fn modified(&self, _path: &Path) -> io::Result<Option<SystemTime>> {
+        Ok(None)
+    }
+
+    /// The kind declared by the symlink at `path`, where symlinks are typed as file or directory as on Windows,
+    /// or `None` if symlinks are untyped, which is the default.
+    fn symlink_kind(&self, _path: &Path) -> io::Result<Option<EntryKind>> {
+        Ok(None)
+    }
+
+    /// The kind of entry at `path`, following symlinks, as per `Path::metadata`, so never `Symlink`.
+    ///
+    /// The default implementation follows each symlink in turn using `entry_kind` and `read_link`.
+    fn target_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        let mut path = path.to_path_buf();
+
+        for _ in 0..=MAX_SYMLINK_HOPS {
+            match self.entry_kind(&path)? {
+                EntryKind::Symlink => {
+                    let target = self.read_link(&path)?;
+                    if target.as_os_str().is_empty() {
+                        return Err(empty_target(&path));
+                    }
+                    // intermediate symlinks, including any in the target, are followed by entry_kind
+                    path = match path.parent() {
+                        Some(dir) => dir.join(target),
+                        None => target,
+                    };
+                }
+                kind => return Ok(kind),
+            }
+        }
+
+        Err(too_many_symlinks(&path))
+    }
+}
+
+impl<F> FileSystem for Arc<F>
+where
+    F: FileSystem + ?Sized,
+{
+    fn entry_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        self.as_ref().entry_kind(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.as_ref().read_link(path)
+    }
+
+    fn target_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        self.as_ref().target_kind(path)
+    }
+
+    fn symlink_kind(&self, path: &Path) -> io::Result<Option<EntryKind>> {
+        self.as_ref().symlink_kind(path)
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<Option<SystemTime>> {
+        self.as_ref().modified(path)
+    }
+
+    fn dir_identity(&self, path: &Path) -> io::Result<Option<FileIdentity>> {
+        self.as_ref().dir_identity(path)
+    }
+
+    fn is_root(&self, path: &Path) -> io::Result<bool> {
+        self.as_ref().is_root(path)
+    }
+}
+
+/// The real filesystem, which is the default.
+///
+/// On Windows, junctions are symlinks, as reported by `Path::symlink_metadata`, with targets which are verbatim paths.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn entry_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        let metadata = path.symlink_metadata()?;
+
+        Ok(if metadata.is_symlink() {
+            EntryKind::Symlink
+        } else if metadata.is_dir() {
+            EntryKind::Dir
+        } else {
+            EntryKind::File
+        })
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        path.read_link()
+    }
+
+    fn target_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        Ok(if path.metadata()?.is_dir() {
+            EntryKind::Dir
+        } else {
+            EntryKind::File
+        })
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<Option<SystemTime>> {
+        path.symlink_metadata()?.modified().map(Some)
+    }
+
+    #[cfg(target_family = "windows")]
+    fn symlink_kind(&self, path: &Path) -> io::Result<Option<EntryKind>> {
+        use std::os::windows::fs::FileTypeExt;
+
+        let file_type = path.symlink_metadata()?.file_type();
+        Ok(if file_type.is_symlink_dir() {
+            Some(EntryKind::Dir)
+        } else if file_type.is_symlink_file() {
+            Some(EntryKind::File)
+        } else {
+            None
+        })
+    }
+
+    #[cfg(target_family = "unix")]
+    fn dir_identity(&self, path: &Path) -> io::Result<Option<FileIdentity>> {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = path.metadata()?;
+        Ok(metadata
+            .is_dir()
+            .then(|| FileIdentity::new(metadata.dev(), metadata.ino())))
+    }
+
+    fn is_root(&self, path: &Path) -> io::Result<bool> {
+        crate::PathExt::is_real_root(path)
+    }
+}
+
+// another filesystem, with each call retried on transient errors, see `Resolver::retry`
+#[derive(Debug)]
+pub(crate) struct RetryFileSystem {
+    inner: Arc<dyn FileSystem>,
+    attempts: u32,
+    backoff: Duration,
+}
+
+impl RetryFileSystem {
+    pub(crate) fn new(inner: Arc<dyn FileSystem>, attempts: u32, backoff: Duration) -> Self {
+        Self {
+            inner,
+            attempts,
+            backoff,
+        }
+    }
+
+    fn retrying<T, F>(&self, call: F) -> io::Result<T>
+    where
+        F: Fn() -> io::Result<T>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            match call() {
+                Err(e) if attempt < self.attempts && is_transient(&e) => {
+                    thread::sleep(self.backoff);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+// whether an error may succeed on retry, such as a stale file handle on a network filesystem
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::StaleNetworkFileHandle | io::ErrorKind::Interrupted
+    )
+}
+
+impl FileSystem for RetryFileSystem {
+    fn entry_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        self.retrying(|| self.inner.entry_kind(path))
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.retrying(|| self.inner.read_link(path))
+    }
+
+    fn target_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        self.retrying(|| self.inner.target_kind(path))
+    }
+
+    fn symlink_kind(&self, path: &Path) -> io::Result<Option<EntryKind>> {
+        self.retrying(|| self.inner.symlink_kind(path))
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<Option<SystemTime>> {
+        self.retrying(|| self.inner.modified(path))
+    }
+
+    fn dir_identity(&self, path: &Path) -> io::Result<Option<FileIdentity>> {
+        self.retrying(|| self.inner.dir_identity(path))
+    }
+
+    fn is_root(&self, path: &Path) -> io::Result<bool> {
+        self.retrying(|| self.inner.is_root(path))
+    }
+}
+
+// another filesystem, with each call run on its own thread and abandoned if it takes longer than the timeout, see `Resolver::timeout`
+#[derive(Debug)]
+pub(crate) struct TimeoutFileSystem {
+    inner: Arc<dyn FileSystem>,
+    timeout: Duration,
+}
+
+impl TimeoutFileSystem {
+    pub(crate) fn new(inner: Arc<dyn FileSystem>, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+
+    fn timing_out<T, F>(&self, path: &Path, call: F) -> io::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn FileSystem, &Path) -> io::Result<T> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let inner = self.inner.clone();
+        let path = path.to_path_buf();
+
+        // the receiver may have gone by the time the call completes, which is fine
+        thread::spawn(move || sender.send(call(inner.as_ref(), &path)));
+
+        receiver
+            .recv_timeout(self.timeout)
+            .unwrap_or_else(|e| match e {
+                mpsc::RecvTimeoutError::Timeout => Err(io::ErrorKind::TimedOut.into()),
+                mpsc::RecvTimeoutError::Disconnected => {
+                    Err(io::Error::other("filesystem call panicked"))
+                }
+            })
+    }
+}
+
+impl FileSystem for TimeoutFileSystem {
+    fn entry_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        self.timing_out(path, |fs, path| fs.entry_kind(path))
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.timing_out(path, |fs, path| fs.read_link(path))
+    }
+
+    fn target_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        self.timing_out(path, |fs, path| fs.target_kind(path))
+    }
+
+    fn symlink_kind(&self, path: &Path) -> io::Result<Option<EntryKind>> {
+        self.timing_out(path, |fs, path| fs.symlink_kind(path))
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<Option<SystemTime>> {
+        self.timing_out(path, |fs, path| fs.modified(path))
+    }
+
+    fn dir_identity(&self, path: &Path) -> io::Result<Option<FileIdentity>> {
+        self.timing_out(path, |fs, path| fs.dir_identity(path))
+    }
+
+    fn is_root(&self, path: &Path) -> io::Result<bool> {
+        self.timing_out(path, |fs, path| fs.is_root(path))
+    }
+}
+
+/// An entry in a `MemoryFileSystem`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Entry {
+    File,
+    Dir,
+    Symlink(PathBuf),
+}
+
+/// A read-only filesystem held in memory, for example an index of the contents of an archive.
+///
+/// Relative paths are with respect to the top of the filesystem, which like the root directory need not have an entry.
+/// Intermediate symlinks are followed as on a real filesystem.
+#[derive(Default, Clone, Debug)]
+pub struct MemoryFileSystem {
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+// as per Linux
+const MAX_SYMLINK_HOPS: usize = 40;
+
+impl MemoryFileSystem {
+    pub fn new(entries: BTreeMap<PathBuf, Entry>) -> Self {
+        let entries = entries
+            .into_iter()
+            .map(|(path, entry)| (path.components().collect(), entry))
+            .collect();
+
+        Self { entries }
+    }
+
+    /// A snapshot of the real filesystem below the absolute path `root`, recording the kind of every entry, and every symlink target.
+    ///
+    /// Symlinks are not followed, so targets outside `root` are not found.
+    /// Ancestors of `root` are recorded as plain directories, so `root` is best canonicalized if its ancestors may include symlinks.
+    pub fn snapshot<P>(root: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let root = root.as_ref();
+        let mut entries = BTreeMap::new();
+
+        for ancestor in root.ancestors().skip(1) {
+            if ancestor.file_name().is_some() {
+                entries.insert(ancestor.to_path_buf(), Entry::Dir);
+            }
+        }
+        entries.insert(root.to_path_buf(), Entry::Dir);
+
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for dir_entry in fs::read_dir(&dir)? {
+                let dir_entry = dir_entry?;
+                let path = dir_entry.path();
+                let file_type = dir_entry.file_type()?;
+
+                let entry = if file_type.is_symlink() {
+                    Entry::Symlink(fs::read_link(&path)?)
+                } else if file_type.is_dir() {
+                    pending.push(path.clone());
+                    Entry::Dir
+                } else {
+                    Entry::File
+                };
+                entries.insert(path, entry);
+            }
+        }
+
+        Ok(Self::new(entries))
+    }
+
+    // Find the physical location of a path, following intermediate symlinks but not a trailing one.
+    fn locate(&self, path: &Path) -> io::Result<PathBuf> {
+        // components remaining to be located, in reverse order
+        let mut pending = reversed_components(path);
+        let mut located = PathBuf::new();
+        let mut hops = 0;
+
+        while let Some(component) = pending.pop() {
+            match component.components().next() {
+                None | Some(Component::CurDir) => (),
+                Some(Component::Prefix(_) | Component::RootDir) => located.push(&component),
+                Some(Component::ParentDir) => {
+                    // the parent of the top of the filesystem or the root directory is itself
+                    if matches!(located.components().next_back(), Some(Component::Normal(_))) {
+                        located.pop();
+                    }
+                }
+                Some(Component::Normal(_)) => {
+                    located.push(&component);
+
+                    if pending.is_empty() {
+                        break;
+                    }
+
+                    match self.entry(&located)? {
+                        Entry::Symlink(target) => {
+                            hops += 1;
+                            if hops > MAX_SYMLINK_HOPS {
+                                return Err(too_many_symlinks(&located));
+                            }
+
+                            if target.as_os_str().is_empty() {
+                                return Err(empty_target(&located));
+                            }
+
+                            located.pop();
+                            if target.has_root() {
+                                located = PathBuf::new();
+                            }
+                            pending.extend(reversed_components(target));
+                        }
+                        Entry::File if !pending.is_empty() => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::NotADirectory,
+                                format!("not a directory: {}", located.to_string_lossy()),
+                            ));
+                        }
+                        Entry::File | Entry::Dir => (),
+                    }
+                }
+            }
+        }
+
+        Ok(located)
+    }
+
+    fn entry(&self, located: &Path) -> io::Result<&Entry> {
+        self.entries.get(located).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no such entry: {}", located.to_string_lossy()),
+            )
+        })
+    }
+}
+
+fn too_many_symlinks(path: &Path) -> io::Error {
+    io::Error::other(format!(
+        "too many levels of symbolic links at {}",
+        path.to_string_lossy()
+    ))
+}
+
+// as on Linux, an empty symlink target is not found
+fn empty_target(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("symlink has empty target at {}", path.to_string_lossy()),
+    )
+}
+
+// whether a path is the root directory without regard to symlinks, that is, having a root which it does not ascend above
+fn is_lexical_root(path: &Path) -> bool {
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::ParentDir => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+
+    path.has_root() && depth == 0
+}
+
+fn reversed_components(path: &Path) -> Vec<PathBuf> {
+    path.components()
+        .rev()
+        .map(|c| c.as_os_str().into())
+        .collect()
+}
+
+impl From<BTreeMap<PathBuf, Entry>> for MemoryFileSystem {
+    fn from(entries: BTreeMap<PathBuf, Entry>) -> Self {
+        Self::new(entries)
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn entry_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        let located = self.locate(path)?;
+
+        if located.file_name().is_none() {
+            // top of the filesystem or root directory
+            return Ok(EntryKind::Dir);
+        }
+
+        Ok(match self.entry(&located)? {
+            Entry::File => EntryKind::File,
+            Entry::Dir => EntryKind::Dir,
+            Entry::Symlink(_) => EntryKind::Symlink,
+        })
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let located = self.locate(path)?;
+
+        match self.entry(&located)? {
+            Entry::Symlink(target) => Ok(target.clone()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("not a symlink: {}", located.to_string_lossy()),
+            )),
+        }
+    }
+
+    // the top of the filesystem is its own parent, just like the root directory
+    fn is_root(&self, path: &Path) -> io::Result<bool> {
+        let mut located = self.locate(path)?;
+
+        for _ in 0..=MAX_SYMLINK_HOPS {
+            let Some(dir) = located.parent().filter(|_| located.file_name().is_some()) else {
+                return Ok(true);
+            };
+
+            match self.entry(&located)? {
+                Entry::Symlink(target) if target.as_os_str().is_empty() => {
+                    return Err(empty_target(&located))
+                }
+                Entry::Symlink(target) => located = self.locate(&dir.join(target))?,
+                Entry::File | Entry::Dir => return Ok(false),
+            }
+        }
+
+        Err(too_many_symlinks(&located))
+    }
+}
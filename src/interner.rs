@@ -0,0 +1,153 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Interner for resolved paths, built from interned component names.
+///
+/// In a large tree the same names, such as `src` or `node_modules`, recur under many parents,
+/// so each unique name is stored once, and each interned path is a node in a tree of those names.
+/// Since `PathBuf` cannot share storage, interned paths are handed out as `Arc<Path>`, built from the names when first interned,
+/// so that identical results share a single allocation.  UTF-8 paths may also be interned as `Arc<str>`.
+#[derive(Debug)]
+pub(crate) struct Interner {
+    names: HashSet<Arc<OsStr>>,
+    // the first node is the empty path, from which all others descend
+    nodes: Vec<Node>,
+    children: HashMap<(usize, Arc<OsStr>), usize>,
+    paths: usize,
+    name_hits: usize,
+    hits: usize,
+    misses: usize,
+}
+
+#[derive(Default, Debug)]
+struct Node {
+    path: Option<Arc<Path>>,
+    str: Option<Arc<str>>,
+}
+
+/// Statistics for the interner of a `Resolver`.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InternStats {
+    /// Number of unique paths interned, whether as paths or as strings.
+    pub paths: usize,
+    /// Number of unique component names across all interned paths.
+    pub names: usize,
+    /// Number of component names which were already interned, whether in the same path or another.
+    pub name_hits: usize,
+    /// Number of lookups which found an already interned path.
+    pub hits: usize,
+    /// Number of lookups which interned a new path.
+    pub misses: usize,
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self {
+            names: HashSet::new(),
+            nodes: vec![Node::default()],
+            children: HashMap::new(),
+            paths: 0,
+            name_hits: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+}
+
+impl Interner {
+    pub(crate) fn intern(&mut self, path: PathBuf) -> Arc<Path> {
+        let (node, names) = self.node(&path);
+
+        if let Some(interned) = &self.nodes[node].path {
+            self.hits += 1;
+            return interned.clone();
+        }
+        self.misses += 1;
+        self.count_path(node);
+
+        let interned: Arc<Path> = built(&names).into();
+        self.nodes[node].path = Some(interned.clone());
+        interned
+    }
+
+    #[cfg(feature = "camino")]
+    pub(crate) fn intern_str(&mut self, path: String) -> Arc<str> {
+        let (node, names) = self.node(Path::new(&path));
+
+        if let Some(interned) = &self.nodes[node].str {
+            self.hits += 1;
+            return interned.clone();
+        }
+        self.misses += 1;
+        self.count_path(node);
+
+        // the names are all UTF-8, since the path is
+        let interned: Arc<str> = built(&names)
+            .into_os_string()
+            .into_string()
+            .unwrap_or(path)
+            .into();
+        self.nodes[node].str = Some(interned.clone());
+        interned
+    }
+
+    pub(crate) fn stats(&self) -> InternStats {
+        InternStats {
+            paths: self.paths,
+            names: self.names.len(),
+            name_hits: self.name_hits,
+            hits: self.hits,
+            misses: self.misses,
+        }
+    }
+
+    // the node for a path, with its component names as interned
+    fn node(&mut self, path: &Path) -> (usize, Vec<Arc<OsStr>>) {
+        let mut node = 0;
+        let mut names = Vec::new();
+
+        for component in path.components() {
+            let name = self.name(component.as_os_str());
+            node = match self.children.get(&(node, name.clone())) {
+                Some(child) => *child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(Node::default());
+                    self.children.insert((node, name.clone()), child);
+                    child
+                }
+            };
+            names.push(name);
+        }
+
+        (node, names)
+    }
+
+    fn name(&mut self, name: &OsStr) -> Arc<OsStr> {
+        if let Some(interned) = self.names.get(name) {
+            self.name_hits += 1;
+            return interned.clone();
+        }
+
+        let interned: Arc<OsStr> = name.into();
+        self.names.insert(interned.clone());
+        interned
+    }
+
+    // count a path interned for the first time, whether as a path or as a string
+    fn count_path(&mut self, node: usize) {
+        let node = &self.nodes[node];
+        if node.path.is_none() && node.str.is_none() {
+            self.paths += 1;
+        }
+    }
+}
+
+// a path built from its component names
+fn built(names: &[Arc<OsStr>]) -> PathBuf {
+    names.iter().map(AsRef::<OsStr>::as_ref).collect()
+}
@@ -0,0 +1,39 @@
+//! Resolution within the platform's standard directories, enabled by the `directories` feature.
+//!
+//! The directories are as per `directories::BaseDirs`, according to the platform's conventions.
+
+use std::{io, path::PathBuf};
+
+use directories::BaseDirs;
+
+use super::{Error, PathExt};
+
+/// As per `PathExt::real_clean`, for `path` relative to the platform's standard directory for configuration files,
+/// that is, `$XDG_CONFIG_HOME` or `$HOME/.config` on Linux, `$HOME/Library/Application Support` on macOS,
+/// and `{FOLDERID_RoamingAppData}` on Windows, as per `BaseDirs::config_dir`.
+///
+/// For example, where the configuration directory is itself a symlink, `..` in `path` is resolved correctly.
+/// If `path` is absolute, it is cleaned as is.  It is an error of kind `InvalidInput` if there is no home directory.
+pub fn real_clean_in_config_dir<P>(path: P) -> io::Result<PathBuf>
+where
+    P: AsRef<std::path::Path>,
+{
+    let dirs = base_dirs()?;
+    dirs.config_dir().join(path).real_clean()
+}
+
+/// As per `real_clean_in_config_dir`, but relative to the platform's standard directory for data files,
+/// that is, `$XDG_DATA_HOME` or `$HOME/.local/share` on Linux, and otherwise the same as for configuration files,
+/// as per `BaseDirs::data_dir`.
+pub fn real_clean_in_data_dir<P>(path: P) -> io::Result<PathBuf>
+where
+    P: AsRef<std::path::Path>,
+{
+    let dirs = base_dirs()?;
+    dirs.data_dir().join(path).real_clean()
+}
+
+// the standard directories, which are all found relative to the home directory
+fn base_dirs() -> Result<BaseDirs, Error> {
+    BaseDirs::new().ok_or(Error::NoHomeDir)
+}
@@ -2,13 +2,74 @@
 
 use std::{
     borrow::Cow,
+    cell::Cell,
     collections::HashSet,
+    ffi::{OsStr, OsString},
     fmt::Display,
-    io,
-    path::{Component, Path, PathBuf},
+    fs::Metadata,
+    io::{self, BufRead, Write},
+    path::{Component, Path, PathBuf, Prefix},
 };
 
+#[cfg(feature = "access")]
+mod access;
+mod ancestors;
+#[cfg(all(feature = "at-dir", target_family = "unix"))]
+mod at_dir;
+mod cache;
+mod env;
+mod file_system;
+mod interner;
+#[cfg(feature = "directories")]
+mod known_dirs;
+mod resolver;
+mod step;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod trie;
+
+#[cfg(feature = "access")]
+pub use access::AccessMode;
+pub use ancestors::RealAncestors;
+#[cfg(all(feature = "at-dir", target_family = "unix"))]
+pub use at_dir::AtDirFileSystem;
+pub use cache::CacheStats;
+pub use env::expand_env_with;
+pub use file_system::{
+    Entry, EntryKind, FileIdentity, FileSystem, MemoryFileSystem, RealFileSystem,
+};
+pub use interner::InternStats;
+#[cfg(feature = "directories")]
+pub use known_dirs::{real_clean_in_config_dir, real_clean_in_data_dir};
+pub use resolver::{
+    CleanOptions, FollowPolicy, MagicLinks, OutputStyle, Resolver, RootParentPolicy,
+};
+use resolver::{RootPredicate, SymlinkCallback};
+pub use step::{ResolveState, StepResult};
+use trie::PrefixTrie;
+
 /// Extension methods for `std::path::Path` which are correct in the presence of symlinks.
+///
+/// Since these are methods on `Path`, they are equally available via any smart pointer to a path, such as `Arc<Path>` or `Rc<Path>`,
+/// or via `PathBuf`.
+///
+/// Errors are `io::Error`, wrapping an error which describes the path that failed.  Both are `Send + Sync + 'static`,
+/// so that failures may be sent from a background thread, or converted into other error types such as `anyhow::Error`.
+///
+/// ```
+/// use std::{env::temp_dir, path::Path, rc::Rc, sync::Arc};
+/// use real_parent::PathExt;
+///
+/// let dir = temp_dir().join("..");
+/// let expected = dir.real_parent()?;
+///
+/// let shared: Arc<Path> = Arc::from(dir.as_path());
+/// assert_eq!(shared.real_parent()?, expected);
+///
+/// let counted: Rc<Path> = Rc::from(dir.as_path());
+/// assert_eq!(counted.real_parent()?, expected);
+/// # Ok::<(), std::io::Error>(())
+/// ```
 pub trait PathExt {
     /// As per `Path::parent` except that it touches the filesystem to ensure that the resulting path
     /// is correct with respect to symlinks.
@@ -22,15 +83,203 @@ pub trait PathExt {
     /// - `Path::new("..").parent() == ""`, which is incorrect, so `Path::new("..").real_parent() == "../.."`
     /// - `Path::new("foo").parent() == ""`, which is not a valid path, so `Path::new("foo").real_parent() == "."`
     /// - where `Path::parent()` returns `None`, `real_parent()` returns self for absolute root path, and appends `..` otherwise
+    ///
+    /// On Linux, the magic symlinks for open files, `/proc/<pid>/fd/<n>`, are followed as described for `MagicLinks::Follow`.
     fn real_parent(&self) -> io::Result<PathBuf>;
 
+    /// As per `real_parent`, but falling back to `lexical_parent` on any error, so never failing.
+    ///
+    /// For example, for formatting log messages, which must not fail just because a directory became unreadable.
+    /// The fallback does not touch the filesystem, so may be incorrect where the path contains symlinks.
+    fn real_parent_or_lexical(&self) -> PathBuf;
+
+    /// As per `real_parent`, but returning `None` for the root directory, as `Path::parent` does,
+    /// for example to migrate code which expects that.
+    ///
+    /// See `RootParentPolicy::None`.
+    fn real_parent_opt(&self) -> io::Result<Option<PathBuf>>;
+
+    /// As per `real_parent`, but classifying the result, see `RealParent`.
+    ///
+    /// For example, so that an ancestor walk may distinguish remaining within a relative tree from ascending above it,
+    /// without inspecting the result for `..`.
+    fn real_parent_classified(&self) -> io::Result<RealParent>;
+
+    /// As per `real_parent`, but returning `Ok(None)` where the path, or some component required to resolve it, doesn't exist.
+    ///
+    /// For example, for callers which treat a missing path as a normal outcome but a symlink cycle as a bug,
+    /// without inspecting `io::ErrorKind`.  Since the target of a dangling symlink doesn't exist, that is also `Ok(None)`.
+    fn try_real_parent(&self) -> Result<Option<PathBuf>, RealParentError>;
+
     /// Return a clean path, with `.` and `..` folded away as much as possible, and without expanding symlinks except where required
     /// for correctness.
+    ///
+    /// If cleaning collapses the path to nothing, the result is `.`.  See `try_real_clean_raw` to detect that case.
+    /// A leading `..` cannot be folded away, see `CleanOptions::preserve_leading_parent` to treat that as an error.
     fn real_clean(&self) -> io::Result<PathBuf>;
 
+    /// As per `real_clean`, but without substituting `.` for an empty result, which therefore means the current directory.
+    fn try_real_clean_raw(&self) -> io::Result<PathBuf>;
+
+    /// As per `real_clean`, but also return a summary of what cleaning changed.
+    fn real_clean_diff(&self) -> io::Result<(PathBuf, CleanChanges)>;
+
+    /// Return whether this path is already minimal, that is, `real_clean` would return it unchanged.
+    ///
+    /// For example, for a linter to flag paths in configuration files containing `.`, foldable `..`, or redundant separators.
+    /// This stops at the first change found, without building the cleaned path.
+    fn is_real_minimal(&self) -> io::Result<bool>;
+
+    /// Split the path into its deepest existing real ancestor, cleaned as per `real_clean`, and the remaining tail which does not exist.
+    ///
+    /// For example, to determine which directories `create_dir_all` would create.
+    /// The tail is empty if the whole path exists, and is otherwise returned as is, including any `.` or `..`.
+    /// A dangling symlink counts as existing.
+    fn real_existing_prefix(&self) -> io::Result<(PathBuf, PathBuf)>;
+
+    /// Split the path into its longest prefix which contains no symlink, and the remaining tail, starting at the first symlink.
+    ///
+    /// For example, for a build system to cache results keyed on the prefix, which is stable since it involves no symlink.
+    /// Components are examined from left to right, so the path must exist as far as any symlink.  The prefix is returned as is,
+    /// including any `.` or `..`, or as `.` if the first component is a symlink, and the tail is empty if the path contains no symlink.
+    fn real_symlink_free_prefix(&self) -> io::Result<(PathBuf, PathBuf)>;
+
+    /// Return the canonical path of the directory containing the final component, joined with that component exactly as given.
+    ///
+    /// For example, to obtain an absolute and fully real directory for a file which is to be created, while keeping the chosen name.
+    /// The final component need not exist, and if it is a symlink it is not followed.
+    /// If the path does not end in a normal component, for example if it ends in `..`, the whole path is canonicalized.
+    ///
+    /// Contrast with `real_parent`, which keeps the directory as relative and unresolved as correctness allows.
+    fn real_anchor(&self) -> io::Result<PathBuf>;
+
+    /// Return an absolute path for this one, as per `std::path::absolute`, except that `..` is folded physically.
+    ///
+    /// `std::path::absolute` is purely lexical, so `..` is either retained, as on Unix, or folded lexically, as on Windows,
+    /// which is incorrect after a symlink to a directory.  Here a relative path is joined to the current directory
+    /// and then cleaned as per `real_clean`, so symlinks are resolved only where required for correctness.
+    /// As for `std::path::absolute`, the empty path is an error.
+    fn real_absolute(&self) -> io::Result<PathBuf>;
+
+    /// Return a `file://` URL for this path, as made absolute by `real_absolute`, so that it locates the file correctly through any symlinks.
+    ///
+    /// For example, for clickable links in editor diagnostics.  Each component is percent-encoded, and Windows paths are written
+    /// as `file:///C:/...`, or `file://server/share/...` for UNC paths.  A path which is not valid Unicode is an error of kind `InvalidData`.
+    fn real_file_url(&self) -> io::Result<String>;
+
+    /// Return whether this path is physically within `base`, or is `base` itself, regardless of symlinks.
+    ///
+    /// On Windows and macOS, whose default filesystems are case-insensitive, components which differ only in case are equal.
+    fn real_starts_with<P>(&self, base: P) -> io::Result<bool>
+    where
+        P: AsRef<Path>;
+
+    /// As per `real_starts_with`, returning the remainder of the path below `base`, or `None` if it is not within `base`.
+    ///
+    /// The remainder is in terms of the cleaned path where possible, and otherwise in terms of the canonical path,
+    /// where symlinks have obscured the relationship.
+    fn real_strip_prefix<P>(&self, base: P) -> io::Result<Option<PathBuf>>
+    where
+        P: AsRef<Path>;
+
+    /// Return whether this path is physically within the user's home directory, `$HOME`, or `%USERPROFILE%` on Windows,
+    /// as per `real_starts_with`, for example for a tool which refuses to operate elsewhere.
+    ///
+    /// Since it is where the path leads that matters, a trailing symlink is followed, so a symlink within home to somewhere else
+    /// is not within home, and a symlink elsewhere to within home is.  Likewise the home directory may itself be a symlink.
+    /// A missing path is within home if its nearest existing ancestor is, and any dangling symlink means the path is not within home,
+    /// since where it leads can't be determined.
+    /// It is an error of kind `InvalidInput` if the home directory is undefined.
+    fn is_within_home(&self) -> io::Result<bool>;
+
+    /// Return whether this path is physically within the system temporary directory, as per `std::env::temp_dir`,
+    /// for example for a cleanup tool which must only delete there.
+    ///
+    /// As per `is_within_home`, which explains how symlinks and missing paths are treated.  This matters on macOS,
+    /// where the temporary directory is below `/var`, which is a symlink to `/private/var`, so a naive `starts_with` fails.
+    fn is_within_temp(&self) -> io::Result<bool>;
+
+    /// Return this path relative to the root of the git repository containing it, or `None` if it is not within a repository.
+    ///
+    /// For example, for a developer tool displaying repository-relative paths.  The root is the nearest of the `real_ancestors`,
+    /// beginning with the `real_parent` for a file, containing a `.git` entry, which may be a directory, or a file as for worktrees and submodules, see `Resolver::find_upwards`.
+    /// The path is then expressed relative to the root as per `real_strip_prefix`, with the root itself being `.`.
+    fn real_repo_relative(&self) -> io::Result<Option<PathBuf>>;
+
+    /// Return whether this path and `other` refer to the same file or directory once all symlinks are followed.
+    fn real_same_file<P>(&self, other: P) -> io::Result<bool>
+    where
+        P: AsRef<Path>;
+
+    /// Return a key identifying the file or directory this path refers to once all symlinks are followed,
+    /// so that keys for different spellings of the same path are equal, as per `real_same_file`.
+    ///
+    /// For example, to deduplicate references to files in a `HashSet<RealKey>`.
+    fn real_key(&self) -> io::Result<RealKey>;
+
+    /// Return the key of the real directory containing this path, that is, the `real_key` of its `real_parent`,
+    /// so that paths in the same directory have equal keys however they are reached.
+    ///
+    /// For example, for a build system caching per directory.  Where the path is a symlink, the directory is that of its target.
+    fn real_dir_key(&self) -> io::Result<RealKey>;
+
+    /// Return the number of hard links to the file or directory this path refers to once all symlinks are followed,
+    /// that is, `st_nlink` on Unix, and the number of links from `GetFileInformationByHandle` on Windows.
+    ///
+    /// For example, for a deduplication tool, alongside `real_key`, where a count greater than one means the file is also reachable
+    /// by some other path.
+    fn real_hardlink_count(&self) -> io::Result<u64>;
+
+    /// If this path is a symlink, return its ultimate target, following every hop, otherwise `None`.
+    ///
+    /// The target is expressed relative to the directory containing the symlink where possible, using `..` as required,
+    /// and is otherwise as found, for example an absolute target.
+    /// Unlike `Path::read_link` all hops are followed, and unlike `Path::canonicalize` the result is minimal.
+    fn real_link_target(&self) -> io::Result<Option<PathBuf>>;
+
+    /// Return whether the current process has the access in `mode` to this path once cleaned as per `real_clean`,
+    /// where lack of permission is not an error, but a missing path is.
+    ///
+    /// This avoids a separate resolution and check, and ensures that the check applies to the same target as resolution.
+    /// On Unix this is as per `faccessat`, where a read-only filesystem also denies write access.
+    /// On Windows it is a best-effort check of the read-only attribute, with only directories
+    /// and files with the usual executable extensions deemed executable.
+    #[cfg(feature = "access")]
+    fn real_access(&self, mode: AccessMode) -> io::Result<bool>;
+
+    /// Return how many real parent steps from this path reach `ancestor`, or `None` if it is not a real ancestor.
+    ///
+    /// For example, for the number of `../` required in a relative URL, without building the relative path.
+    /// The `real_ancestors` are compared with `ancestor` by identity, as per `real_same_file`, stopping at the first match.
+    /// The path itself is its own ancestor, at depth zero.
+    fn real_updepth_to<P>(&self, ancestor: P) -> io::Result<Option<usize>>
+    where
+        P: AsRef<Path>;
+
     /// Return whether this is a path to the root directory, regardless of whether or not it is relative or contains symlinks.
     /// Empty path is treated as `.`, that is, current directory, for compatibility with `Path::parent`.
     fn is_real_root(&self) -> io::Result<bool>;
+
+    /// Iterate over the real ancestors of this path, starting with the path itself and ending with the root directory.
+    ///
+    /// Each ancestor is the `real_parent` of the previous one, so unlike `Path::ancestors`, relative paths ascend beyond their first component.
+    fn real_ancestors(&self) -> RealAncestors<Resolver>;
+
+    /// As per `real_ancestors`, but with the metadata of each ancestor, for example for a disk usage tool walking upwards.
+    ///
+    /// The metadata is as per `fs::metadata`, that is, following symlinks, so where the path itself is a symlink,
+    /// this is the metadata of its target.  Every subsequent ancestor is a real directory, so there is no symlink to follow.
+    /// Failure to read the metadata is an error for that ancestor, which does not end the iteration.
+    fn real_ancestors_with_metadata(&self)
+        -> impl Iterator<Item = io::Result<(PathBuf, Metadata)>>;
+
+    /// Return breadcrumbs for this path, from the root directory down to the path itself,
+    /// each being the name of a component paired with the real path up to and including it.
+    ///
+    /// These are the `real_ancestors` of `real_absolute` in reverse, so each path is physically valid,
+    /// being the real parent of the next, with symlinked directories resolved as required.
+    /// The name of the root directory is the whole of its path.
+    fn real_breadcrumbs(&self) -> io::Result<Vec<(OsString, PathBuf)>>;
 }
 
 fn empty_to_dot(p: PathBuf) -> PathBuf {
@@ -39,111 +288,2019 @@ fn empty_to_dot(p: PathBuf) -> PathBuf {
     } else {
         p
     }
-}
+}
+
+impl PathExt for Path {
+    fn real_parent(&self) -> io::Result<PathBuf> {
+        let mut real_path = RealPath::default();
+        real_path
+            .parent(self)
+            .map(empty_to_dot)
+            .map_err(|e| e.resolving(self).into())
+    }
+
+    fn real_parent_or_lexical(&self) -> PathBuf {
+        self.real_parent().unwrap_or_else(|_| lexical_parent(self))
+    }
+
+    fn real_parent_opt(&self) -> io::Result<Option<PathBuf>> {
+        Resolver::new()
+            .root_parent(RootParentPolicy::None)
+            .real_parent_opt(self)
+    }
+
+    fn real_parent_classified(&self) -> io::Result<RealParent> {
+        let parent = self.real_parent()?;
+
+        // only the root directory is its own parent, so the path itself need be examined only if its parent is the root
+        let is_root = parent.is_real_root()?
+            && match self.is_real_root() {
+                Ok(is_root) => is_root,
+                Err(e) if is_not_found(&e) => false,
+                Err(e) => return Err(e),
+            };
+
+        // the real parent contains no symlinks, so may be normalized lexically
+        Ok(if is_root {
+            RealParent::RootItself(parent)
+        } else if parent.is_relative()
+            && leading_dotdots(&lexical_normalize(&parent))
+                > leading_dotdots(&lexical_normalize(self))
+        {
+            RealParent::AboveRoot(parent)
+        } else {
+            RealParent::Parent(parent)
+        })
+    }
+
+    fn try_real_parent(&self) -> Result<Option<PathBuf>, RealParentError> {
+        let mut real_path = RealPath::default();
+        let parent = match real_path.parent(self) {
+            Ok(parent) => empty_to_dot(parent),
+            Err(e) if e.is_not_found() => return Ok(None),
+            Err(e) => return Err(e.resolving(self).into()),
+        };
+
+        // resolution succeeds for a missing final component, so check that exists too
+        let path = if self.as_os_str().is_empty() {
+            Path::new(DOT)
+        } else {
+            self
+        };
+        match path.symlink_metadata() {
+            Ok(_) => Ok(Some(parent)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::IO(e, path.to_path_buf()).into()),
+        }
+    }
+
+    fn real_clean(&self) -> io::Result<PathBuf> {
+        let mut real_path = RealPath::default();
+        real_path
+            .clean(self)
+            .map(empty_to_dot)
+            .map_err(|e| e.resolving(self).into())
+    }
+
+    fn try_real_clean_raw(&self) -> io::Result<PathBuf> {
+        let mut real_path = RealPath::default();
+        real_path.clean(self).map_err(|e| e.resolving(self).into())
+    }
+
+    fn real_clean_diff(&self) -> io::Result<(PathBuf, CleanChanges)> {
+        let mut real_path = RealPath::default();
+        let clean = real_path
+            .clean(self)
+            .map(empty_to_dot)
+            .map_err(|e| io::Error::from(e.resolving(self)))?;
+
+        // a lone dot may be all that remains
+        let dots_retained = usize::from(clean.as_os_str() == DOT);
+        let changes = CleanChanges {
+            dots_removed: count_dots(self).saturating_sub(dots_retained),
+            changed: clean.as_os_str() != self.as_os_str(),
+            ..real_path.changes
+        };
+
+        Ok((clean, changes))
+    }
+
+    fn is_real_minimal(&self) -> io::Result<bool> {
+        // Cleaning touches the filesystem only to resolve a `..` which follows some other component,
+        // and that is always a change, so the answer is found without ever doing so.
+        Ok(is_minimal_lexically(self))
+    }
+
+    fn real_existing_prefix(&self) -> io::Result<(PathBuf, PathBuf)> {
+        let mut real_path = RealPath::default();
+        real_path
+            .existing_prefix(self)
+            .map(|(existing, tail)| (empty_to_dot(existing), tail))
+            .map_err(|e| e.resolving(self).into())
+    }
+
+    fn real_symlink_free_prefix(&self) -> io::Result<(PathBuf, PathBuf)> {
+        let real_path = RealPath::default();
+        real_path
+            .symlink_free_prefix(self)
+            .map(|(prefix, tail)| (empty_to_dot(prefix), tail))
+            .map_err(|e| e.resolving(self).into())
+    }
+
+    fn real_anchor(&self) -> io::Result<PathBuf> {
+        // light clean, so that a trailing dot is not mistaken for the final component
+        let path = self.components().collect::<PathBuf>();
+
+        let anchor = match path.components().next_back() {
+            Some(Component::Normal(leaf)) => {
+                // unwrap is safe because the last path component is normal
+                let dir = empty_to_dot(path.parent().unwrap().to_path_buf());
+                dir.canonicalize()
+                    .with_path_context(&dir)
+                    .map(|dir| dir.join(leaf))
+            }
+            _ => {
+                let path = empty_to_dot(path);
+                path.canonicalize().with_path_context(&path)
+            }
+        };
+
+        anchor.map_err(|e| e.resolving(self).into())
+    }
+
+    fn real_file_url(&self) -> io::Result<String> {
+        let absolute = self.real_absolute()?;
+        file_url(&absolute).map_err(|e| e.resolving(self).into())
+    }
+
+    fn real_absolute(&self) -> io::Result<PathBuf> {
+        if self.as_os_str().is_empty() {
+            // whatever error the standard library reports
+            return std::path::absolute(self);
+        }
+
+        let path = if self.is_absolute() {
+            self.into()
+        } else {
+            Cow::Owned(std::env::current_dir()?.join(self))
+        };
+        let mut real_path = RealPath::default();
+        let clean = real_path
+            .clean(&path)
+            .map_err(|e| Into::<io::Error>::into(e.resolving(self)))?;
+
+        // a no-op, except for a Windows path relative to the current directory on some other drive
+        std::path::absolute(clean)
+    }
+
+    fn real_starts_with<P>(&self, base: P) -> io::Result<bool>
+    where
+        P: AsRef<Path>,
+    {
+        self.real_strip_prefix(base).map(|rest| rest.is_some())
+    }
+
+    fn real_strip_prefix<P>(&self, base: P) -> io::Result<Option<PathBuf>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = self.real_clean()?;
+        let base = base.as_ref().real_clean()?;
+
+        if let Some(rest) = strip_prefix_folded(&path, &base) {
+            return Ok(Some(rest));
+        }
+
+        // symlinks or a mixture of relative and absolute paths may obscure the relationship,
+        // which only exists in any case if both paths exist
+        match (path.canonicalize(), base.canonicalize()) {
+            (Ok(path), Ok(base)) => Ok(strip_prefix_folded(&path, &base)),
+            (Err(e), _) | (_, Err(e)) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            (Err(e), _) | (_, Err(e)) => Err(e),
+        }
+    }
+
+    fn is_within_home(&self) -> io::Result<bool> {
+        let home = env::home_dir(|name| std::env::var_os(name))?;
+        is_within(self, &home)
+    }
+
+    fn is_within_temp(&self) -> io::Result<bool> {
+        is_within(self, &std::env::temp_dir())
+    }
+
+    fn real_repo_relative(&self) -> io::Result<Option<PathBuf>> {
+        // a file can't contain the entry, so the search begins with its directory
+        let dir = if self.is_dir() {
+            Cow::Borrowed(self)
+        } else {
+            Cow::Owned(self.real_parent()?)
+        };
+        let Some(git) = Resolver::new().find_upwards(&dir, GIT)? else {
+            return Ok(None);
+        };
+
+        // unwrap is safe because the entry was found within some ancestor
+        let root = git.parent().unwrap();
+        self.real_strip_prefix(root)
+            .map(|rest| rest.map(empty_to_dot))
+    }
+
+    fn real_same_file<P>(&self, other: P) -> io::Result<bool>
+    where
+        P: AsRef<Path>,
+    {
+        is_same_file(self, other.as_ref())
+    }
+
+    fn real_key(&self) -> io::Result<RealKey> {
+        real_key(self).map_err(|e| Error::IO(e, self.to_path_buf()).into())
+    }
+
+    fn real_dir_key(&self) -> io::Result<RealKey> {
+        self.real_parent()?.real_key()
+    }
+
+    fn real_hardlink_count(&self) -> io::Result<u64> {
+        let clean = self.real_clean()?;
+        hardlink_count(&clean)
+            .with_path_context(&clean)
+            .map_err(|e| e.resolving(self).into())
+    }
+
+    fn real_link_target(&self) -> io::Result<Option<PathBuf>> {
+        let mut real_path = RealPath::default();
+        real_path
+            .link_target(self)
+            .map_err(|e| e.resolving(self).into())
+    }
+
+    #[cfg(feature = "access")]
+    fn real_access(&self, mode: AccessMode) -> io::Result<bool> {
+        let clean = self.real_clean()?;
+        access::has_access(&clean, mode)
+            .with_path_context(&clean)
+            .map_err(|e| e.resolving(self).into())
+    }
+
+    fn real_updepth_to<P>(&self, ancestor: P) -> io::Result<Option<usize>>
+    where
+        P: AsRef<Path>,
+    {
+        let ancestor = ancestor.as_ref();
+        for (depth, path) in self.real_ancestors().enumerate() {
+            if is_same_file(&path?, ancestor)? {
+                return Ok(Some(depth));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn is_real_root(&self) -> io::Result<bool> {
+        let path = if self.as_os_str().is_empty() {
+            AsRef::<Path>::as_ref(DOT)
+        } else {
+            self
+        };
+
+        is_root_dir(path)
+    }
+
+    fn real_ancestors(&self) -> RealAncestors<Resolver> {
+        RealAncestors::new(Resolver::new(), self)
+    }
+
+    fn real_ancestors_with_metadata(
+        &self,
+    ) -> impl Iterator<Item = io::Result<(PathBuf, Metadata)>> {
+        self.real_ancestors().map(|ancestor| {
+            let path = ancestor?;
+            let metadata = path.metadata().with_path_context(&path)?;
+            Ok((path, metadata))
+        })
+    }
+
+    fn real_breadcrumbs(&self) -> io::Result<Vec<(OsString, PathBuf)>> {
+        let mut ancestors = self
+            .real_absolute()?
+            .real_ancestors()
+            .collect::<io::Result<Vec<_>>>()?;
+        ancestors.reverse();
+
+        Ok(ancestors
+            .into_iter()
+            .map(|path| {
+                let name = path.file_name().unwrap_or(path.as_os_str()).to_owned();
+                (name, path)
+            })
+            .collect())
+    }
+}
+
+// On Unix the root directory is its own parent, which we check by comparing file identity rather than canonicalizing,
+// since canonicalization fails if the canonical path would be longer than `PATH_MAX`, as for a very deep current directory.
+#[cfg(target_family = "unix")]
+fn is_root_dir(path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = path.metadata()?;
+    if !metadata.is_dir() {
+        return Ok(false);
+    }
+
+    let parent_metadata = path.join(DOTDOT).metadata()?;
+    Ok(metadata.dev() == parent_metadata.dev() && metadata.ino() == parent_metadata.ino())
+}
+
+#[cfg(not(target_family = "unix"))]
+fn is_root_dir(path: &Path) -> io::Result<bool> {
+    Ok(matches!(
+        path.canonicalize()?.components().next_back(),
+        Some(Component::RootDir)
+    ))
+}
+
+// the path with any 8.3 short names expanded to their long form, or unchanged if that fails, as for a nonexistent path
+#[cfg(target_family = "windows")]
+fn long_path_name(path: PathBuf) -> PathBuf {
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use windows_sys::Win32::Storage::FileSystem::GetLongPathNameW;
+
+    // only a path with a tilde may contain short names
+    if !path.as_os_str().encode_wide().any(|c| c == u16::from(b'~')) {
+        return path;
+    }
+
+    let short = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect::<Vec<_>>();
+    let mut long = vec![0u16; short.len()];
+
+    loop {
+        // SAFETY: `short` is nul-terminated, and `long` has the length we pass
+        let len = unsafe { GetLongPathNameW(short.as_ptr(), long.as_mut_ptr(), long.len() as u32) }
+            as usize;
+
+        if len == 0 {
+            return path;
+        } else if len < long.len() {
+            long.truncate(len);
+            return OsString::from_wide(&long).into();
+        } else {
+            // buffer too small, and len is the size required
+            long.resize(len, 0);
+        }
+    }
+}
+
+#[cfg(not(target_family = "windows"))]
+fn long_path_name(path: PathBuf) -> PathBuf {
+    path
+}
+
+#[cfg(target_family = "unix")]
+fn is_same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let (a, b) = (a.metadata()?, b.metadata()?);
+    Ok(a.dev() == b.dev() && a.ino() == b.ino())
+}
+
+#[cfg(not(target_family = "unix"))]
+fn is_same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    Ok(a.canonicalize()? == b.canonicalize()?)
+}
+
+/// A key identifying a file or directory independently of the path by which it is reached, as returned by `PathExt::real_key`.
+///
+/// On Unix this is the device and inode number, and elsewhere the canonical path.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct RealKey {
+    #[cfg(target_family = "unix")]
+    identity: FileIdentity,
+    #[cfg(not(target_family = "unix"))]
+    canonical: PathBuf,
+}
+
+#[cfg(target_family = "unix")]
+fn hardlink_count(path: &Path) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    path.metadata().map(|metadata| metadata.nlink())
+}
+
+#[cfg(target_family = "windows")]
+fn hardlink_count(path: &Path) -> io::Result<u64> {
+    use std::{
+        fs::OpenOptions,
+        os::windows::{fs::OpenOptionsExt, io::AsRawHandle},
+    };
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION, FILE_FLAG_BACKUP_SEMANTICS,
+        FILE_READ_ATTRIBUTES,
+    };
+
+    // backup semantics are required to open a directory
+    let file = OpenOptions::new()
+        .access_mode(FILE_READ_ATTRIBUTES)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)?;
+    let mut info = BY_HANDLE_FILE_INFORMATION::default();
+
+    // SAFETY: the handle is open for the duration of the call, and `info` is of the type required
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle(), &mut info) } == 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(info.nNumberOfLinks.into())
+    }
+}
+
+#[cfg(not(any(target_family = "unix", target_family = "windows")))]
+fn hardlink_count(_path: &Path) -> io::Result<u64> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+// whether path leads physically within dir, where dir may itself be a symlink, see `PathExt::is_within_home`
+fn is_within(path: &Path, dir: &Path) -> io::Result<bool> {
+    let dir = dir.canonicalize().with_path_context(dir)?;
+    let clean = path.real_clean()?;
+
+    // the rest of the path below its nearest existing ancestor doesn't exist, so can't contain a symlink
+    for ancestor in clean.ancestors() {
+        let existing = if ancestor.as_os_str().is_empty() {
+            Path::new(DOT)
+        } else {
+            ancestor
+        };
+
+        match existing.canonicalize() {
+            Ok(target) => {
+                // unwrap is safe because ancestor is an ancestor of clean
+                let rest = clean.strip_prefix(ancestor).unwrap();
+                return target.join(rest).real_starts_with(dir);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                if existing.is_symlink() {
+                    return Ok(false);
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(false)
+}
+
+#[cfg(target_family = "unix")]
+fn real_key(path: &Path) -> io::Result<RealKey> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = path.metadata()?;
+    Ok(RealKey {
+        identity: FileIdentity::new(metadata.dev(), metadata.ino()),
+    })
+}
+
+#[cfg(not(target_family = "unix"))]
+fn real_key(path: &Path) -> io::Result<RealKey> {
+    Ok(RealKey {
+        canonical: path.canonicalize()?,
+    })
+}
+
+/// As per `PathExt::real_parent` but purely lexical, without touching the filesystem.
+///
+/// The result is correct only if no component of the path is a symlink.
+/// It does however avoid the errors in `Path::parent` for `..`, empty, and single component paths.
+pub fn lexical_parent(path: &Path) -> PathBuf {
+    if path.as_os_str().is_empty() {
+        DOTDOT.into()
+    } else {
+        // light clean, as per `RealPath::parent`
+        let path = path.components().collect::<PathBuf>();
+        empty_to_dot(parent_lexically(&path).into_owned())
+    }
+}
+
+/// As per `PathExt::real_clean` but purely lexical, without touching the filesystem.
+///
+/// The result is correct only if no component of the path is a symlink.
+/// As for `real_clean`, leading `..` cannot be folded away, and the parent of the root directory is itself.
+pub fn lexical_normalize(path: &Path) -> PathBuf {
+    use Component::*;
+
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            CurDir => (),
+            ParentDir => {
+                normalized = parent_lexically(&normalized).into_owned();
+            }
+            Prefix(_) | RootDir | Normal(_) => {
+                normalized.push(component);
+            }
+        }
+    }
+
+    empty_to_dot(normalized)
+}
+
+// the parent of a path which is known not to be a symlink, and has been lightly cleaned by `components()`,
+// or empty if the parent is the current directory
+fn parent_lexically(path: &Path) -> Cow<'_, Path> {
+    match path.file_name() {
+        Some(_) => path.parent().unwrap().into(),
+
+        None => {
+            if path == AsRef::<Path>::as_ref(DOT) {
+                Into::<PathBuf>::into(DOTDOT).into()
+            } else {
+                match path.components().next_back() {
+                    None | Some(Component::ParentDir) => {
+                        // don't attempt to fold away `..` in the base path
+                        path.join(DOTDOT).into()
+                    }
+                    _ => {
+                        // parent of root dir is itself
+                        path.into()
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// As per `PathExt::real_parent`, but with relative paths resolved with respect to `base` rather than the current directory.
+///
+/// See `Resolver::with_base`.
+pub fn real_parent_in<P1, P2>(path: P1, base: P2) -> io::Result<PathBuf>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+{
+    Resolver::new().with_base(base).real_parent(path.as_ref())
+}
+
+/// Resolve `rel` with respect to each of `bases` in turn, returning the first which exists, or `None`.
+///
+/// For example, the classic search path lookup, but with `..` in `rel` resolved correctly where a base contains symlinks.
+/// A single `Resolver` is shared across the bases, see `Resolver::real_clean_in_first`.
+pub fn real_clean_in_first<P>(bases: &[PathBuf], rel: P) -> io::Result<Option<PathBuf>>
+where
+    P: AsRef<Path>,
+{
+    Resolver::new().real_clean_in_first(bases, rel.as_ref())
+}
+
+/// As per `PathExt::real_clean`, for a path given as a string in which `/` is the separator, whatever the platform.
+///
+/// For example, for paths read from a manifest which always uses `/`.  The path is converted to the native representation
+/// before resolution, with repeated and trailing separators ignored, and a leading separator denoting the root directory.
+/// Note that this concerns only the interpretation of the input, and that on Windows a backslash remains a separator.
+pub fn real_clean_from_unix_str(s: &str) -> io::Result<PathBuf> {
+    from_unix_str(s).real_clean()
+}
+
+/// As per `PathExt::real_clean`, for `path` relative to the directory containing the real current executable.
+///
+/// For example, to locate assets installed alongside a binary, where the binary is invoked via a symlink,
+/// since the directory is the `real_parent` of `std::env::current_exe`, which therefore follows any such symlink.
+/// If `path` is absolute, it is cleaned as is.
+pub fn real_clean_from_exe_dir<P>(path: P) -> io::Result<PathBuf>
+where
+    P: AsRef<Path>,
+{
+    let exe_dir = std::env::current_exe()?.real_parent()?;
+    exe_dir.join(path).real_clean()
+}
+
+/// Clean each entry of a list of paths such as `PATH`, separated by `sep`, such as `:`, or `;` on Windows,
+/// dropping any entry which is the same directory as an earlier one, as per `PathExt::real_clean`.
+///
+/// For example, to sanitize `PATH`.  All entries are cleaned by a single `Resolver`.  An entry which fails to resolve is returned as an error
+/// in its place in the list, so that the caller may decide what to do about it.  As for `PATH`, an empty entry means the current directory.
+/// A separator which is not ASCII is an error of kind `InvalidInput`.
+pub fn real_clean_path_list(list: &OsStr, sep: char) -> io::Result<Vec<io::Result<PathBuf>>> {
+    if !sep.is_ascii() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("path list separator {:?} is not ASCII", sep),
+        ));
+    }
+
+    let mut resolver = Resolver::new();
+    let mut cleaned = Vec::<io::Result<PathBuf>>::new();
+    // entries so far, both as cleaned and by identity where they exist, so that each is compared against all in constant time
+    let mut paths = HashSet::new();
+    let mut keys = HashSet::new();
+
+    for entry in list.as_encoded_bytes().split(|b| *b == sep as u8) {
+        // SAFETY: splitting on an ASCII byte leaves valid encoded bytes either side
+        let entry = Path::new(unsafe { OsStr::from_encoded_bytes_unchecked(entry) });
+        let result = resolver.real_clean(entry);
+
+        if let Ok(path) = &result {
+            let is_duplicate =
+                !paths.insert(path.clone()) || real_key(path).is_ok_and(|key| !keys.insert(key));
+            if is_duplicate {
+                continue;
+            }
+        }
+
+        cleaned.push(result);
+    }
+
+    Ok(cleaned)
+}
+
+fn from_unix_str(s: &str) -> PathBuf {
+    let mut path = PathBuf::new();
+    if s.starts_with('/') {
+        path.push(std::path::MAIN_SEPARATOR_STR);
+    }
+    for component in s.split('/').filter(|component| !component.is_empty()) {
+        path.push(component);
+    }
+
+    path
+}
+
+/// Resolve `path` relative to the directory `base`, returning a fully real absolute path, as per `Path::canonicalize`,
+/// which must be within `base`, without ever consulting the current directory.
+///
+/// For example, to resolve a path supplied by a client of a file server with respect to the root of what is served.
+/// Both `base` and `path` may contain symlinks, and must exist.
+/// It is an error of kind `InvalidInput` if `base` is relative, and of kind `PermissionDenied` if `path` is absolute,
+/// or resolves to anywhere outside of `base`, whether by `..` or by symlink.
+pub fn real_resolve_abs<P1, P2>(base: P1, path: P2) -> io::Result<PathBuf>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+{
+    let (base, path) = (base.as_ref(), path.as_ref());
+
+    if !base.is_absolute() {
+        return Err(Error::NotAbsolute(base.to_path_buf()).into());
+    }
+    if path.has_root() {
+        return Err(Error::Escape(path.to_path_buf()).into());
+    }
+
+    let real_base = base.canonicalize()?;
+    let resolved = real_base.join(path).canonicalize()?;
+
+    if resolved.starts_with(&real_base) {
+        Ok(resolved)
+    } else {
+        Err(Error::Escape(path.to_path_buf()).into())
+    }
+}
+
+/// Return the target for a new symlink in `new_dir` which refers to the same file as the existing symlink `link`,
+/// for example to copy a symlink elsewhere while preserving its relative nature.
+///
+/// The target is the ultimate target of `link`, as per `PathExt::real_link_target`, re-expressed relative to `new_dir`,
+/// ascending from it via its real parents as required.
+/// If the target of `link` itself is absolute, it is returned unchanged.
+/// It is an error of kind `InvalidInput` if `link` is not a symlink.
+pub fn real_retarget_symlink<P1, P2>(link: P1, new_dir: P2) -> io::Result<PathBuf>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+{
+    let link = link.as_ref();
+    let mut real_path = RealPath::default();
+    real_path
+        .retarget(link, new_dir.as_ref())
+        .map_err(|e| e.resolving(link).into())
+}
+
+/// Return the target to pass to `std::os::unix::fs::symlink` or its Windows equivalents, to create a new symlink at `link`
+/// referring to `target`, relative to the directory which is to contain the link where possible.
+///
+/// The target is cleaned as per `real_clean`, and re-expressed relative to the real directory containing `link`,
+/// ascending from it via its real parents as required, as the operating system will when following the link.
+/// Since `link` is yet to be created, or is to be replaced, any existing symlink at `link` itself is not followed.
+/// Where there is no common ancestor, as for different drives on Windows, the target is absolute.
+/// It is an error of kind `InvalidInput` if `link` has no file name.
+pub fn real_symlink_target_for<P1, P2>(link: P1, target: P2) -> io::Result<PathBuf>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+{
+    let link = link.as_ref();
+    let mut real_path = RealPath::default();
+    real_path
+        .symlink_target_for(link, target.as_ref())
+        .map_err(|e| e.resolving(link).into())
+}
+
+/// Return those of `candidates` which are symlinks ultimately referring to `target`, following every hop.
+///
+/// For example, to report all the links to a directory which is about to be removed.
+/// Candidates which are not symlinks, or which are dangling, are excluded.
+pub fn real_aliases_of<P>(target: P, candidates: &[PathBuf]) -> io::Result<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+{
+    let target = target.as_ref();
+    let mut aliases = Vec::new();
+
+    for candidate in candidates {
+        let is_symlink = match candidate.symlink_metadata() {
+            Ok(metadata) => metadata.is_symlink(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+            Err(e) => return Err(e),
+        };
+
+        let is_alias = is_symlink
+            && match candidate.real_same_file(target) {
+                Ok(same) => same,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+                Err(e) => return Err(e),
+            };
+
+        if is_alias {
+            aliases.push(candidate.clone());
+        }
+    }
+
+    Ok(aliases)
+}
+
+/// Expand the glob `pattern`, returning each match paired with its real parent, resolved through a single `Resolver`.
+///
+/// Matches which are symlinks have the real parent of their target, which may differ from their lexical parent.
+/// An invalid pattern is an error of kind `InvalidInput`.
+#[cfg(feature = "glob")]
+pub fn real_parents_of_glob(pattern: &str) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let matches =
+        glob::glob(pattern).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut resolver = Resolver::new();
+
+    matches
+        .map(|path| {
+            let path = path.map_err(io::Error::from)?;
+            let parent = resolver.real_parent(&path)?;
+            Ok((path, parent))
+        })
+        .collect()
+}
+
+/// Read paths line by line from `input`, writing the real parent of each to `output`, resolved using `resolver`.
+///
+/// Lines are paths as raw bytes, so on Unix need not be valid UTF-8, and are terminated by `\n` or `\r\n`.
+/// A line which fails to resolve, or elsewhere than Unix is not valid UTF-8, results in `DIRNAME_ERROR_MARKER`
+/// followed by the error message on a single line, so that a single bad line doesn't abort the whole stream.
+/// Other I/O errors are returned.
+pub fn real_dirname_lines<R, W>(
+    mut input: R,
+    mut output: W,
+    resolver: &mut Resolver,
+) -> io::Result<()>
+where
+    R: BufRead,
+    W: Write,
+{
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if input.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        let line = line
+            .strip_suffix(b"\n")
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .unwrap_or(&line);
+
+        let parent = match path_from_bytes(line) {
+            Some(path) => resolver.real_parent(&path),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "path is not valid UTF-8",
+            )),
+        };
+
+        match parent {
+            Ok(parent) => output.write_all(parent.as_os_str().as_encoded_bytes())?,
+            Err(e) => {
+                output.write_all(DIRNAME_ERROR_MARKER.as_bytes())?;
+                output.write_all(e.to_string().replace('\n', " ").as_bytes())?;
+            }
+        }
+        output.write_all(b"\n")?;
+    }
+
+    output.flush()
+}
+
+/// The start of a line written by `real_dirname_lines` for a path which failed to resolve, and is followed by the error message.
+///
+/// This begins with a NUL byte, which may never appear in a path, so cannot be mistaken for a result.
+pub const DIRNAME_ERROR_MARKER: &str = "\0error: ";
+
+// a path from its raw bytes, where only on Unix may these be other than UTF-8
+#[cfg(target_family = "unix")]
+fn path_from_bytes(bytes: &[u8]) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Some(OsStr::from_bytes(bytes).into())
+}
+
+#[cfg(not(target_family = "unix"))]
+fn path_from_bytes(bytes: &[u8]) -> Option<PathBuf> {
+    std::str::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/// Summary of what `real_clean_diff` changed in cleaning a path.
+///
+/// Counts include any `..` folded or symlinks resolved while following symlink targets.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CleanChanges {
+    /// Number of `..` components folded away.
+    pub dotdots_folded: usize,
+    /// Number of `.` components removed.
+    pub dots_removed: usize,
+    /// Number of symlinks resolved in order to fold away `..`.
+    pub symlinks_resolved: usize,
+    /// Whether the cleaned path differs at all from the original.
+    pub changed: bool,
+}
+
+/// A single step in the resolution of a path, as returned by `Resolver::resolve_trace`, one for each component of the path.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ResolvedStep {
+    /// The component of the input path.
+    pub input_component: OsString,
+    /// The path resolved so far, cleaned as per `real_clean`, so including this component unless it was `.` or `..`.
+    pub resolved_to: PathBuf,
+    /// Whether this component is a symlink.
+    pub was_symlink: bool,
+    /// The target of the symlink, if it is one.
+    pub target: Option<PathBuf>,
+}
+
+/// The cost of resolving a path, as returned by `Resolver::real_parent_metered`, for example for telemetry.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ResolveStats {
+    /// Number of symlinks followed.
+    pub symlink_hops: usize,
+    /// Number of filesystem entries examined in finding parents.
+    pub dirs_stated: usize,
+    /// Number of `..` components resolved against the filesystem, whether or not they could be folded away.
+    pub dotdots_resolved: usize,
+}
+
+/// The real parent of a path, classified, as returned by `PathExt::real_parent_classified`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RealParent {
+    /// The parent, within the tree of a relative path, or anywhere below the root for an absolute path.
+    Parent(PathBuf),
+    /// The parent of a relative path which has ascended above where the path is relative to, that is,
+    /// once cleaned it has more leading `..` than the path itself, cleaned lexically,
+    /// for example the parent of `.`, `..`, or of a symlink to `../x`.
+    AboveRoot(PathBuf),
+    /// The parent of the root directory, which is the root directory itself, however the path reaches it, as per `PathExt::is_real_root`.
+    RootItself(PathBuf),
+}
+
+/// A genuine failure to resolve the real parent, as returned by `PathExt::try_real_parent`,
+/// where a missing path is not considered a failure.
+#[derive(Debug)]
+pub enum RealParentError {
+    /// A symlink cycle, detected at the given path.
+    Cycle(PathBuf),
+    /// Any other failure, for example permission denied, with the path which failed included in the error.
+    Io(io::Error),
+}
+
+impl Display for RealParentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RealParentError::Cycle(path) => {
+                write!(f, "symlink cycle detected at {}", path.to_string_lossy())
+            }
+            RealParentError::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for RealParentError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RealParentError::Cycle(_) => None,
+            RealParentError::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<Error> for RealParentError {
+    fn from(e: Error) -> Self {
+        match e.cycle() {
+            Some(path) => RealParentError::Cycle(path.to_path_buf()),
+            None => RealParentError::Io(e.into()),
+        }
+    }
+}
+
+impl From<RealParentError> for io::Error {
+    fn from(e: RealParentError) -> Self {
+        match e {
+            RealParentError::Cycle(_) => io::Error::other(e),
+            RealParentError::Io(e) => e,
+        }
+    }
+}
+
+/// The details of resolving the real parent of a path, as returned by `Resolver::resolve_detailed`,
+/// for example to answer a client of a service in a single payload.
+///
+/// With the `serde` feature, this is serializable.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Resolution {
+    /// The path as given.
+    pub input: PathBuf,
+    /// The real parent, as per `Resolver::real_parent`.
+    pub real_parent: PathBuf,
+    /// Whether any symlink was followed.
+    pub crossed_symlink: bool,
+    /// Number of symlinks followed.
+    pub hops: usize,
+    /// Whether the real parent is on a different filesystem from the directory lexically containing the path,
+    /// for example so that a backup tool may decline to descend across mounts.
+    ///
+    /// On Unix this compares the device (`st_dev`) of the two directories.  Elsewhere, and for any filesystem
+    /// which doesn't provide directory identities, this is always false.
+    pub crossed_mount: bool,
+    /// Whether any reparse point was followed.
+    ///
+    /// On Windows, symlinks, junctions, and mounted folders are all reparse points, so this is true whenever
+    /// any symlink was followed.  Unix has no reparse points, so this is always false there.
+    pub crossed_reparse: bool,
+}
+
+/// What cleaning a path would involve, as far as can be determined without touching the filesystem,
+/// as returned by `Resolver::explain_lexical`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Explanation {
+    /// The path normalized as per `lexical_normalize`, which is correct only if none of `maybe_symlinks` is in fact a symlink.
+    pub normalized: PathBuf,
+    /// Number of `.` components removed.
+    pub dots_removed: usize,
+    /// Number of `..` components folded away lexically.
+    pub dotdots_folded: usize,
+    /// Each prefix of the path which is followed by `..`, and so would have to be checked on the filesystem,
+    /// since if it is a symlink the `..` refers to the parent of its target.
+    pub maybe_symlinks: Vec<PathBuf>,
+}
+
+impl Explanation {
+    /// Whether the path relies on symlink resolution, that is, whether its lexical normalization may be wrong.
+    pub fn requires_io(&self) -> bool {
+        !self.maybe_symlinks.is_empty()
+    }
+}
+
+// explain what lexical normalization does to a path, noting any components which may be symlinks
+fn explain_lexically(path: &Path) -> Explanation {
+    use Component::*;
+
+    let mut normalized = PathBuf::new();
+    let mut explanation = Explanation {
+        normalized: PathBuf::new(),
+        dots_removed: 0,
+        dotdots_folded: 0,
+        maybe_symlinks: Vec::new(),
+    };
+
+    for component in path.components() {
+        match component {
+            CurDir => (),
+            ParentDir => {
+                match normalized.components().next_back() {
+                    Some(Normal(_)) => {
+                        explanation.maybe_symlinks.push(normalized.clone());
+                        explanation.dotdots_folded += 1;
+                    }
+                    // the parent of the root directory is itself
+                    Some(Prefix(_) | RootDir) => explanation.dotdots_folded += 1,
+                    _ => (),
+                }
+                normalized = parent_lexically(&normalized).into_owned();
+            }
+            Prefix(_) | RootDir | Normal(_) => {
+                normalized.push(component);
+            }
+        }
+    }
+
+    explanation.normalized = empty_to_dot(normalized);
+    // as for `real_clean_diff`, a lone dot may be all that remains
+    let dots_retained = usize::from(explanation.normalized.as_os_str() == DOT);
+    explanation.dots_removed = count_dots(path).saturating_sub(dots_retained);
+    explanation
+}
+
+// whether a path is one of the Linux magic symlinks for open files, `/proc/<pid>/fd/<n>` or `/proc/<pid>/task/<tid>/fd/<n>`,
+// whose targets may not be paths at all
+#[cfg(target_os = "linux")]
+fn is_magic_link(path: &Path) -> bool {
+    let names = path
+        .components()
+        .map(Component::as_os_str)
+        .collect::<Vec<_>>();
+    let is_proc = |root: &OsStr, proc: &OsStr| root == "/" && proc == "proc";
+
+    match names.as_slice() {
+        [root, proc, _pid, fd, _n] => is_proc(root, proc) && *fd == "fd",
+        [root, proc, _pid, task, _tid, fd, _n] => {
+            is_proc(root, proc) && *task == "task" && *fd == "fd"
+        }
+        _ => false,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_magic_link(_path: &Path) -> bool {
+    false
+}
+
+// whether the target of a magic symlink is marked as deleted
+fn is_deleted_target(target: &Path) -> bool {
+    target
+        .as_os_str()
+        .as_encoded_bytes()
+        .ends_with(DELETED_SUFFIX.as_bytes())
+}
+
+const DELETED_SUFFIX: &str = " (deleted)";
+
+// the directory containing a symlink, where the root directory or a bare prefix, having no final name, can't be a symlink,
+// whatever the filesystem may claim
+fn symlink_dir(path: &Path) -> Result<PathBuf, Error> {
+    match path.parent() {
+        Some(dir) if path.file_name().is_some() => Ok(dir.to_path_buf()),
+        _ => Err(Error::NotSymlink(path.to_path_buf())),
+    }
+}
+
+// check that no file name in path contains a character which the platform never permits there,
+// so that the error is clear, rather than whatever some syscall would make of it
+fn check_legal(path: &Path) -> Result<(), Error> {
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            continue;
+        };
+        if let Some(c) = name
+            .as_encoded_bytes()
+            .iter()
+            .find(|c| ILLEGAL_CHARS.contains(c))
+        {
+            let reason = if *c == 0 {
+                "contains a NUL character".to_string()
+            } else {
+                format!("contains `{}`, which is reserved", *c as char)
+            };
+            return Err(Error::InvalidPath {
+                path: path.to_path_buf(),
+                reason,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// whether a path is absolute and ends in a normal component, with no trailing dot or separator,
+// either of which would cause `symlink_metadata()` to follow a trailing symlink
+fn is_plain_absolute(path: &Path) -> bool {
+    let leaf = path
+        .as_os_str()
+        .as_encoded_bytes()
+        .rsplit(|b| std::path::is_separator(*b as char))
+        .next();
+
+    path.is_absolute()
+        && !matches!(leaf, None | Some(b"" | b"." | b".."))
+        && matches!(path.components().next_back(), Some(Component::Normal(_)))
+}
+
+// the kind of prefix of a path, which only exists on Windows
+fn prefix_kind(path: &Path) -> Option<Prefix<'_>> {
+    match path.components().next() {
+        Some(Component::Prefix(prefix)) => Some(prefix.kind()),
+        _ => None,
+    }
+}
+
+fn is_verbatim(path: &Path) -> bool {
+    prefix_kind(path).is_some_and(|prefix| prefix.is_verbatim())
+}
+
+fn is_device_namespace(path: &Path) -> bool {
+    matches!(prefix_kind(path), Some(Prefix::DeviceNS(_)))
+}
+
+// as per `Path::strip_prefix`, but ignoring case on platforms whose default filesystems are case-insensitive
+fn strip_prefix_folded(path: &Path, base: &Path) -> Option<PathBuf> {
+    let mut components = path.components();
+
+    for base_component in base.components() {
+        match components.next() {
+            Some(component) if eq_folded(component, base_component) => (),
+            _ => return None,
+        }
+    }
+
+    Some(components.as_path().to_path_buf())
+}
+
+#[cfg(any(target_family = "windows", target_os = "macos"))]
+fn eq_folded(a: Component, b: Component) -> bool {
+    a == b
+        || a.as_os_str().to_string_lossy().to_lowercase()
+            == b.as_os_str().to_string_lossy().to_lowercase()
+}
+
+#[cfg(not(any(target_family = "windows", target_os = "macos")))]
+fn eq_folded(a: Component, b: Component) -> bool {
+    a == b
+}
+
+// the path with each component in its on-disk case, as found by reading its directory,
+// where a component which cannot be found, or whose directory cannot be read, is left as is
+#[cfg(any(target_family = "windows", target_os = "macos"))]
+fn case_corrected(path: PathBuf) -> PathBuf {
+    let mut corrected = PathBuf::new();
+
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            corrected.push(component);
+            continue;
+        };
+
+        let names = std::fs::read_dir(empty_to_dot(corrected.clone()))
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.file_name())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        // an exact match is preferred, in case the directory is in fact case-sensitive
+        let on_disk = names.iter().find(|on_disk| *on_disk == name).or_else(|| {
+            names
+                .iter()
+                .find(|on_disk| eq_folded(Component::Normal(on_disk), component))
+        });
+
+        corrected.push(on_disk.map(OsString::as_os_str).unwrap_or(name));
+    }
+
+    corrected
+}
+
+#[cfg(not(any(target_family = "windows", target_os = "macos")))]
+fn case_corrected(path: PathBuf) -> PathBuf {
+    path
+}
+
+// the `file://` URL for an absolute path
+fn file_url(path: &Path) -> Result<String, Error> {
+    let mut url = String::from("file://");
+
+    for component in path.components() {
+        match component {
+            Component::Prefix(prefix) => match prefix.kind() {
+                Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => {
+                    url.push('/');
+                    url.push(letter as char);
+                    url.push(':');
+                }
+                Prefix::UNC(server, share) | Prefix::VerbatimUNC(server, share) => {
+                    url.push_str(&percent_encoded(server, path)?);
+                    url.push('/');
+                    url.push_str(&percent_encoded(share, path)?);
+                }
+                Prefix::Verbatim(_) | Prefix::DeviceNS(_) => {
+                    return Err(Error::NoFileUrl(path.to_path_buf()))
+                }
+            },
+            Component::RootDir => url.push('/'),
+            Component::CurDir | Component::ParentDir | Component::Normal(_) => {
+                if !url.ends_with('/') {
+                    url.push('/');
+                }
+                url.push_str(&percent_encoded(component.as_os_str(), path)?);
+            }
+        }
+    }
+
+    Ok(url)
+}
+
+// a URL path segment, with all but unreserved characters percent-encoded, for a component of path
+fn percent_encoded(segment: &OsStr, path: &Path) -> Result<String, Error> {
+    let segment = segment
+        .to_str()
+        .ok_or_else(|| Error::NotUnicode(path.to_path_buf()))?;
+
+    Ok(segment
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect())
+}
+
+// if a path is the root directory followed only by `.` or `..` components, the root directory
+fn root_with_dots(path: &Path) -> Option<PathBuf> {
+    use Component::*;
+
+    // checked before building the root, since this is called for every `..` resolved
+    if !path.has_root() || path.components().any(|c| matches!(c, Normal(_))) {
+        return None;
+    }
+
+    Some(
+        path.components()
+            .filter(|c| matches!(c, Prefix(_) | RootDir))
+            .collect(),
+    )
+}
+
+// the number of leading `..` components of a path
+fn leading_dotdots(path: &Path) -> usize {
+    path.components()
+        .take_while(|c| *c == Component::ParentDir)
+        .count()
+}
+
+// whether a path contains any `.` or `..` components, noting that `Path::components` yields `.` only in some cases
+fn has_dots(path: &Path) -> bool {
+    path.components()
+        .any(|c| matches!(c, Component::CurDir | Component::ParentDir))
+}
+
+// count the `.` components in a path, which are mostly invisible to `Path::components`
+fn count_dots(path: &Path) -> usize {
+    path.as_os_str()
+        .as_encoded_bytes()
+        .split(|b| std::path::is_separator(*b as char))
+        .filter(|c| *c == DOT.as_bytes())
+        .count()
+}
+
+// whether cleaning would leave the path unchanged, deciding on the first change found
+fn is_minimal_lexically(path: &Path) -> bool {
+    use Component::*;
+
+    let mut rest = path.as_os_str().as_encoded_bytes();
+    if rest == DOT.as_bytes() {
+        return true;
+    } else if rest.is_empty() {
+        // which cleans to `.`
+        return false;
+    }
+
+    let mut previous = None;
+    for component in path.components() {
+        match component {
+            CurDir => return false,
+            ParentDir if previous.is_some_and(|c| c != ParentDir) => return false,
+            _ => (),
+        }
+
+        // each component must be exactly as it would be after cleaning, with a single native separator where required
+        if matches!(previous, Some(ParentDir | Normal(_))) {
+            match rest.split_first() {
+                Some((b, tail)) if *b as char == std::path::MAIN_SEPARATOR => rest = tail,
+                _ => return false,
+            }
+        }
+        match rest.strip_prefix(component.as_os_str().as_encoded_bytes()) {
+            Some(tail) => rest = tail,
+            None => return false,
+        }
+
+        previous = Some(component);
+    }
+
+    // anything left over is a trailing separator
+    rest.is_empty()
+}
+
+// the outcome of a single level of parent resolution
+enum ParentStep {
+    // the parent itself
+    Parent(PathBuf),
+    // the path without its final component, which the caller may truncate in place rather than allocating afresh
+    Pop,
+    // the parent of this path once cleaned
+    Clean(PathBuf),
+    // the parent of the target of this symlink, with the target itself if that was already read
+    Symlink(PathBuf, Option<PathBuf>),
+}
+
+#[derive(Debug)]
+struct RealPath<'fs> {
+    fs: &'fs dyn FileSystem,
+    // symlinks whose resolution is in progress, outermost first
+    symlinks_resolving: Vec<PathBuf>,
+    changes: CleanChanges,
+    stats: ResolveStats,
+    follow: FollowPolicy,
+    opaque_magic_links: bool,
+    stay_on_device: bool,
+    keep_dangling_symlinks: bool,
+    max_resolved_len: Option<usize>,
+    allowed_target_prefixes: Option<Vec<PathBuf>>,
+    on_symlink: Option<&'fs SymlinkCallback>,
+    virtual_root: Option<PathBuf>,
+    root_predicate: Option<&'fs RootPredicate>,
+    trie: Option<PrefixTrie>,
+    max_io_ops: Option<usize>,
+    io_ops: Cell<usize>,
+    confined: bool,
+    expand_link_targets: bool,
+    check_link_types: bool,
+    traversed: Option<Vec<PathBuf>>,
+}
+
+impl Default for RealPath<'static> {
+    fn default() -> Self {
+        Self::new(&RealFileSystem)
+    }
+}
+
+impl<'fs> RealPath<'fs> {
+    fn new(fs: &'fs dyn FileSystem) -> Self {
+        Self {
+            fs,
+            symlinks_resolving: Vec::new(),
+            changes: CleanChanges::default(),
+            stats: ResolveStats::default(),
+            follow: FollowPolicy::All,
+            opaque_magic_links: false,
+            stay_on_device: false,
+            keep_dangling_symlinks: false,
+            max_resolved_len: None,
+            allowed_target_prefixes: None,
+            on_symlink: None,
+            virtual_root: None,
+            root_predicate: None,
+            trie: None,
+            max_io_ops: None,
+            io_ops: Cell::new(0),
+            confined: false,
+            expand_link_targets: false,
+            check_link_types: false,
+            traversed: None,
+        }
+    }
+
+    fn with_follow_policy(self, follow: FollowPolicy) -> Self {
+        Self { follow, ..self }
+    }
+
+    fn with_stay_on_device(self, stay_on_device: bool) -> Self {
+        Self {
+            stay_on_device,
+            ..self
+        }
+    }
+
+    fn with_keep_dangling_symlinks(self, keep_dangling_symlinks: bool) -> Self {
+        Self {
+            keep_dangling_symlinks,
+            ..self
+        }
+    }
+
+    fn with_max_resolved_len(self, max_resolved_len: Option<usize>) -> Self {
+        Self {
+            max_resolved_len,
+            ..self
+        }
+    }
+
+    fn with_allowed_target_prefixes(self, allowed_target_prefixes: Option<Vec<PathBuf>>) -> Self {
+        Self {
+            allowed_target_prefixes,
+            ..self
+        }
+    }
+
+    fn with_on_symlink(self, on_symlink: Option<&'fs SymlinkCallback>) -> Self {
+        Self { on_symlink, ..self }
+    }
+
+    fn with_virtual_root(self, virtual_root: Option<PathBuf>) -> Self {
+        Self {
+            virtual_root,
+            ..self
+        }
+    }
+
+    fn with_root_predicate(self, root_predicate: Option<&'fs RootPredicate>) -> Self {
+        Self {
+            root_predicate,
+            ..self
+        }
+    }
+
+    fn with_trie(self, trie: Option<PrefixTrie>) -> Self {
+        Self { trie, ..self }
+    }
+
+    // the real parent of a directory, as previously resolved in this batch, if any
+    fn trie_parent(&self, path: &Path) -> Option<PathBuf> {
+        self.trie.as_ref()?.parent(path).map(Path::to_path_buf)
+    }
+
+    fn with_max_io_ops(self, max_io_ops: Option<usize>) -> Self {
+        Self { max_io_ops, ..self }
+    }
+
+    fn with_confined(self, confined: bool) -> Self {
+        Self { confined, ..self }
+    }
+
+    fn with_expand_link_targets(self, expand_link_targets: bool) -> Self {
+        Self {
+            expand_link_targets,
+            ..self
+        }
+    }
+
+    fn with_check_link_types(self, check_link_types: bool) -> Self {
+        Self {
+            check_link_types,
+            ..self
+        }
+    }
+
+    fn with_collect_trace(self, collect_trace: bool) -> Self {
+        Self {
+            traversed: collect_trace.then(Vec::new),
+            ..self
+        }
+    }
+
+    // record the directory containing path as traversed, if collecting a trace, since an entry was read from it
+    fn record_traversal(&mut self, path: &Path) {
+        if let Some(traversed) = self.traversed.as_mut() {
+            match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() && !traversed.iter().any(|d| d == dir) => {
+                    traversed.push(dir.to_path_buf())
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // attach the directories traversed to an error of kind `PermissionDenied`, if collecting a trace,
+    // where only now do we find how far along the denied path itself access went
+    fn traced(&mut self, e: Error) -> Error {
+        if self.traversed.is_none() || !e.is_permission_denied() {
+            return e;
+        }
+
+        if let Some(denied) = e.denied_path() {
+            let ancestors = denied.ancestors().collect::<Vec<_>>();
+            for ancestor in ancestors.into_iter().rev().skip(1) {
+                if self.fs.entry_kind(ancestor).is_err() {
+                    break;
+                }
+                self.record_traversal(ancestor);
+            }
+        }
+
+        match self.traversed.take() {
+            Some(traversed) => Error::Traced {
+                error: Box::new(e),
+                traversed,
+            },
+            None => e,
+        }
+    }
+
+    // check that the declared kind of a typed symlink matches the kind of its target, if required,
+    // where a target which can't be reached is left for resolution to report
+    fn check_link_type(&self, path: &Path) -> Result<(), Error> {
+        if !self.check_link_types {
+            return Ok(());
+        }
+
+        let fs = self.metered_fs(path)?;
+        let Some(expected) = fs.symlink_kind(path).with_fs_context(self, path)? else {
+            return Ok(());
+        };
+        match fs.target_kind(path) {
+            Ok(actual) if actual != expected => Err(Error::LinkTypeMismatch {
+                link: path.to_path_buf(),
+                expected,
+                actual,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // check that a path doesn't escape the directory to which resolution is confined, if any, as any absolute path would
+    fn check_confined(&self, path: &Path) -> Result<(), Error> {
+        if self.confined && path.has_root() {
+            Err(Error::Escape(path.to_path_buf()))
+        } else {
+            Ok(())
+        }
+    }
+
+    // the filesystem, for a single call on path, which is counted against the budget, if any
+    fn metered_fs(&self, path: &Path) -> Result<&'fs dyn FileSystem, Error> {
+        let io_ops = self.io_ops.get() + 1;
+        self.io_ops.set(io_ops);
+
+        match self.max_io_ops {
+            Some(max) if io_ops > max => Err(Error::BudgetExceeded(path.to_path_buf())),
+            _ => Ok(self.fs),
+        }
+    }
+
+    // When access to a path is denied, it is typically because some ancestor directory is not searchable,
+    // so we attempt to find that directory, to avoid confusing the user.
+    fn denied_path(&self, path: &Path) -> PathBuf {
+        let ancestors = path.ancestors().collect::<Vec<_>>();
+
+        for pair in ancestors.windows(2).rev() {
+            let (child, parent) = (pair[0], pair[1]);
+
+            let Ok(fs) = self.metered_fs(child) else {
+                // out of budget, so settle for the path itself
+                break;
+            };
+            if fs
+                .entry_kind(child)
+                .is_err_and(|e| e.kind() == io::ErrorKind::PermissionDenied)
+            {
+                return empty_to_dot(parent.to_path_buf());
+            }
+        }
+
+        path.to_path_buf()
+    }
+
+    fn with_opaque_magic_links(self, opaque_magic_links: bool) -> Self {
+        Self {
+            opaque_magic_links,
+            ..self
+        }
+    }
+
+    // the step to follow the symlink at path, unless it is not to be followed according to policy,
+    // where the target of a magic link is read to decide, so is passed on rather than read again
+    fn follow_step(&self, path: &Path) -> Result<Option<ParentStep>, Error> {
+        let mut target = None;
+        if is_magic_link(path) {
+            if self.opaque_magic_links {
+                return Ok(None);
+            }
+            let magic_target = self
+                .metered_fs(path)?
+                .read_link(path)
+                .with_fs_context(self, path)?;
+            // targets such as `pipe:[1234]` are not paths at all
+            if !magic_target.is_absolute() {
+                return Ok(None);
+            }
+            target = Some(magic_target);
+        }
+
+        let target_kind = || {
+            self.metered_fs(path)?
+                .target_kind(path)
+                .with_fs_context(self, path)
+        };
+
+        let follows = match self.follow {
+            FollowPolicy::All => true,
+            FollowPolicy::FilesOnly => target_kind()? == EntryKind::File,
+            FollowPolicy::DirsOnly => target_kind()? == EntryKind::Dir,
+            FollowPolicy::None => false,
+        };
+
+        Ok(follows.then(|| ParentStep::Symlink(path.to_path_buf(), target)))
+    }
 
-impl PathExt for Path {
-    fn real_parent(&self) -> io::Result<PathBuf> {
-        let mut real_path = RealPath::default();
-        real_path
-            .parent(self)
-            .map(empty_to_dot)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    fn parent(&mut self, path: &Path) -> Result<PathBuf, Error> {
+        let step = self.parent_step(path)?;
+        self.step_parent(path, step)
     }
 
-    fn real_clean(&self) -> io::Result<PathBuf> {
-        let mut real_path = RealPath::default();
-        real_path
-            .clean(self)
-            .map(empty_to_dot)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    // as per `parent` for the final component of a path, so that a dangling symlink may be kept
+    fn leaf_parent(&mut self, path: &Path) -> Result<PathBuf, Error> {
+        match self.parent_step(path)? {
+            ParentStep::Symlink(path, _)
+                if self.keep_dangling_symlinks && self.is_dangling(&path)? =>
+            {
+                symlink_dir(&path)
+            }
+            ParentStep::Clean(path) => {
+                let path = self.clean(&path)?;
+                self.leaf_parent(&path)
+            }
+            step => self.step_parent(path, step),
+        }
     }
 
-    fn is_real_root(&self) -> io::Result<bool> {
-        let path = if self.as_os_str().is_empty() {
-            AsRef::<Path>::as_ref(DOT)
-        } else {
-            self
-        };
+    fn step_parent(&mut self, path: &Path, step: ParentStep) -> Result<PathBuf, Error> {
+        match step {
+            ParentStep::Parent(parent) => Ok(parent),
+            // unwrap is safe because the last path component is normal
+            ParentStep::Pop => Ok(path.parent().unwrap().to_path_buf()),
+            ParentStep::Clean(path) => {
+                let path = self.clean(&path)?;
+                self.parent(&path)
+            }
+            ParentStep::Symlink(path, target) => {
+                self.symlink_parent(&path, target).map(|p| p.into())
+            }
+        }
+    }
 
-        // a canonical path cannot be empty
-        match path.canonicalize()?.components().last().unwrap() {
-            Component::RootDir => Ok(true),
-            _ => Ok(false),
+    // whether the ultimate target of the symlink at path doesn't exist
+    fn is_dangling(&self, path: &Path) -> Result<bool, Error> {
+        match self.metered_fs(path)?.target_kind(path) {
+            Ok(_) => Ok(false),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(true),
+            Err(e) => Err(Error::IO(e, path.to_path_buf())),
         }
     }
-}
 
-#[derive(Default, Debug)]
-struct RealPath {
-    symlinks_visited: HashSet<PathBuf>,
-}
+    // a single level of parent resolution, leaving any cleaning or symlink resolution to the caller
+    fn parent_step(&mut self, path: &Path) -> Result<ParentStep, Error> {
+        self.check_confined(path)?;
+        check_legal(path)?;
 
-impl RealPath {
-    fn parent(&mut self, path: &Path) -> Result<PathBuf, Error> {
-        if path.as_os_str().is_empty() {
-            Ok(DOTDOT.into())
+        if self.is_virtual_root(path) {
+            // the virtual root is its own parent, just like the real one
+            Ok(ParentStep::Parent(path.to_path_buf()))
+        } else if path.as_os_str().is_empty() {
+            Ok(ParentStep::Parent(DOTDOT.into()))
+        } else if let Some(root) = root_with_dots(path) {
+            // the root directory is its own parent, so this is simply the root, and so is its parent
+            Ok(ParentStep::Parent(root))
+        } else if (is_verbatim(path) || self.confined) && has_dots(path) {
+            // Windows doesn't interpret `.` or `..` in verbatim paths, so we must fold them away ourselves,
+            // as we must also where confined, since the operating system would let `..` ascend above the confining directory.
+            Ok(ParentStep::Clean(path.to_path_buf()))
+        } else if is_plain_absolute(path) {
+            // Fast path for the common case, where no light clean is required.
+            self.stats.dirs_stated += 1;
+            let kind = self
+                .metered_fs(path)?
+                .entry_kind(path)
+                .with_fs_context(self, path)?;
+            self.record_traversal(path);
+
+            if kind == EntryKind::Symlink {
+                if let Some(step) = self.follow_step(path)? {
+                    return Ok(step);
+                }
+            }
+            Ok(ParentStep::Pop)
         } else {
             // Trailing dot is troublesome.  The problem is, it looks like a directory to symlink_metadata(),
             // but is invisible to file_name().  We mitigate that inconsistency with a light clean, via components().
             let path = path.components().collect::<PathBuf>();
-            let metadata = path.symlink_metadata().with_path_context(&path)?;
 
-            let parent = if metadata.is_symlink() {
-                self.symlink_parent(&path)
-            } else if metadata.is_dir() {
-                self.dir_parent(&path)
-            } else {
-                self.file_parent(&path)
+            if path.file_name().is_none() && is_device_namespace(&path) {
+                // the Windows device namespace has no hierarchy above the device
+                return Err(Error::NoParent(path));
+            }
+
+            self.stats.dirs_stated += 1;
+            let kind = self
+                .metered_fs(&path)?
+                .entry_kind(&path)
+                .with_fs_context(self, &path)?;
+            self.record_traversal(&path);
+            let parent = match kind {
+                // only a path with a final name may be followed as a symlink, whereas on Windows a drive-relative path
+                // such as `C:` has the kind of the current directory on that drive
+                EntryKind::Symlink if path.file_name().is_some() => {
+                    match self.follow_step(&path)? {
+                        Some(step) => return Ok(step),
+                        None => self.dir_parent(&path)?,
+                    }
+                }
+                EntryKind::Symlink | EntryKind::Dir => self.dir_parent(&path)?,
+                EntryKind::File => self.file_parent(&path)?,
             };
 
-            parent.map(|p| p.into())
+            Ok(ParentStep::Parent(parent.into_owned()))
         }
     }
 
-    fn symlink_parent(&mut self, path: &Path) -> Result<Cow<'_, Path>, Error> {
-        // check we are not in a cycle of twisty little symlinks, all alike
-        let symlink_path = path.to_path_buf();
-        if self.symlinks_visited.contains(&symlink_path) {
-            return Err(Error::Cycle(symlink_path));
+    fn symlink_parent(
+        &mut self,
+        path: &Path,
+        target: Option<PathBuf>,
+    ) -> Result<Cow<'_, Path>, Error> {
+        let depth = self.symlinks_resolving.len();
+        let (symlink_dir, target) = self.symlink_hop(path, target)?;
+
+        let parent = self
+            .join(symlink_dir, &target)
+            .and_then(|resolved_target| self.parent(&resolved_target));
+        // the symlink is resolved, so may legitimately be traversed again, as by a later component
+        self.symlinks_resolving.truncate(depth);
+
+        parent.map(|p| p.into())
+    }
+
+    // the directory containing a symlink and its target, which must then be joined to it and resolved,
+    // where the target is read unless it already has been
+    fn symlink_hop(
+        &mut self,
+        path: &Path,
+        target: Option<PathBuf>,
+    ) -> Result<(PathBuf, PathBuf), Error> {
+        // check we are not in a cycle of twisty little symlinks, all alike,
+        // that is, the symlink is not already being resolved
+        if self.symlinks_resolving.iter().any(|p| p == path) {
+            return Err(Error::Cycle(path.to_path_buf()));
+        }
+        self.symlinks_resolving.push(path.to_path_buf());
+        self.changes.symlinks_resolved += 1;
+        self.stats.symlink_hops += 1;
+        if self.stats.symlink_hops > MAX_RESOLUTION_HOPS {
+            return Err(Error::Cycle(path.to_path_buf()));
         }
-        self.symlinks_visited.insert(symlink_path);
 
         // we'll have to recurse until we find something that's not a symlink,
-        let target = path.read_link().with_path_context(path)?;
+        // relying on the metadata of the resolved target rather than the type of the link itself,
+        // since on Windows a symlink is typed as file or directory, and that may not match its target
+        let target = match target {
+            Some(target) => self.checked_target(path, target)?,
+            None => self.read_link(path)?,
+        };
+        if self.check_confined(&target).is_err() {
+            return Err(Error::Escape(path.to_path_buf()));
+        }
+        self.check_link_type(path)?;
+
+        let symlink_dir = symlink_dir(path)?;
+        self.check_target_allowed(path, &symlink_dir, &target)?;
+        if let Some(on_symlink) = self.on_symlink {
+            on_symlink.call(path, &target);
+        }
 
-        // unwrap is safe because the last path component is a symlink
-        let symlink_dir = path.parent().unwrap();
+        Ok((symlink_dir, target))
+    }
+
+    // check that the target of a symlink is within one of the allowed prefixes, if any,
+    // where the target is made absolute lexically, since any symlinks it traverses are checked in turn as they are followed
+    fn check_target_allowed(
+        &self,
+        link: &Path,
+        symlink_dir: &Path,
+        target: &Path,
+    ) -> Result<(), Error> {
+        let Some(prefixes) = self.allowed_target_prefixes.as_ref() else {
+            return Ok(());
+        };
+
+        let absolute = |path: &Path| {
+            let path = lexical_normalize(path);
+            std::path::absolute(&path).with_path_context(&path)
+        };
+        let target = absolute(&symlink_dir.join(target))?;
 
-        let resolved_target = self.join(symlink_dir, &target)?;
+        for prefix in prefixes {
+            if target.starts_with(absolute(prefix)?) {
+                return Ok(());
+            }
+        }
 
-        self.parent(resolved_target.as_path()).map(|p| p.into())
+        Err(Error::ForbiddenTarget {
+            link: link.to_path_buf(),
+            target,
+        })
     }
 
-    fn dir_parent<'a>(&mut self, path: &'a Path) -> Result<Cow<'a, Path>, Error> {
-        match path.file_name() {
-            Some(_) => Ok(path.parent().unwrap().into()),
+    // the ultimate target of a symlink, relative to the symlink directory where possible
+    fn link_target(&mut self, path: &Path) -> Result<Option<PathBuf>, Error> {
+        let path = path.components().collect::<PathBuf>();
+        let Some(link_dir) = path.parent() else {
+            // root directory
+            return Ok(None);
+        };
 
-            None => {
-                if path == AsRef::<Path>::as_ref(DOT) {
-                    Ok(Into::<PathBuf>::into(DOTDOT).into())
-                } else {
-                    match path.components().last() {
-                        None | Some(Component::ParentDir) => {
-                            // don't attempt to fold away `..` in the base path
-                            Ok(path.join(DOTDOT).into())
-                        }
-                        _ => {
-                            // parent of root dir is itself
-                            Ok(path.into())
-                        }
-                    }
+        let mut resolved = path.clone();
+        let mut hops = HashSet::new();
+        while self
+            .metered_fs(&resolved)?
+            .entry_kind(&empty_to_dot(resolved.clone()))
+            .with_fs_context(self, &resolved)?
+            == EntryKind::Symlink
+        {
+            if !hops.insert(resolved.clone()) {
+                return Err(Error::Cycle(resolved));
+            }
+
+            let target = self.read_link(&resolved)?;
+            let symlink_dir = symlink_dir(&resolved)?;
+            resolved = self.join(symlink_dir, &target)?;
+        }
+
+        if resolved == path {
+            return Ok(None);
+        }
+
+        self.relative_to(&resolved, link_dir).map(Some)
+    }
+
+    // the target for a new symlink in new_dir referring to the same file as link
+    fn retarget(&mut self, link: &Path, new_dir: &Path) -> Result<PathBuf, Error> {
+        let target = match self.link_target(link)? {
+            Some(target) => target,
+            None => return Err(Error::NotSymlink(link.to_path_buf())),
+        };
+
+        let immediate_target = self.read_link(link)?;
+        if immediate_target.is_absolute() {
+            return Ok(immediate_target);
+        }
+
+        // light clean, as per `parent`
+        let link = link.components().collect::<PathBuf>();
+        let resolved = self.clean(symlink_dir(&link)?.join(target))?;
+
+        if resolved.is_absolute() == new_dir.is_absolute() {
+            self.relative_to(&resolved, new_dir)
+        } else {
+            let absolute = |path: &Path| std::path::absolute(path).with_path_context(path);
+            self.relative_to(&absolute(&resolved)?, &absolute(new_dir)?)
+        }
+    }
+
+    fn symlink_target_for(&mut self, link: &Path, target: &Path) -> Result<PathBuf, Error> {
+        let (Some(_), Some(link_dir)) = (link.file_name(), link.parent()) else {
+            return Err(Error::InvalidPath {
+                path: link.to_path_buf(),
+                reason: "has no file name".to_string(),
+            });
+        };
+
+        // made absolute before cleaning, so that any `..` is resolved from the current directory, which is real,
+        // and so that a relative target is then relative to the link directory, not the current directory
+        let absolute = |path: &Path| std::path::absolute(path).with_path_context(path);
+        let link_dir = self.clean(absolute(&empty_to_dot(link_dir.to_path_buf()))?)?;
+        let target = self.clean(absolute(target)?)?;
+
+        self.relative_to(&target, &link_dir)
+    }
+
+    // express path relative to dir, ascending from dir via its real parents as required
+    fn relative_to(&mut self, path: &Path, dir: &Path) -> Result<PathBuf, Error> {
+        let leading_dotdots = path
+            .components()
+            .take_while(|c| *c == Component::ParentDir)
+            .count();
+
+        let mut ancestor = if dir == AsRef::<Path>::as_ref(DOT) {
+            PathBuf::new()
+        } else {
+            dir.to_path_buf()
+        };
+        let mut ascent = PathBuf::new();
+
+        loop {
+            if ancestor.is_absolute() == path.is_absolute() {
+                if let Ok(descent) = path.strip_prefix(&ancestor) {
+                    return Ok(empty_to_dot(ascent.join(descent)));
                 }
             }
+
+            // Ascending beyond more `..` than the path has cannot help.  The number of levels is not otherwise bounded,
+            // since ascending through a symlink may lead anywhere.
+            if ancestor.is_relative()
+                && ancestor.components().all(|c| c == Component::ParentDir)
+                && ancestor.components().count() > leading_dotdots
+            {
+                break;
+            }
+
+            let parent = self.parent(&ancestor)?;
+            if parent == ancestor {
+                // root directory
+                break;
+            }
+            ancestor = parent;
+            ascent.push(DOTDOT);
+        }
+
+        Ok(path.to_path_buf())
+    }
+
+    // read a symlink, rejecting an empty target, which would otherwise resolve silently to the symlink directory
+    fn read_link(&self, path: &Path) -> Result<PathBuf, Error> {
+        let target = self
+            .metered_fs(path)?
+            .read_link(path)
+            .with_fs_context(self, path)?;
+
+        self.checked_target(path, target)
+    }
+
+    // the target of the symlink at path as read, checked as per `read_link`
+    fn checked_target(&self, path: &Path, target: PathBuf) -> Result<PathBuf, Error> {
+        if target.as_os_str().is_empty() {
+            Err(Error::Dangling(path.to_path_buf()))
+        } else if is_magic_link(path) && is_deleted_target(&target) {
+            // the deleted marker is not part of any real path, and the file is no longer reachable by path
+            Err(Error::Deleted(path.to_path_buf()))
+        } else if self.expand_link_targets {
+            let target = env::expand_tilde(&target, |name| std::env::var_os(name))?;
+            env::expand(&target, |name| std::env::var_os(name))
+        } else {
+            Ok(target)
         }
     }
 
+    fn dir_parent<'a>(&mut self, path: &'a Path) -> Result<Cow<'a, Path>, Error> {
+        Ok(parent_lexically(path))
+    }
+
     fn file_parent<'a>(&self, path: &'a Path) -> Result<Cow<'a, Path>, Error> {
-        Ok(path.parent().unwrap().into())
+        Ok(parent_lexically(path))
+    }
+
+    // whether path is the virtual root, if any, either lexically or by file identity, or satisfies the root predicate, if any,
+    // where any failure to determine identity is left for resolution to report
+    fn is_virtual_root(&self, path: &Path) -> bool {
+        if self
+            .root_predicate
+            .is_some_and(|predicate| predicate.is_root(path))
+        {
+            return true;
+        }
+
+        let Some(root) = self.virtual_root.as_ref() else {
+            return false;
+        };
+
+        if path.components().eq(root.components()) {
+            return true;
+        }
+
+        // any budget exceeded here is reported by the next call
+        let identity = |path: &Path| {
+            self.metered_fs(path)
+                .ok()?
+                .dir_identity(&empty_to_dot(path.to_path_buf()))
+                .ok()
+                .flatten()
+        };
+        match (identity(path), identity(root)) {
+            (Some(path_identity), Some(root_identity)) => path_identity == root_identity,
+            _ => false,
+        }
+    }
+
+    // check that a path being resolved has not grown beyond the maximum length, if any
+    fn check_resolved_len(&self, path: &Path) -> Result<(), Error> {
+        match self.max_resolved_len {
+            Some(max) if path.as_os_str().len() > max => Err(Error::TooLong(path.to_path_buf())),
+            _ => Ok(()),
+        }
+    }
+
+    // check that `..` from dir to its parent doesn't cross onto another device, where both have a known identity
+    fn check_same_device(&self, dir: &Path, parent: &Path) -> Result<(), Error> {
+        let device = |path: &Path| {
+            let path = empty_to_dot(path.to_path_buf());
+            self.metered_fs(&path)?
+                .dir_identity(&path)
+                .with_fs_context(self, &path)
+                .map(|identity| identity.map(|identity| identity.device()))
+        };
+
+        match (device(dir)?, device(parent)?) {
+            (Some(dir_device), Some(parent_device)) if dir_device != parent_device => {
+                Err(Error::CrossesMount(dir.to_path_buf()))
+            }
+            _ => Ok(()),
+        }
     }
 
     // join paths, folding away `..`
@@ -155,41 +2312,184 @@ impl RealPath {
         use Component::*;
 
         let other = other.as_ref();
+        let mut resolving = self.join_start(origin.as_ref(), other)?;
 
-        let (root, relative) = other
-            .components()
-            .partition::<Vec<_>, _>(|c| matches!(c, Prefix(_) | RootDir));
+        for component in other.components() {
+            match component {
+                CurDir => (),
+                Prefix(_) | RootDir => {
+                    resolving.push(component);
+                }
+                ParentDir => {
+                    // in the common case the parent is simply resolving without its final component,
+                    // so we truncate in place, avoiding an allocation per `..`
+                    let parent = match self.trie_parent(&resolving) {
+                        Some(parent) => Some(parent),
+                        None => {
+                            let parent = match self.parent_step(&resolving)? {
+                                ParentStep::Pop => None,
+                                step => Some(self.step_parent(&resolving, step)?),
+                            };
+                            self.remember_parent(&resolving, parent.as_deref());
+                            parent
+                        }
+                    };
+                    self.ascend(&mut resolving, parent)?;
+                }
+                Normal(_) => {
+                    resolving.push(component);
+                }
+            }
 
-        let mut resolving = if root.is_empty() {
-            origin.as_ref().to_path_buf()
-        } else {
-            root.iter().collect::<PathBuf>()
-        };
+            self.check_resolved_len(&resolving)?;
+        }
+
+        Ok(resolving)
+    }
+
+    // the path to which the components of `other` are joined, that is, `origin` unless `other` is absolute,
+    // having checked that `other` is acceptable
+    fn join_start(&self, origin: &Path, other: &Path) -> Result<PathBuf, Error> {
+        use Component::*;
+
+        self.check_confined(other)?;
+        check_legal(other)?;
+
+        // an absolute path replaces the origin, and its prefix and root, which can only come first, are simply pushed
+        Ok(match other.components().next() {
+            Some(Prefix(_) | RootDir) => PathBuf::new(),
+            _ => origin.to_path_buf(),
+        })
+    }
+
+    // record the real parent of a directory for the rest of the batch, if resolving a batch,
+    // where `None` is for the directory without its final component
+    fn remember_parent(&mut self, dir: &Path, parent: Option<&Path>) {
+        if let Some(trie) = self.trie.as_mut() {
+            // unwrap is safe because Pop is only for a path whose last component is normal
+            let parent = parent.unwrap_or_else(|| dir.parent().unwrap());
+            trie.insert(dir, parent.to_path_buf());
+        }
+    }
+
+    // replace the path being resolved with its real parent, for `..`,
+    // where `None` is for the path without its final component, which is truncated in place
+    fn ascend(&mut self, resolving: &mut PathBuf, parent: Option<PathBuf>) -> Result<(), Error> {
+        if self.stay_on_device {
+            // unwrap is safe because Pop is only for a path whose last component is normal
+            let parent = parent
+                .as_deref()
+                .unwrap_or_else(|| resolving.parent().unwrap());
+            self.check_same_device(resolving, parent)?;
+        }
+        self.stats.dotdots_resolved += 1;
+        match parent {
+            Some(path) => {
+                if path.components().next_back() != Some(Component::ParentDir) {
+                    self.changes.dotdots_folded += 1;
+                }
+                *resolving = path;
+            }
+            None => {
+                self.changes.dotdots_folded += 1;
+                resolving.pop();
+            }
+        }
+
+        Ok(())
+    }
+
+    // split a path into its deepest existing ancestor, cleaned, and the nonexistent remainder
+    fn existing_prefix(&mut self, path: &Path) -> Result<(PathBuf, PathBuf), Error> {
+        use Component::*;
+
+        let mut components = path.components();
+        let mut existing = PathBuf::new();
 
-        for component in relative {
+        while let Some(component) = components.next() {
             match component {
+                Prefix(_) | RootDir => existing.push(component),
                 CurDir => (),
-                Prefix(_) | RootDir => {
-                    panic!(
-                        "impossible absolute component in relative part of path {:?}",
-                        other
-                    )
-                }
-                ParentDir => match self.parent(resolving.as_path()) {
-                    Ok(path) => {
-                        resolving = path;
-                    }
-                    Err(e) => {
-                        return Err(e);
+                ParentDir => existing = self.parent(&existing)?,
+                Normal(_) => {
+                    let candidate = existing.join(component);
+
+                    match self.metered_fs(&candidate)?.entry_kind(&candidate) {
+                        Ok(_) => existing = candidate,
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                            let tail = Path::new(&component).join(components.as_path());
+                            return Ok((existing, tail));
+                        }
+                        Err(e) => return Err(e).with_fs_context(self, &candidate),
                     }
-                },
+                }
+            }
+        }
+
+        Ok((existing, PathBuf::new()))
+    }
+
+    // split a path into its longest prefix without any symlink, and the remainder starting at the first symlink
+    fn symlink_free_prefix(&self, path: &Path) -> Result<(PathBuf, PathBuf), Error> {
+        let mut components = path.components();
+        let mut prefix = PathBuf::new();
+
+        while let Some(component) = components.next() {
+            if let Component::Normal(_) = component {
+                let candidate = prefix.join(component);
+
+                if self
+                    .metered_fs(&candidate)?
+                    .entry_kind(&candidate)
+                    .with_fs_context(self, &candidate)?
+                    == EntryKind::Symlink
+                {
+                    let tail = Path::new(&component).join(components.as_path());
+                    return Ok((prefix, tail));
+                }
+            }
+            prefix.push(component);
+        }
+
+        Ok((prefix, PathBuf::new()))
+    }
+
+    // trace the resolution of each component of a path
+    fn trace(&mut self, path: &Path) -> Result<Vec<ResolvedStep>, Error> {
+        use Component::*;
+
+        let mut resolving = PathBuf::new();
+        let mut steps = Vec::new();
+
+        for component in path.components() {
+            let mut target = None;
+
+            match component {
+                Prefix(_) | RootDir => resolving.push(component),
+                CurDir => (),
+                ParentDir => resolving = self.parent(&resolving)?,
                 Normal(_) => {
                     resolving.push(component);
+                    if self
+                        .metered_fs(&resolving)?
+                        .entry_kind(&resolving)
+                        .with_fs_context(self, &resolving)?
+                        == EntryKind::Symlink
+                    {
+                        target = Some(self.read_link(&resolving)?);
+                    }
                 }
             }
+
+            steps.push(ResolvedStep {
+                input_component: component.as_os_str().to_os_string(),
+                resolved_to: empty_to_dot(resolving.clone()),
+                was_symlink: target.is_some(),
+                target,
+            });
         }
 
-        Ok(resolving)
+        Ok(steps)
     }
 
     // clean a path, folding away `..`
@@ -208,6 +2508,46 @@ impl RealPath {
 enum Error {
     IO(io::Error, PathBuf),
     Cycle(PathBuf),
+    Cancelled,
+    NoParent(PathBuf),
+    UndefinedVar {
+        name: String,
+    },
+    #[cfg(feature = "directories")]
+    NoHomeDir,
+    Dangling(PathBuf),
+    AboveBase(PathBuf),
+    Deleted(PathBuf),
+    NotAbsolute(PathBuf),
+    NotSymlink(PathBuf),
+    Escape(PathBuf),
+    CrossesMount(PathBuf),
+    TooLong(PathBuf),
+    ForbiddenTarget {
+        link: PathBuf,
+        target: PathBuf,
+    },
+    LinkTypeMismatch {
+        link: PathBuf,
+        expected: EntryKind,
+        actual: EntryKind,
+    },
+    TimedOut {
+        path: PathBuf,
+    },
+    RootParent(PathBuf),
+    BudgetExceeded(PathBuf),
+    NotUnicode(PathBuf),
+    InvalidPath {
+        path: PathBuf,
+        reason: String,
+    },
+    NoFileUrl(PathBuf),
+    Resolving(Box<Error>, PathBuf),
+    Traced {
+        error: Box<Error>,
+        traversed: Vec<PathBuf>,
+    },
 }
 
 impl Display for Error {
@@ -217,26 +2557,284 @@ impl Display for Error {
         match self {
             IO(e, path) => write!(f, "{} on {}", e, path.to_string_lossy()),
             Cycle(path) => write!(f, "symlink cycle detected at {}", path.to_string_lossy()),
+            Cancelled => write!(f, "resolution cancelled"),
+            NoParent(path) => write!(
+                f,
+                "no parent in device namespace for {}",
+                path.to_string_lossy()
+            ),
+            UndefinedVar { name } => write!(f, "undefined environment variable {}", name),
+            #[cfg(feature = "directories")]
+            NoHomeDir => write!(f, "no home directory"),
+            Dangling(path) => write!(f, "symlink has empty target at {}", path.to_string_lossy()),
+            Deleted(path) => write!(f, "target of {} has been deleted", path.to_string_lossy()),
+            NotAbsolute(path) => write!(f, "{} is not absolute", path.to_string_lossy()),
+            NotSymlink(path) => write!(f, "{} is not a symlink", path.to_string_lossy()),
+            CrossesMount(path) => write!(
+                f,
+                "parent directory crosses a mount point at {}",
+                path.to_string_lossy()
+            ),
+            TooLong(path) => write!(
+                f,
+                "resolved path exceeds maximum length at {}",
+                path.to_string_lossy()
+            ),
+            ForbiddenTarget { link, target } => write!(
+                f,
+                "symlink {} has target {} outside the allowed prefixes",
+                link.to_string_lossy(),
+                target.to_string_lossy()
+            ),
+            LinkTypeMismatch {
+                link,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "symlink {} is typed as {} but its target is a {}",
+                link.to_string_lossy(),
+                kind_name(*expected),
+                kind_name(*actual)
+            ),
+            RootParent(path) => write!(
+                f,
+                "{} is the root directory, which has no parent",
+                path.to_string_lossy()
+            ),
+            NotUnicode(path) => write!(f, "{} is not valid Unicode", path.to_string_lossy()),
+            InvalidPath { path, reason } => {
+                write!(
+                    f,
+                    "{} is not a valid path: {}",
+                    path.to_string_lossy(),
+                    reason
+                )
+            }
+            NoFileUrl(path) => write!(f, "no file URL for {}", path.to_string_lossy()),
+            BudgetExceeded(path) => write!(
+                f,
+                "filesystem operation budget exceeded at {}",
+                path.to_string_lossy()
+            ),
+            TimedOut { path } => {
+                write!(f, "filesystem call timed out on {}", path.to_string_lossy())
+            }
+            Escape(path) => write!(f, "{} escapes its base directory", path.to_string_lossy()),
+            AboveBase(path) => write!(
+                f,
+                "result ascends above base directory for {}",
+                path.to_string_lossy()
+            ),
+            Resolving(e, original) => {
+                write!(f, "{} (while resolving {})", e, original.to_string_lossy())
+            }
+            Traced { error, traversed } => {
+                write!(f, "{}", error)?;
+                for (i, dir) in traversed.iter().enumerate() {
+                    let sep = if i == 0 { " after traversing " } else { ", " };
+                    write!(f, "{}{}", sep, dir.to_string_lossy())?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
 impl std::error::Error for Error {}
 
+fn kind_name(kind: EntryKind) -> &'static str {
+    match kind {
+        EntryKind::File => "file",
+        EntryKind::Dir => "directory",
+        EntryKind::Symlink => "symlink",
+    }
+}
+
+impl Error {
+    // add the original input as context, where the error arose from some other path during resolution
+    fn resolving<P>(self, original: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        use Error::*;
+
+        let original = original.as_ref();
+        match &self {
+            IO(_, path)
+            | Cycle(path)
+            | NoParent(path)
+            | Dangling(path)
+            | Deleted(path)
+            | CrossesMount(path)
+            | TooLong(path)
+            | ForbiddenTarget { link: path, .. }
+            | LinkTypeMismatch { link: path, .. }
+            | TimedOut { path }
+            | BudgetExceeded(path)
+                if path != original =>
+            {
+                Resolving(Box::new(self), original.to_path_buf())
+            }
+            _ => self,
+        }
+    }
+
+    // whether the error arose from some path not existing
+    fn is_not_found(&self) -> bool {
+        use Error::*;
+
+        match self {
+            IO(e, _) => e.kind() == io::ErrorKind::NotFound,
+            Resolving(e, _) | Traced { error: e, .. } => e.is_not_found(),
+            e => e.kind() == io::ErrorKind::NotFound,
+        }
+    }
+
+    // whether the error arose from access to some path being denied
+    fn is_permission_denied(&self) -> bool {
+        use Error::*;
+
+        match self {
+            IO(e, _) => e.kind() == io::ErrorKind::PermissionDenied,
+            Resolving(e, _) | Traced { error: e, .. } => e.is_permission_denied(),
+            e => e.kind() == io::ErrorKind::PermissionDenied,
+        }
+    }
+
+    // the path to which access was denied, if that was the error
+    fn denied_path(&self) -> Option<&Path> {
+        use Error::*;
+
+        match self {
+            IO(e, path) if e.kind() == io::ErrorKind::PermissionDenied => Some(path),
+            Resolving(e, _) | Traced { error: e, .. } => e.denied_path(),
+            _ => None,
+        }
+    }
+
+    // where a symlink cycle was detected, if that was the error
+    fn cycle(&self) -> Option<&Path> {
+        use Error::*;
+
+        match self {
+            Cycle(path) => Some(path),
+            Resolving(e, _) | Traced { error: e, .. } => e.cycle(),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> io::ErrorKind {
+        use Error::*;
+
+        match self {
+            Cancelled => io::ErrorKind::Interrupted,
+            NoParent(_)
+            | UndefinedVar { .. }
+            | Dangling(_)
+            | AboveBase(_)
+            | NotAbsolute(_)
+            | NotSymlink(_)
+            | RootParent(_) => io::ErrorKind::InvalidInput,
+            #[cfg(feature = "directories")]
+            NoHomeDir => io::ErrorKind::InvalidInput,
+            Deleted(_) => io::ErrorKind::NotFound,
+            Escape(_) | ForbiddenTarget { .. } => io::ErrorKind::PermissionDenied,
+            CrossesMount(_) => io::ErrorKind::CrossesDevices,
+            TooLong(_) => io::ErrorKind::InvalidFilename,
+            TimedOut { .. } => io::ErrorKind::TimedOut,
+            BudgetExceeded(_) => io::ErrorKind::QuotaExceeded,
+            NotUnicode(_) | LinkTypeMismatch { .. } => io::ErrorKind::InvalidData,
+            NoFileUrl(_) | InvalidPath { .. } => io::ErrorKind::InvalidInput,
+            Resolving(e, _) | Traced { error: e, .. } => e.kind(),
+            IO(..) | Cycle(_) => io::ErrorKind::Other,
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        io::Error::new(e.kind(), e)
+    }
+}
+
+// whether an error arose from some path not existing, including where that is wrapped in our own error
+pub(crate) fn is_not_found(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::NotFound
+        || e.get_ref()
+            .and_then(|inner| inner.downcast_ref::<Error>())
+            .is_some_and(Error::is_not_found)
+}
+
+/// The directories which were successfully traversed before resolution failed with `e`, where that is of kind `PermissionDenied`
+/// and the trace was collected, see `Resolver::collect_trace_on_error`.
+///
+/// Directories are in the order first traversed, and any traversed more than once are listed only the first time.
+pub fn traversed_before_error(e: &io::Error) -> Option<&[PathBuf]> {
+    match e.get_ref()?.downcast_ref::<Error>()? {
+        Error::Traced { traversed, .. } => Some(traversed),
+        _ => None,
+    }
+}
+
 trait PathContext<T> {
     fn with_path_context<P>(self, path: P) -> Result<T, Error>
     where
         P: AsRef<Path>;
+
+    fn with_fs_context<P>(self, real_path: &RealPath, path: P) -> Result<T, Error>
+    where
+        P: AsRef<Path>;
 }
 
 impl<T> PathContext<T> for Result<T, io::Error> {
+    // for direct access to the real filesystem
     fn with_path_context<P>(self, path: P) -> Result<T, Error>
     where
         P: AsRef<Path>,
     {
-        self.map_err(|io_error| Error::IO(io_error, path.as_ref().to_path_buf()))
+        self.with_fs_context(&RealPath::default(), path)
+    }
+
+    // for access via the filesystem of `real_path`, where any probing is counted against its budget
+    fn with_fs_context<P>(self, real_path: &RealPath, path: P) -> Result<T, Error>
+    where
+        P: AsRef<Path>,
+    {
+        self.map_err(|io_error| {
+            let path = path.as_ref();
+            if io_error.kind() == io::ErrorKind::TimedOut {
+                return Error::TimedOut {
+                    path: path.to_path_buf(),
+                };
+            }
+            // a filesystem which is itself confined, such as `AtDirFileSystem::beneath`, reports an escape as we would
+            if io_error
+                .get_ref()
+                .and_then(|inner| inner.downcast_ref::<Error>())
+                .is_some_and(|e| matches!(e, Error::Escape(_)))
+            {
+                return Error::Escape(path.to_path_buf());
+            }
+            let path = if io_error.kind() == io::ErrorKind::PermissionDenied {
+                real_path.denied_path(path)
+            } else {
+                path.to_path_buf()
+            };
+            Error::IO(io_error, path)
+        })
     }
 }
 
 const DOT: &str = ".";
 const DOTDOT: &str = "..";
+// symlinks followed in a single resolution beyond which it is deemed a cycle, even though no path repeats,
+// as for a symlink `a` to `../a` in the root directory, whose relative resolution grows without limit
+const MAX_RESOLUTION_HOPS: usize = 256;
+// characters which may never appear in a file name, where on Windows `:` is permitted since it introduces an alternate data stream
+#[cfg(target_family = "windows")]
+const ILLEGAL_CHARS: &[u8] = b"\0<>\"|?*";
+#[cfg(not(target_family = "windows"))]
+const ILLEGAL_CHARS: &[u8] = b"\0";
+// the entry marking the root of a git repository
+const GIT: &str = ".git";
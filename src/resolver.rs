@@ -0,0 +1,1046 @@
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    io,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use super::{
+    cache::ParentCache,
+    case_corrected, empty_to_dot, env, explain_lexically,
+    file_system::{RetryFileSystem, TimeoutFileSystem},
+    interner::Interner,
+    is_not_found, long_path_name,
+    trie::PrefixTrie,
+    CacheStats, Error, Explanation, FileSystem, InternStats, MemoryFileSystem, RealAncestors,
+    RealFileSystem, RealPath, Resolution, ResolveState, ResolveStats, ResolvedStep, StepResult,
+    DOT,
+};
+
+/// Which symlinks are followed during resolution, as distinguished by the kind of their target.
+///
+/// A symlink which is not followed is treated lexically, as if it were a regular file or directory.
+/// Since `..` then refers to the lexical rather than the physical parent,
+/// results may be physically incorrect, or an error, where a relative symlink target traverses a symlink which is not followed.
+/// This allows tools to replicate options such as `-L` and `-P` as found in `find` or `rsync`.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum FollowPolicy {
+    /// Follow all symlinks, which is the default, and the only policy which is always correct.
+    #[default]
+    All,
+    /// Follow only symlinks to files.
+    FilesOnly,
+    /// Follow only symlinks to directories.
+    DirsOnly,
+    /// Follow no symlinks, so that resolution is purely lexical, although it still touches the filesystem.
+    None,
+}
+
+/// How the Linux magic symlinks for open files, `/proc/<pid>/fd/<n>`, are resolved, see `Resolver::magic_links`.
+///
+/// Whatever the option, a target which is not a path, such as `pipe:[1234]` or `socket:[1234]`, is never followed,
+/// and the magic symlinks are recognised only in absolute paths.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum MagicLinks {
+    /// Follow to the path of the underlying file, which is the default.
+    ///
+    /// If the file has been deleted, so that the target is marked ` (deleted)`, this is an error of kind `NotFound`,
+    /// rather than the marker being mistaken for part of the path.
+    #[default]
+    Follow,
+    /// Treat as opaque, that is, as if not symlinks, so the parent is the `fd` directory.
+    Opaque,
+}
+
+/// What the real parent of the root directory is, see `Resolver::root_parent`.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum RootParentPolicy {
+    /// The root directory itself, as for `..` in the root directory, which is the default.
+    #[default]
+    SelfSame,
+    /// None, as for `Path::parent`, so that `real_parent_opt` returns `None` at the root.
+    /// Since `real_parent` must return a path, it returns the root directory itself.
+    None,
+    /// An error of kind `InvalidInput`, from both `real_parent` and `real_parent_opt`.
+    Error,
+}
+
+/// How the results of `real_parent` and `real_clean` are expressed, see `Resolver::output_style`.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub enum OutputStyle {
+    /// As found by resolution, which is as relative and unresolved as correctness allows, and is the default.
+    #[default]
+    AsResolved,
+    /// Absolute, by prefixing any relative result with the current directory, without otherwise resolving it.
+    Absolute,
+    /// Relative to the given directory, ascending from it via its real parents as required, so remaining correct
+    /// in the presence of symlinks.
+    ///
+    /// A relative directory is with respect to the base, if any.
+    /// A result which cannot be expressed relative to the directory, for example on a different Windows drive, is as resolved.
+    RelativeTo(PathBuf),
+}
+
+/// Options controlling the results of `real_parent` and `real_clean`, see `Resolver::clean_options`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CleanOptions {
+    preserve_leading_parent: bool,
+}
+
+impl Default for CleanOptions {
+    fn default() -> Self {
+        Self {
+            preserve_leading_parent: true,
+        }
+    }
+}
+
+impl CleanOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a result may begin with `..`, which cannot be folded away since it ascends above the base directory,
+    /// or the current directory if there is no base.  Default is true.
+    ///
+    /// If false, such a result is instead an error of kind `InvalidInput`, for example for `real_clean` of `../foo`,
+    /// or `real_parent` of `.`.
+    pub fn preserve_leading_parent(mut self, preserve: bool) -> Self {
+        self.preserve_leading_parent = preserve;
+        self
+    }
+}
+
+// a callback for each symlink followed, with the link and its target
+type SymlinkFn = dyn FnMut(&Path, &Path) + Send;
+
+// the symlink callback, which is opaque, so must be debugged by proxy
+pub(crate) struct SymlinkCallback(Mutex<Box<SymlinkFn>>);
+
+impl SymlinkCallback {
+    pub(crate) fn call(&self, link: &Path, target: &Path) {
+        // a callback which panicked previously is nonetheless called again
+        let mut callback = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        callback(link, target)
+    }
+}
+
+impl std::fmt::Debug for SymlinkCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SymlinkCallback")
+    }
+}
+
+// a predicate for whether a path is to be treated as a root directory
+type RootFn = dyn Fn(&Path) -> bool + Send + Sync;
+
+// the root predicate, which is opaque, so must be debugged by proxy
+pub(crate) struct RootPredicate(Box<RootFn>);
+
+impl RootPredicate {
+    pub(crate) fn is_root(&self, path: &Path) -> bool {
+        (self.0)(path)
+    }
+}
+
+impl std::fmt::Debug for RootPredicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RootPredicate")
+    }
+}
+
+/// A reusable resolver, for resolving many paths with shared configuration and state.
+///
+/// The methods of `PathExt` are equivalent to using a default `Resolver` for a single path.
+#[derive(Debug)]
+pub struct Resolver {
+    base: Option<PathBuf>,
+    interner: Option<Interner>,
+    cache: Option<ParentCache>,
+    stop_marker: Option<OsString>,
+    fs: Arc<dyn FileSystem>,
+    expand_env: bool,
+    follow: FollowPolicy,
+    clean_options: CleanOptions,
+    magic_links: MagicLinks,
+    output_style: OutputStyle,
+    stay_on_device: bool,
+    keep_dangling_symlinks: bool,
+    expand_short_names: bool,
+    correct_case: bool,
+    max_resolved_len: Option<usize>,
+    allowed_target_prefixes: Option<Vec<PathBuf>>,
+    on_symlink: Option<SymlinkCallback>,
+    virtual_root: Option<PathBuf>,
+    max_io_ops: Option<usize>,
+    confined: bool,
+    expand_link_targets: bool,
+    check_link_types: bool,
+    root_parent: RootParentPolicy,
+    validate_cache_mtime: bool,
+    collect_trace_on_error: bool,
+    root_predicate: Option<RootPredicate>,
+    trie: Option<PrefixTrie>,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self {
+            base: None,
+            interner: None,
+            cache: None,
+            stop_marker: None,
+            fs: Arc::new(RealFileSystem),
+            expand_env: false,
+            follow: FollowPolicy::All,
+            clean_options: CleanOptions::default(),
+            magic_links: MagicLinks::default(),
+            output_style: OutputStyle::default(),
+            stay_on_device: false,
+            keep_dangling_symlinks: false,
+            expand_short_names: false,
+            correct_case: false,
+            max_resolved_len: None,
+            allowed_target_prefixes: None,
+            on_symlink: None,
+            virtual_root: None,
+            max_io_ops: None,
+            confined: false,
+            expand_link_targets: false,
+            check_link_types: false,
+            root_parent: RootParentPolicy::default(),
+            validate_cache_mtime: false,
+            collect_trace_on_error: false,
+            root_predicate: None,
+            trie: None,
+        }
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve relative paths with respect to `base` rather than the current directory.
+    ///
+    /// Results for relative paths are then prefixed with `base`, so that `.` and the empty path both mean the base directory,
+    /// and their parent is the real parent of the base directory.
+    pub fn with_base<P>(mut self, base: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.base = Some(base.as_ref().to_path_buf());
+        self
+    }
+
+    /// Resolve relative paths with respect to the current directory as it is now, as per `with_base`,
+    /// so that results are unaffected by any later change of current directory, even by another thread.
+    pub fn capture_cwd(self) -> io::Result<Self> {
+        let cwd = std::env::current_dir()?;
+        Ok(self.with_base(cwd))
+    }
+
+    /// Treat the first ancestor containing an entry called `file_name` as a logical root,
+    /// so that `real_ancestors` and `find_upwards` don't ascend beyond it.
+    ///
+    /// For example, a marker file at the root of a repository.
+    /// Unlike the real root directory, this merely ends iteration.  See also `marker_root`.
+    pub fn stop_at_marker<S>(mut self, file_name: S) -> Self
+    where
+        S: Into<OsString>,
+    {
+        self.stop_marker = Some(file_name.into());
+        self
+    }
+
+    /// Treat `root` as the root directory, as in a sandbox which pretends that a subdirectory is the root,
+    /// so that `root` is its own real parent, and `is_real_root` and hence `real_ancestors` stop there.
+    ///
+    /// Unlike `stop_at_marker`, this applies for all purposes within the resolver, including `..` in `real_clean`.
+    /// Only paths which ascend through `root` are affected.  A relative `root` is with respect to the base, if any.
+    pub fn virtual_root<P>(mut self, root: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.virtual_root = Some(root.as_ref().to_path_buf());
+        self
+    }
+
+    /// Treat any path for which `predicate` returns true as a root directory, as per `virtual_root`,
+    /// for example for a virtual filesystem or chroot whose root is not known as a single path in advance.
+    ///
+    /// So such a path is its own real parent, and `is_real_root` and hence `real_ancestors` and `find_upwards` stop there.
+    /// The predicate is called with paths as resolved, so these are relative where the path being resolved is,
+    /// and is in addition to the real root directory and any virtual root.
+    pub fn root_predicate(mut self, predicate: Box<RootFn>) -> Self {
+        self.root_predicate = Some(RootPredicate(predicate));
+        self
+    }
+
+    /// A resolver which resolves entirely from a snapshot of the filesystem below `root`, taken now,
+    /// with relative paths resolved with respect to `root`, as per `with_base`.
+    ///
+    /// Results are reproducible for the lifetime of the resolver, regardless of concurrent changes to the filesystem,
+    /// but are correspondingly stale, and may refer to entries which no longer exist.
+    /// Nothing outside `root` is found, see `MemoryFileSystem::snapshot`.
+    pub fn snapshot(root: &Path) -> io::Result<Resolver> {
+        let root = std::env::current_dir()?.join(root);
+        let fs = MemoryFileSystem::snapshot(&root)?;
+
+        Ok(Resolver::new().with_file_system(fs).with_base(root))
+    }
+
+    /// Resolve using the given filesystem rather than the real one.
+    pub fn with_file_system<F>(mut self, fs: F) -> Self
+    where
+        F: FileSystem + 'static,
+    {
+        self.fs = Arc::new(fs);
+        self
+    }
+
+    /// Which symlinks to follow during `real_parent` and `real_clean`.  Default is all of them.
+    pub fn follow_policy(mut self, follow: FollowPolicy) -> Self {
+        self.follow = follow;
+        self
+    }
+
+    /// How the results of `real_parent` and `real_clean`, and their variants, are expressed.  Default is `OutputStyle::AsResolved`.
+    pub fn output_style(mut self, output_style: OutputStyle) -> Self {
+        self.output_style = output_style;
+        self
+    }
+
+    /// Whether a symlink typed as file or directory whose target is of the other kind is an error of kind `InvalidData`,
+    /// for example for a tool auditing links on Windows.  Default is false, since resolution relies on the target itself.
+    ///
+    /// This relies on `FileSystem::symlink_kind`, so is a no-op where symlinks are untyped, as on Unix.
+    pub fn check_link_types(mut self, check_link_types: bool) -> Self {
+        self.check_link_types = check_link_types;
+        self
+    }
+
+    /// Whether an error of kind `PermissionDenied` records the directories which were successfully traversed before access broke,
+    /// for example for a diagnostics mode showing a user exactly where that was.  Default is false, to avoid the overhead.
+    ///
+    /// See `traversed_before_error`.
+    pub fn collect_trace_on_error(mut self, collect_trace_on_error: bool) -> Self {
+        self.collect_trace_on_error = collect_trace_on_error;
+        self
+    }
+
+    /// Whether resolving `..` from a directory onto its parent on a different device, that is, out of a mount point,
+    /// is an error of kind `CrossesDevices`.  Default is false.
+    ///
+    /// This relies on `FileSystem::dir_identity`, so is a no-op for a filesystem without device identities.
+    #[cfg(target_family = "unix")]
+    pub fn stay_on_device(mut self, stay_on_device: bool) -> Self {
+        self.stay_on_device = stay_on_device;
+        self
+    }
+
+    /// Whether the real parent of a dangling symlink is the directory containing the link,
+    /// rather than an error since its target doesn't exist.  Default is false.
+    ///
+    /// This applies only where the symlink is the final component, so that `..` after a dangling symlink remains an error.
+    pub fn keep_dangling_symlinks(mut self, keep: bool) -> Self {
+        self.keep_dangling_symlinks = keep;
+        self
+    }
+
+    /// Whether to expand any 8.3 short names in results, such as `PROGRA~1`, to their long form, as per `GetLongPathNameW`,
+    /// so that results are consistent regardless of the form of the input.  Default is false.
+    ///
+    /// Expansion is of the result of resolution on the real filesystem, and a result which cannot be expanded is left as is.
+    #[cfg(target_family = "windows")]
+    pub fn expand_short_names(mut self, expand: bool) -> Self {
+        self.expand_short_names = expand;
+        self
+    }
+
+    /// Whether to replace each component of results with its on-disk case, on platforms whose default filesystems are case-insensitive,
+    /// so that `SRC/main.rs` resolves to `src` if that is the real name.  Default is false, and this is a no-op on other platforms.
+    ///
+    /// The on-disk case is found by reading each directory in the result on the real filesystem, so this is relatively expensive.
+    pub fn correct_case(mut self, correct: bool) -> Self {
+        self.correct_case = correct;
+        self
+    }
+
+    /// The maximum length in bytes of any path while it is being resolved, beyond which resolution is an error of kind `InvalidFilename`.
+    /// Default is unlimited.
+    ///
+    /// This guards against a crafted chain of symlinks on an untrusted filesystem inflating the path being resolved.
+    /// The limit applies to paths as they are built up by following symlinks and resolving `..`, not to the input path itself.
+    pub fn max_resolved_len(mut self, max: usize) -> Self {
+        self.max_resolved_len = Some(max);
+        self
+    }
+
+    /// The maximum number of filesystem calls in resolving any single path, beyond which resolution is an error of kind `QuotaExceeded`.
+    /// Default is unlimited.
+    ///
+    /// This bounds the cost of resolving a hostile path more directly than `max_resolved_len`, since it accounts for every call,
+    /// whether examining an entry, reading a symlink, or finding a directory identity.  Each call to a `Resolver` method starts afresh,
+    /// except that stepwise resolution via `real_parent_step` is counted from `real_parent_start`.
+    pub fn max_io_ops(mut self, max: usize) -> Self {
+        self.max_io_ops = Some(max);
+        self
+    }
+
+    /// Follow only symlinks whose targets are within one of `prefixes`, so that following any other symlink
+    /// is an error of kind `PermissionDenied`.  Default is to follow symlinks wherever they lead.
+    ///
+    /// Unlike `real_resolve_abs`, which bounds the result, this bounds each symlink target as it is followed.
+    /// A target is checked once joined to the directory containing the symlink and made absolute lexically,
+    /// since any further symlinks which it traverses are themselves checked as they are followed.
+    /// Relative prefixes are with respect to the base, if any.
+    pub fn allowed_target_prefixes(mut self, prefixes: Vec<PathBuf>) -> Self {
+        self.allowed_target_prefixes = Some(prefixes);
+        self
+    }
+
+    /// Call `callback` with the link and its target as read, each time a symlink is followed,
+    /// for example to log resolution as it happens, without collecting a trace as per `resolve_trace`.
+    ///
+    /// The callback merely observes, and cannot affect resolution.
+    pub fn on_symlink(mut self, callback: Box<SymlinkFn>) -> Self {
+        self.on_symlink = Some(SymlinkCallback(Mutex::new(callback)));
+        self
+    }
+
+    /// How Linux magic symlinks for open files are resolved.  Default is `MagicLinks::Follow`.
+    #[cfg(target_os = "linux")]
+    pub fn magic_links(mut self, magic_links: MagicLinks) -> Self {
+        self.magic_links = magic_links;
+        self
+    }
+
+    /// Resolve relative paths with respect to the open directory `dir` rather than the current directory, as per `openat`,
+    /// so that resolution is unaffected by the current directory or any ancestor of `dir` being changed or renamed meanwhile.
+    ///
+    /// Results for relative paths are relative to `dir`.  See `AtDirFileSystem`.
+    #[cfg(all(feature = "at-dir", target_family = "unix"))]
+    pub fn at_dir<F>(self, dir: F) -> io::Result<Self>
+    where
+        F: std::os::fd::AsFd,
+    {
+        Ok(self.with_file_system(super::AtDirFileSystem::new(dir)?))
+    }
+
+    /// Confine resolution to the open directory `dir`, as a capability, such as a `cap_std::fs::Dir`, or any other directory handle.
+    ///
+    /// As per `at_dir`, relative paths are resolved with respect to `dir`, and further, `dir` is the `virtual_root`, so that `..` cannot ascend above it.
+    /// Any absolute path, whether given or as the target of a symlink, is an error of kind `PermissionDenied`, since it would escape `dir`.
+    /// Every filesystem call is itself confined beneath `dir`, as per `AtDirFileSystem::beneath`,
+    /// so that this holds even where the operating system follows a symlink in an intermediate component.
+    #[cfg(all(feature = "at-dir", target_os = "linux"))]
+    pub fn in_capability<F>(self, dir: F) -> io::Result<Self>
+    where
+        F: std::os::fd::AsFd,
+    {
+        let mut resolver = self
+            .with_file_system(super::AtDirFileSystem::new(dir)?.beneath())
+            .virtual_root(".");
+        resolver.confined = true;
+        Ok(resolver)
+    }
+
+    /// Retry each filesystem call which fails with a transient error, such as a stale file handle on a network filesystem,
+    /// or an interrupted system call, making up to `attempts` attempts in all, sleeping for `backoff` between attempts.
+    /// Other errors, such as `NotFound` or `PermissionDenied`, are returned immediately.  Default is no retries.
+    ///
+    /// This applies to the filesystem in effect when called, so must follow any of `with_file_system`, `snapshot`, or `at_dir`.
+    pub fn retry(mut self, attempts: u32, backoff: Duration) -> Self {
+        if attempts > 1 {
+            self.fs = Arc::new(RetryFileSystem::new(self.fs, attempts, backoff));
+        }
+        self
+    }
+
+    /// Abandon any filesystem call which takes longer than `timeout`, such as on an unresponsive network filesystem,
+    /// failing with `io::ErrorKind::TimedOut`.  Default is no timeout.
+    ///
+    /// Blocking filesystem calls cannot be interrupted, so each call is run on its own thread,
+    /// and a call which times out is left running in the background until the operating system returns.
+    /// This costs a thread per call, so is only worthwhile where hangs are a real possibility.
+    ///
+    /// This applies to the filesystem in effect when called, so must follow any of `with_file_system`, `snapshot`, or `at_dir`,
+    /// and should precede `retry` if timeouts are not to be retried.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.fs = Arc::new(TimeoutFileSystem::new(self.fs, timeout));
+        self
+    }
+
+    /// Whether to cache the results of `real_parent`, keyed both by path and by directory identity,
+    /// so that once any alias of a directory has been resolved, other aliases reuse the result.  Default is false.
+    ///
+    /// Cached results are stale if the filesystem or the current directory changes, and for a directory reached by several aliases,
+    /// the result is that found for whichever was resolved first, so is correct but not necessarily minimal for the others.
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.cache = cache.then(ParentCache::default);
+        self
+    }
+
+    /// Whether a cached result of `real_parent` is validated before use, by checking that the modification time of the path,
+    /// without following a trailing symlink, is unchanged since the result was cached, and otherwise resolving it afresh.
+    /// Default is false.
+    ///
+    /// For example, for a long-lived service resolving paths as symlinks are retargeted, at the cost of an extra stat per lookup.
+    /// Only the path itself is checked, not any symlink among its ancestors.
+    /// This has no effect unless caching is enabled, see `cache`, and relies on `FileSystem::modified`.
+    pub fn validate_cache_mtime(mut self, validate: bool) -> Self {
+        self.validate_cache_mtime = validate;
+        self
+    }
+
+    /// Whether to intern the results of `real_parent_interned`, `real_clean_interned`, and with the `camino` feature `real_parent_arc_str`,
+    /// so that repeated results share storage, and each component name, such as `src`, is stored only once
+    /// however many results contain it.  See `intern_stats`.  Default is false.
+    pub fn intern(mut self, intern: bool) -> Self {
+        self.interner = intern.then(Interner::default);
+        self
+    }
+
+    /// Whether to expand environment variable references in paths passed to `real_parent` and `real_clean`,
+    /// before resolution.  Default is false, since `$` and `%` are legitimate filename characters.
+    ///
+    /// See `expand_env_with` for the syntax.  An undefined variable is an error of kind `InvalidInput`.
+    pub fn expand_env(mut self, expand_env: bool) -> Self {
+        self.expand_env = expand_env;
+        self
+    }
+
+    /// Whether to expand a leading `~` and environment variable references in symlink targets, as they are read during resolution.
+    /// Default is false, since the OS takes targets literally, so a target of `~/foo` refers to a directory named `~`.
+    ///
+    /// This is independent of `expand_env`, which applies only to the paths passed in.
+    /// The home directory is `$HOME`, or `%USERPROFILE%` on Windows, and `~user` is left unchanged.
+    /// See `expand_env_with` for the syntax of variable references.  An undefined variable is an error of kind `InvalidInput`.
+    pub fn expand_link_targets(mut self, expand: bool) -> Self {
+        self.expand_link_targets = expand;
+        self
+    }
+
+    /// What the real parent of the root directory is, including any virtual root.  Default is `RootParentPolicy::SelfSame`.
+    ///
+    /// For example, `RootParentPolicy::None` eases migration of code expecting `Path::parent`, in conjunction with `real_parent_opt`.
+    /// Whether a path is the root is as per `is_real_root`, so it may be relative or contain symlinks.
+    pub fn root_parent(mut self, policy: RootParentPolicy) -> Self {
+        self.root_parent = policy;
+        self
+    }
+
+    /// Options for the results of `real_parent` and `real_clean`.  Default is `CleanOptions::default()`.
+    pub fn clean_options(mut self, options: CleanOptions) -> Self {
+        self.clean_options = options;
+        self
+    }
+
+    /// As per `PathExt::real_parent`.
+    pub fn real_parent(&mut self, path: &Path) -> io::Result<PathBuf> {
+        let path = self.expanded(path)?;
+        let path = self.based(&path).into_owned();
+        if self.root_parent == RootParentPolicy::Error {
+            self.check_not_root(&path)?;
+        }
+        self.based_parent(&path)
+    }
+
+    /// As per `real_parent` for each of `paths`, but resolving each `..` from any directory only once across the batch,
+    /// by means of a prefix trie of the directories resolved so far.
+    ///
+    /// For example, for every file in a deep monorepo, where many symlinks have relative targets ascending the same directories,
+    /// perhaps via symlinks themselves.  Since such work is shared, `on_symlink` is called only for symlinks followed afresh,
+    /// and the trie is discarded afterwards, so that changes to the filesystem are seen by later resolutions.
+    pub fn real_parents_trie(&mut self, paths: &[PathBuf]) -> Vec<io::Result<PathBuf>> {
+        self.trie = Some(PrefixTrie::default());
+        let parents = paths.iter().map(|path| self.real_parent(path)).collect();
+        self.trie = None;
+
+        parents
+    }
+
+    /// As per `real_parent`, but returning `None` for the root directory under `RootParentPolicy::None`, see `root_parent`.
+    pub fn real_parent_opt(&mut self, path: &Path) -> io::Result<Option<PathBuf>> {
+        let path = self.expanded(path)?;
+        let path = self.based(&path).into_owned();
+        match self.root_parent {
+            RootParentPolicy::SelfSame => (),
+            RootParentPolicy::None => {
+                if self.is_root(&path)? {
+                    return Ok(None);
+                }
+            }
+            RootParentPolicy::Error => self.check_not_root(&path)?,
+        }
+        self.based_parent(&path).map(Some)
+    }
+
+    // the real parent of a path which has already been expanded and made relative to the base
+    fn based_parent(&mut self, path: &Path) -> io::Result<PathBuf> {
+        let parent = self.parent(path)?;
+        let parent = self.checked_leading_parent(path, parent)?;
+        self.styled(parent)
+    }
+
+    fn check_not_root(&self, path: &Path) -> io::Result<()> {
+        if self.is_root(path)? {
+            Err(Error::RootParent(path.to_path_buf()).into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// As per `real_parent`, but also return the cost of resolution.
+    ///
+    /// Any cache is bypassed, so that the statistics reflect the full cost of resolving the path.
+    pub fn real_parent_metered(&mut self, path: &Path) -> io::Result<(PathBuf, ResolveStats)> {
+        let (_, parent, stats) = self.unstyled_parent_metered(path)?;
+        self.styled(parent).map(|parent| (parent, stats))
+    }
+
+    // the path as resolved against any base, its real parent prior to output styling, and the cost of resolution
+    fn unstyled_parent_metered(
+        &mut self,
+        path: &Path,
+    ) -> io::Result<(PathBuf, PathBuf, ResolveStats)> {
+        let path = self.expanded(path)?;
+        let path = self.based(&path).into_owned();
+        let mut real_path = self.real_path();
+        let parent = real_path
+            .leaf_parent(&path)
+            .map(empty_to_dot)
+            .map_err(|e| io::Error::from(real_path.traced(e.resolving(&path))))?;
+
+        let stats = real_path.stats;
+        let parent = self.checked_leading_parent(&path, parent)?;
+        Ok((path, parent, stats))
+    }
+
+    /// As per `real_parent`, but returning the details of resolution together, see `Resolution`.
+    ///
+    /// As for `real_parent_metered`, any cache is bypassed.
+    pub fn resolve_detailed(&mut self, path: &Path) -> io::Result<Resolution> {
+        let (based, parent, stats) = self.unstyled_parent_metered(path)?;
+        let crossed_mount = self.crossed_mount(&based, &parent);
+        let real_parent = self.styled(parent)?;
+
+        Ok(Resolution {
+            input: path.to_path_buf(),
+            real_parent,
+            crossed_symlink: stats.symlink_hops > 0,
+            hops: stats.symlink_hops,
+            crossed_mount,
+            // every symlink on Windows is a reparse point, as is every junction and mounted folder
+            crossed_reparse: cfg!(target_family = "windows") && stats.symlink_hops > 0,
+        })
+    }
+
+    // whether the real parent is on a different device from the directory lexically containing the path, false if either device is unknown
+    fn crossed_mount(&self, path: &Path, real_parent: &Path) -> bool {
+        let device = |dir: &Path| {
+            let dir = empty_to_dot(dir.to_path_buf());
+            self.fs
+                .dir_identity(&dir)
+                .ok()
+                .flatten()
+                .map(|identity| identity.device())
+        };
+        let lexical_parent = path.parent().unwrap_or(path);
+
+        matches!(
+            (device(lexical_parent), device(real_parent)),
+            (Some(lexical_device), Some(real_device)) if lexical_device != real_device
+        )
+    }
+
+    /// As per `PathExt::real_clean`.
+    pub fn real_clean(&mut self, path: &Path) -> io::Result<PathBuf> {
+        let path = self.expanded(path)?;
+        let path = self.based(&path).into_owned();
+        let clean = self.clean(&path)?;
+        let clean = self.checked_leading_parent(&path, clean)?;
+        self.styled(clean)
+    }
+
+    /// As per `real_clean` of `resolved_base` joined with `rel`, but trusting that `resolved_base` is already the result of `real_clean`,
+    /// so that only the components of `rel` are resolved, for example when walking into subdirectories.
+    ///
+    /// Symlinks among the components of `rel` are resolved as required, just as by `real_clean`, as is any `..` in `rel`
+    /// which ascends above `resolved_base`.  Where `resolved_base` is not already clean, the result may be incorrect.
+    /// An absolute `rel` replaces `resolved_base`, as for `Path::join`.
+    pub fn real_clean_under(&mut self, resolved_base: &Path, rel: &Path) -> io::Result<PathBuf> {
+        let rel = self.expanded(rel)?;
+        let resolved_base = self.based(resolved_base);
+        let path = resolved_base.join(&rel);
+        // `.` is how `real_clean` expresses the empty path
+        let origin = if resolved_base.as_ref() == AsRef::<Path>::as_ref(DOT) {
+            Path::new("")
+        } else {
+            resolved_base.as_ref()
+        };
+
+        let mut real_path = self.real_path();
+        let clean = real_path
+            .join(origin, &rel)
+            .map(empty_to_dot)
+            .map_err(|e| io::Error::from(real_path.traced(e.resolving(&path))))?;
+        let clean = self.checked_leading_parent(&path, clean)?;
+        self.styled(clean)
+    }
+
+    /// Begin stepwise resolution of the real parent of `path`, see `real_parent_step`.
+    pub fn real_parent_start(&self, path: &Path) -> io::Result<ResolveState> {
+        let path = self.expanded(path)?;
+        let path = self.based(&path).into_owned();
+        if self.root_parent == RootParentPolicy::Error {
+            self.check_not_root(&path)?;
+        }
+        Ok(ResolveState::new(path))
+    }
+
+    /// Perform a single unit of work towards resolving a real parent, that is, a single level of parent resolution,
+    /// or a single component of a symlink target.
+    ///
+    /// Calling this repeatedly until it returns `Done` is equivalent to `real_parent`, with all the same options, except that any cache is bypassed,
+    /// but allows control to be returned to the caller between steps, for example to keep a user interface responsive.
+    pub fn real_parent_step(&mut self, state: ResolveState) -> io::Result<StepResult> {
+        let original = state.original().to_path_buf();
+        let mut real_path = self.real_path();
+
+        match state.step(&mut real_path).map_err(io::Error::from)? {
+            StepResult::Done(parent) => {
+                let parent = self.checked_leading_parent(&original, parent)?;
+                self.styled(parent).map(StepResult::Done)
+            }
+            more => Ok(more),
+        }
+    }
+
+    /// Trace the resolution of `path` component by component, including the targets of any symlinks encountered,
+    /// for example to visualize how a path is resolved.
+    pub fn resolve_trace(&mut self, path: &Path) -> io::Result<Vec<ResolvedStep>> {
+        let path = self.expanded(path)?;
+        let path = self.based(&path).into_owned();
+        let mut real_path = self.real_path();
+
+        real_path
+            .trace(&path)
+            .map_err(|e| e.resolving(&path).into())
+    }
+
+    /// Explain what cleaning `path` would involve, without touching the filesystem,
+    /// including which components may be symlinks, and so require filesystem access to resolve correctly.
+    ///
+    /// For example, to warn that a path relies on symlink resolution where the real tree is not present.
+    /// Relative paths are taken with respect to the base, if any, but environment variables are not expanded.
+    pub fn explain_lexical(&self, path: &Path) -> Explanation {
+        explain_lexically(&self.based(path))
+    }
+
+    /// As per `PathExt::real_ancestors`.
+    pub fn real_ancestors(&mut self, path: &Path) -> RealAncestors<&mut Self> {
+        RealAncestors::new(self, path)
+    }
+
+    /// As per `real_ancestors`, except that `should_continue` is checked before ascending each level,
+    /// and if it returns false the iterator yields an error of kind `Interrupted` and ends.
+    ///
+    /// This allows a long walk to be cancelled, for example on shutdown.
+    pub fn real_ancestors_cancellable<F>(
+        &mut self,
+        path: &Path,
+        should_continue: F,
+    ) -> RealAncestors<&mut Self>
+    where
+        F: Fn() -> bool + 'static,
+    {
+        RealAncestors::new(self, path).cancellable(should_continue)
+    }
+
+    /// As per `real_parent`, returning a path which is shared with any identical previous result if interning is enabled.
+    pub fn real_parent_interned(&mut self, path: &Path) -> io::Result<Arc<Path>> {
+        self.real_parent(path).map(|p| self.interned(p))
+    }
+
+    /// As per `real_clean`, returning a path which is shared with any identical previous result if interning is enabled.
+    pub fn real_clean_interned(&mut self, path: &Path) -> io::Result<Arc<Path>> {
+        self.real_clean(path).map(|p| self.interned(p))
+    }
+
+    /// As per `real_parent_interned`, but for a UTF-8 path, returning a string, for example for a language server which holds paths as strings.
+    ///
+    /// It is an error of kind `InvalidData` if the real parent is not valid UTF-8, as it may not be, where reached through a symlink.
+    #[cfg(feature = "camino")]
+    pub fn real_parent_arc_str(&mut self, path: &camino::Utf8Path) -> io::Result<Arc<str>> {
+        let parent = self.real_parent(path.as_std_path())?;
+        let parent = parent
+            .into_os_string()
+            .into_string()
+            .map_err(|parent| Error::NotUnicode(parent.into()))?;
+
+        Ok(match self.interner.as_mut() {
+            Some(interner) => interner.intern_str(parent),
+            None => parent.into(),
+        })
+    }
+
+    /// Find the nearest real ancestor of `path`, including `path` itself, which contains an entry called `file_name`,
+    /// returning the path of that entry.
+    ///
+    /// The search does not extend beyond any marker root, see `stop_at_marker`.
+    pub fn find_upwards<S>(&mut self, path: &Path, file_name: S) -> io::Result<Option<PathBuf>>
+    where
+        S: AsRef<OsStr>,
+    {
+        let file_name = file_name.as_ref();
+        let fs = self.fs.clone();
+
+        for ancestor in self.real_ancestors(path) {
+            let candidate = ancestor?.join(file_name);
+            if exists(fs.as_ref(), &candidate)? {
+                return Ok(Some(candidate));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Resolve `rel` with respect to each of `bases` in turn, as per `real_clean`, returning the first which exists,
+    /// following any trailing symlink, or `None` if there is none.
+    ///
+    /// For example, for resource lookup in a search path such as include directories.
+    /// A base for which resolution fails because some component doesn't exist is skipped, but any other failure is an error.
+    /// If `rel` is absolute, it is the only candidate.
+    pub fn real_clean_in_first(
+        &mut self,
+        bases: &[PathBuf],
+        rel: &Path,
+    ) -> io::Result<Option<PathBuf>> {
+        let fs = self.fs.clone();
+
+        for base in bases {
+            let clean = match self.real_clean(&base.join(rel)) {
+                Ok(clean) => clean,
+                Err(e) if is_not_found(&e) => continue,
+                Err(e) => return Err(e),
+            };
+
+            match fs.target_kind(&clean) {
+                Ok(_) => return Ok(Some(clean)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// The logical root for `path` as discovered by `stop_at_marker`,
+    /// or `None` if there is no such marker, or no ancestor contains it.
+    pub fn marker_root(&mut self, path: &Path) -> io::Result<Option<PathBuf>> {
+        if self.stop_marker.is_none() {
+            return Ok(None);
+        }
+
+        // iteration ends at the marker root, if any
+        let mut last = None;
+        for ancestor in self.real_ancestors(path) {
+            last = Some(ancestor?);
+        }
+
+        match last {
+            Some(last) if self.is_marker_root(&last)? => Ok(Some(last)),
+            _ => Ok(None),
+        }
+    }
+
+    /// As per `PathExt::is_real_root`, but on the resolver's filesystem, see `FileSystem::is_root`,
+    /// and the virtual root, if any, is also a root, as is any path satisfying the root predicate, if any.
+    pub fn is_real_root(&self, path: &Path) -> io::Result<bool> {
+        self.is_root(&self.based(path))
+    }
+
+    /// Cache statistics, or `None` if caching is not enabled.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(ParentCache::stats)
+    }
+
+    /// Interning statistics, or `None` if interning is not enabled.
+    pub fn intern_stats(&self) -> Option<InternStats> {
+        self.interner.as_ref().map(Interner::stats)
+    }
+
+    // real parent of a path already adjusted for the base directory
+    pub(crate) fn parent(&mut self, path: &Path) -> io::Result<PathBuf> {
+        if self.cache.is_none() {
+            return self.uncached_parent(path);
+        }
+
+        // a missing directory is not an error here, since resolution reports that with better context
+        let identity = self.fs.dir_identity(path).unwrap_or(None);
+        let modified = if self.validate_cache_mtime {
+            self.fs.modified(path).unwrap_or(None)
+        } else {
+            None
+        };
+        if let Some(parent) = self
+            .cache
+            .as_mut()
+            .and_then(|c| c.get(path, identity, modified))
+        {
+            return Ok(parent);
+        }
+
+        let parent = self.uncached_parent(path)?;
+        if let Some(cache) = self.cache.as_mut() {
+            cache.insert(path, identity, modified, &parent);
+        }
+
+        Ok(parent)
+    }
+
+    fn uncached_parent(&mut self, path: &Path) -> io::Result<PathBuf> {
+        // any trie for the batch in progress is lent to the resolution, and taken back afterwards
+        let trie = self.trie.take();
+        let mut real_path = self.real_path().with_trie(trie);
+        let parent = real_path
+            .leaf_parent(path)
+            .map(empty_to_dot)
+            .map_err(|e| real_path.traced(e.resolving(path)).into());
+        let trie = real_path.trie.take();
+        self.trie = trie;
+
+        parent
+    }
+
+    // real clean of a path already adjusted for the base directory
+    pub(crate) fn clean(&mut self, path: &Path) -> io::Result<PathBuf> {
+        let mut real_path = self.real_path();
+        real_path
+            .clean(path)
+            .map(empty_to_dot)
+            .map_err(|e| real_path.traced(e.resolving(path)).into())
+    }
+
+    // whether the path contains the stop marker, if any
+    pub(crate) fn is_marker_root(&self, path: &Path) -> io::Result<bool> {
+        match self.stop_marker.as_ref() {
+            Some(marker) => exists(self.fs.as_ref(), &path.join(marker)),
+            None => Ok(false),
+        }
+    }
+
+    // whether a path already adjusted for the base directory is either the virtual root or the real one
+    pub(crate) fn is_root(&self, path: &Path) -> io::Result<bool> {
+        Ok(self.real_path().is_virtual_root(path) || self.fs.is_root(path)?)
+    }
+
+    // the path with respect to the base directory, if any
+    pub(crate) fn based<'a>(&self, path: &'a Path) -> Cow<'a, Path> {
+        match self.base.as_ref() {
+            Some(base) if path.is_relative() => base.join(path).into(),
+            _ => path.into(),
+        }
+    }
+
+    fn real_path(&self) -> RealPath<'_> {
+        RealPath::new(self.fs.as_ref())
+            .with_follow_policy(self.follow)
+            .with_opaque_magic_links(self.magic_links == MagicLinks::Opaque)
+            .with_stay_on_device(self.stay_on_device)
+            .with_keep_dangling_symlinks(self.keep_dangling_symlinks)
+            .with_max_resolved_len(self.max_resolved_len)
+            .with_max_io_ops(self.max_io_ops)
+            .with_confined(self.confined)
+            .with_expand_link_targets(self.expand_link_targets)
+            .with_check_link_types(self.check_link_types)
+            .with_collect_trace(self.collect_trace_on_error)
+            .with_allowed_target_prefixes(self.allowed_target_prefixes.as_ref().map(|prefixes| {
+                prefixes
+                    .iter()
+                    .map(|prefix| self.based(prefix).into_owned())
+                    .collect()
+            }))
+            .with_on_symlink(self.on_symlink.as_ref())
+            .with_root_predicate(self.root_predicate.as_ref())
+            .with_virtual_root(
+                self.virtual_root
+                    .as_ref()
+                    .map(|root| self.based(root).into_owned()),
+            )
+    }
+
+    // a result expressed according to the output style
+    fn styled(&self, path: PathBuf) -> io::Result<PathBuf> {
+        let path = if self.expand_short_names {
+            long_path_name(path)
+        } else {
+            path
+        };
+        let path = if self.correct_case {
+            case_corrected(path)
+        } else {
+            path
+        };
+
+        match &self.output_style {
+            OutputStyle::AsResolved => Ok(path),
+            OutputStyle::Absolute => std::path::absolute(path),
+            OutputStyle::RelativeTo(dir) => {
+                let dir = self.based(dir);
+                let (path, dir) = if path.is_absolute() == dir.is_absolute() {
+                    (path, dir.into_owned())
+                } else {
+                    (std::path::absolute(path)?, std::path::absolute(dir)?)
+                };
+
+                self.real_path()
+                    .relative_to(&path, &dir)
+                    .map_err(|e| e.resolving(&path).into())
+            }
+        }
+    }
+
+    // the result of resolving path, unless it has a leading `..` which is disallowed by the clean options
+    fn checked_leading_parent(&self, path: &Path, result: PathBuf) -> io::Result<PathBuf> {
+        if !self.clean_options.preserve_leading_parent
+            && result.components().next() == Some(Component::ParentDir)
+        {
+            Err(Error::AboveBase(path.to_path_buf()).into())
+        } else {
+            Ok(result)
+        }
+    }
+
+    // the path with environment variables expanded, if enabled
+    fn expanded<'a>(&self, path: &'a Path) -> io::Result<Cow<'a, Path>> {
+        if self.expand_env {
+            env::expand(path, |name| std::env::var_os(name))
+                .map(Cow::from)
+                .map_err(io::Error::from)
+        } else {
+            Ok(path.into())
+        }
+    }
+
+    fn interned(&mut self, path: PathBuf) -> Arc<Path> {
+        match self.interner.as_mut() {
+            Some(interner) => interner.intern(path),
+            None => path.into(),
+        }
+    }
+}
+
+// whether the path exists, without following a trailing symlink
+fn exists(fs: &dyn FileSystem, path: &Path) -> io::Result<bool> {
+    match fs.entry_kind(path) {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    mem,
+    path::{Component, Path, PathBuf},
+};
+
+use super::{empty_to_dot, symlink_dir, Error, ParentStep, RealPath, ResolveStats};
+
+/// The state of a stepwise resolution, see `Resolver::real_parent_step`.
+#[derive(Debug)]
+pub struct ResolveState {
+    original: PathBuf,
+    goal: Goal,
+    // joins in progress, innermost last
+    joins: Vec<Join>,
+    // symlinks whose resolution is in progress, outermost first, each with the number of joins in progress before its own
+    symlinks_resolving: Vec<PathBuf>,
+    symlink_depths: Vec<usize>,
+    // the cost of resolution so far, where symlink hops are counted against the limit for a single resolution
+    stats: ResolveStats,
+    // filesystem calls made so far, counted against any budget
+    io_ops: usize,
+    // directories traversed so far, if collecting a trace
+    traversed: Vec<PathBuf>,
+}
+
+/// The result of a single step of resolution.
+#[derive(Debug)]
+pub enum StepResult {
+    /// Resolution is complete.
+    Done(PathBuf),
+    /// Further steps are required.
+    More(ResolveState),
+}
+
+#[derive(Debug)]
+enum Goal {
+    // find the parent of this path, as per `RealPath::leaf_parent` where this is the final component
+    Parent { path: PathBuf, leaf: bool },
+    // pass this path to the innermost join as the parent for `..`, or return it if none
+    Deliver(PathBuf),
+    // process the next component of the innermost join
+    Join,
+}
+
+// joining components to a path, after which the parent of the result is required
+#[derive(Debug)]
+struct Join {
+    resolving: PathBuf,
+    // components remaining, in reverse order
+    pending: Vec<PathBuf>,
+    // whether the parent of the result is for the final component, as when cleaning it
+    leaf: bool,
+}
+
+impl ResolveState {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            original: path.clone(),
+            goal: Goal::Parent { path, leaf: true },
+            joins: Vec::new(),
+            symlinks_resolving: Vec::new(),
+            symlink_depths: Vec::new(),
+            stats: ResolveStats::default(),
+            io_ops: 0,
+            traversed: Vec::new(),
+        }
+    }
+
+    // the path being resolved
+    pub(crate) fn original(&self) -> &Path {
+        &self.original
+    }
+
+    // perform a single step of resolution, that is, a single level of parent resolution or a single component of a join
+    pub(crate) fn step(mut self, real_path: &mut RealPath) -> Result<StepResult, Error> {
+        self.swap(real_path);
+        let result = self.advance(real_path);
+        self.swap(real_path);
+
+        match result.map_err(|e| real_path.traced(e.resolving(&self.original)))? {
+            Some(path) => Ok(StepResult::Done(empty_to_dot(path))),
+            None => Ok(StepResult::More(self)),
+        }
+    }
+
+    // exchange the state carried between steps with that of a fresh `RealPath`
+    fn swap(&mut self, real_path: &mut RealPath) {
+        mem::swap(
+            &mut real_path.symlinks_resolving,
+            &mut self.symlinks_resolving,
+        );
+        mem::swap(&mut real_path.stats, &mut self.stats);
+        self.io_ops = real_path.io_ops.replace(self.io_ops);
+        if let Some(traversed) = real_path.traversed.as_mut() {
+            mem::swap(traversed, &mut self.traversed);
+        }
+    }
+
+    fn advance(&mut self, real_path: &mut RealPath) -> Result<Option<PathBuf>, Error> {
+        use Component::*;
+
+        match mem::replace(&mut self.goal, Goal::Join) {
+            Goal::Parent { path, leaf } => {
+                let step = real_path.parent_step(&path)?;
+                self.goal = match self.descend(real_path, &path, step, leaf)? {
+                    Some(parent) => Goal::Deliver(parent),
+                    None => Goal::Join,
+                };
+            }
+            Goal::Deliver(path) => {
+                // any symlink whose target has been joined and its parent found is now resolved
+                while self
+                    .symlink_depths
+                    .last()
+                    .is_some_and(|depth| *depth >= self.joins.len())
+                {
+                    self.symlink_depths.pop();
+                    self.symlinks_resolving.pop();
+                }
+
+                match self.joins.last_mut() {
+                    Some(join) => {
+                        real_path.remember_parent(&join.resolving, Some(&path));
+                        real_path.ascend(&mut join.resolving, Some(path))?;
+                        real_path.check_resolved_len(&join.resolving)?;
+                    }
+                    None => return Ok(Some(path)),
+                }
+            }
+            Goal::Join => {
+                // unwrap is safe because a join is always in progress when this is the goal
+                let join = self.joins.last_mut().unwrap();
+
+                match join.pending.pop() {
+                    Some(component) => match component.components().next() {
+                        Some(ParentDir) => {
+                            if let Some(parent) = real_path.trie_parent(&join.resolving) {
+                                real_path.ascend(&mut join.resolving, Some(parent))?;
+                            } else {
+                                match real_path.parent_step(&join.resolving)? {
+                                    ParentStep::Pop => {
+                                        real_path.remember_parent(&join.resolving, None);
+                                        real_path.ascend(&mut join.resolving, None)?;
+                                    }
+                                    step => {
+                                        // the parent is delivered to this join in due course
+                                        let resolving = join.resolving.clone();
+                                        if let Some(parent) =
+                                            self.descend(real_path, &resolving, step, false)?
+                                        {
+                                            self.goal = Goal::Deliver(parent);
+                                        }
+                                        return Ok(None);
+                                    }
+                                }
+                            }
+                        }
+                        Some(Normal(_) | Prefix(_) | RootDir) => join.resolving.push(component),
+                        _ => (),
+                    },
+                    None => {
+                        // unwrap is safe because we have just examined it
+                        let join = self.joins.pop().unwrap();
+                        self.goal = Goal::Parent {
+                            path: join.resolving,
+                            leaf: join.leaf,
+                        };
+                        return Ok(None);
+                    }
+                }
+
+                // unwrap is safe because the join is still in progress
+                real_path.check_resolved_len(&self.joins.last().unwrap().resolving)?;
+            }
+        }
+
+        Ok(None)
+    }
+
+    // follow a single level of parent resolution of path, returning the parent if that is immediate,
+    // or otherwise beginning the join on which it depends, as per `RealPath::leaf_parent` and `RealPath::step_parent`
+    fn descend(
+        &mut self,
+        real_path: &mut RealPath,
+        path: &Path,
+        step: ParentStep,
+        leaf: bool,
+    ) -> Result<Option<PathBuf>, Error> {
+        match step {
+            ParentStep::Parent(parent) => Ok(Some(parent)),
+            // unwrap is safe because the last path component is normal
+            ParentStep::Pop => Ok(Some(path.parent().unwrap().to_path_buf())),
+            ParentStep::Symlink(path, _)
+                if leaf && real_path.keep_dangling_symlinks && real_path.is_dangling(&path)? =>
+            {
+                symlink_dir(&path).map(Some)
+            }
+            ParentStep::Clean(path) => {
+                self.begin_join(real_path, PathBuf::new(), &path, leaf)?;
+                Ok(None)
+            }
+            ParentStep::Symlink(path, target) => {
+                let (symlink_dir, target) = real_path.symlink_hop(&path, target)?;
+                self.symlink_depths.push(self.joins.len());
+                self.begin_join(real_path, symlink_dir, &target, false)?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn begin_join(
+        &mut self,
+        real_path: &RealPath,
+        origin: PathBuf,
+        other: &Path,
+        leaf: bool,
+    ) -> Result<(), Error> {
+        let resolving = real_path.join_start(&origin, other)?;
+
+        self.joins.push(Join {
+            resolving,
+            pending: other
+                .components()
+                .rev()
+                .map(|c| c.as_os_str().into())
+                .collect(),
+            leaf,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+//! Assertions for testing path logic built on this crate, enabled by the `testing` feature.
+
+use std::path::Path;
+
+/// Assert that `actual` and `expected` are equivalent paths, that is, either logically equal, as paths,
+/// or else referring to the same file or directory once canonicalized.
+///
+/// This accepts a result which differs from what was expected only in how much it has been resolved,
+/// for example `A/_B/..` where `A/_B` is a symlink to `A/B`, which is equivalent to `A`.
+///
+/// # Panics
+///
+/// If the paths are not equivalent, or if they are not logically equal and either fails to canonicalize.
+#[track_caller]
+pub fn assert_real_eq<P1, P2>(actual: P1, expected: P2)
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+{
+    let (actual, expected) = (actual.as_ref(), expected.as_ref());
+    if actual == expected {
+        return;
+    }
+
+    let canonicalize = |path: &Path| {
+        path.canonicalize()
+            .unwrap_or_else(|e| panic!("canonicalize({:?}) failed: {}", path, e))
+    };
+
+    assert_eq!(
+        canonicalize(actual),
+        canonicalize(expected),
+        "neither logical nor canonical paths are equal for {:?} and {:?}",
+        actual,
+        expected
+    );
+}
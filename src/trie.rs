@@ -0,0 +1,41 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+/// Trie of resolved directory paths, each with its real parent once resolved,
+/// so that many paths sharing a prefix resolve each `..` along it only once.
+#[derive(Default, Debug)]
+pub(crate) struct PrefixTrie {
+    root: Node,
+}
+
+#[derive(Default, Debug)]
+struct Node {
+    parent: Option<PathBuf>,
+    children: HashMap<OsString, Node>,
+}
+
+impl PrefixTrie {
+    pub(crate) fn parent(&self, path: &Path) -> Option<&Path> {
+        let mut node = &self.root;
+        for component in path.components() {
+            node = node.children.get(component.as_os_str())?;
+        }
+
+        node.parent.as_deref()
+    }
+
+    pub(crate) fn insert(&mut self, path: &Path, parent: PathBuf) {
+        let mut node = &mut self.root;
+        for component in path.components() {
+            node = node
+                .children
+                .entry(component.as_os_str().to_os_string())
+                .or_default();
+        }
+
+        node.parent = Some(parent);
+    }
+}
@@ -0,0 +1,118 @@
+#![cfg(feature = "access")]
+
+#[cfg(target_family = "unix")]
+use std::path::Path;
+
+use real_parent::{AccessMode, PathExt};
+#[cfg(target_family = "unix")]
+use test_case::test_case;
+
+#[test_case("A/B/b1", AccessMode::READ, true)]
+#[test_case("A/B/b1", AccessMode::READ | AccessMode::WRITE, true; "read write")]
+#[test_case("A/_C/../b1", AccessMode::READ, true; "dotdot after symlink")]
+#[test_case("A/B/C", AccessMode::READ | AccessMode::EXECUTE, true; "dir")]
+#[cfg(target_family = "unix")]
+fn test_real_access(path: &str, mode: AccessMode, expected: bool) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("A/B/C")
+        .file("A/B/b1")
+        .symlink_rel("A/_C", "B/C");
+
+    // lexically, this would be `A/b1`, which doesn't exist
+    let actual =
+        with_cwd(farm.absolute(".")).run(|path: &Path| path.real_access(mode), Path::new(path));
+    assert_eq!(actual.unwrap(), expected);
+
+    let actual = farm.absolute(path).real_access(mode);
+    assert_eq!(actual.unwrap(), expected);
+}
+
+#[test_case(0o644, false)]
+#[test_case(0o755, true)]
+#[cfg(target_family = "unix")]
+fn test_real_access_execute(mode: u32, expected: bool) {
+    use std::{
+        fs::{set_permissions, Permissions},
+        os::unix::fs::PermissionsExt,
+    };
+
+    let farm = LinkFarm::new();
+
+    farm.file("x1").symlink_rel("_x1", "x1");
+    set_permissions(farm.absolute("x1"), Permissions::from_mode(mode)).unwrap();
+
+    // even privileged users may execute only what is executable by someone, and the symlink is followed
+    for path in ["x1", "_x1"] {
+        let actual = farm.absolute(path).real_access(AccessMode::EXECUTE);
+        assert_eq!(actual.unwrap(), expected, "{}", path);
+    }
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_real_access_read_only() {
+    use std::{
+        fs::{set_permissions, OpenOptions, Permissions},
+        os::unix::fs::PermissionsExt,
+    };
+
+    let farm = LinkFarm::new();
+
+    farm.file("x1");
+    set_permissions(farm.absolute("x1"), Permissions::from_mode(0o444)).unwrap();
+
+    // privileged users are not subject to permissions, so may write anyway
+    let privileged = OpenOptions::new()
+        .write(true)
+        .open(farm.absolute("x1"))
+        .is_ok();
+
+    let actual = farm.absolute("x1").real_access(AccessMode::WRITE);
+    assert_eq!(actual.unwrap(), privileged);
+    let actual = farm.absolute("x1").real_access(AccessMode::READ);
+    assert!(actual.unwrap());
+}
+
+#[test]
+#[cfg(target_family = "windows")]
+fn test_real_access_read_only_windows() {
+    use std::fs::set_permissions;
+
+    let farm = LinkFarm::new();
+
+    farm.file("x1");
+    let path = farm.absolute("x1");
+    let mut permissions = path.metadata().unwrap().permissions();
+    permissions.set_readonly(true);
+    set_permissions(&path, permissions.clone()).unwrap();
+
+    let write = path.real_access(AccessMode::WRITE);
+    let read = path.real_access(AccessMode::READ);
+    let execute = path.real_access(AccessMode::EXECUTE);
+
+    // restore permissions so the farm may be removed, which on Windows is not the hazard this lint warns of
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    set_permissions(&path, permissions).unwrap();
+
+    assert!(!write.unwrap());
+    assert!(read.unwrap());
+    assert!(!execute.unwrap());
+}
+
+#[test]
+fn test_real_access_missing() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A");
+
+    let actual = farm.absolute("A/x1").real_access(AccessMode::READ);
+    assert!(actual.is_err());
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;
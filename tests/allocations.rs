@@ -0,0 +1,88 @@
+// Regression guard for allocator churn, where resolving `..` must not allocate afresh for each component.
+//
+// On Windows, every filesystem call allocates in converting the path to UTF-16, so the test is not run there.
+#![cfg(not(target_family = "windows"))]
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    path::PathBuf,
+};
+
+use real_parent::Resolver;
+use test_case::test_case;
+
+// the system allocator, counting fresh allocations made by the current thread, but not reallocations
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations<T, F>(f: F) -> (T, usize)
+where
+    F: FnOnce() -> T,
+{
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
+// a path which descends and ascends `n` times before arriving at `A/B/b1`
+fn zigzag(farm: &LinkFarm, n: usize) -> PathBuf {
+    let mut path = farm.absolute("A");
+    for _ in 0..n {
+        path.push("B");
+        path.push("..");
+    }
+    path.join("B/b1")
+}
+
+#[test_case(false; "uncached")]
+#[test_case(true; "cached")]
+fn test_real_clean_allocations_independent_of_dotdots(cache: bool) {
+    let farm = LinkFarm::new();
+    farm.dir("A").dir("A/B").file("A/B/b1");
+    let expected = farm.absolute("A/B/b1");
+
+    let mut resolver = Resolver::new().cache(cache);
+    let (few, many) = (zigzag(&farm, 10), zigzag(&farm, 100));
+
+    // many paths through one resolver, so that any warming up is done with
+    for _ in 0..10 {
+        resolver.real_clean(&few).unwrap();
+        resolver.real_clean(&many).unwrap();
+    }
+
+    let (actual_few, allocations_few) = allocations(|| resolver.real_clean(&few).unwrap());
+    let (actual_many, allocations_many) = allocations(|| resolver.real_clean(&many).unwrap());
+
+    assert_eq!(actual_few, expected);
+    assert_eq!(actual_many, expected);
+    assert_eq!(
+        allocations_few, allocations_many,
+        "allocations grow with the number of `..` components"
+    );
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;
@@ -0,0 +1,120 @@
+#![cfg(all(feature = "at-dir", target_family = "unix"))]
+
+#[cfg(target_os = "linux")]
+use std::io;
+use std::{fs, path::Path};
+
+use real_parent::Resolver;
+use test_case::test_case;
+
+#[test_case("B/b1", "B")]
+#[test_case("B/_b1", "B")]
+#[test_case("B/_a1", ".")]
+#[test_case("_B/.", ".")]
+#[test_case("B/C/..", "B/C/../..")]
+#[test_case(".", "..")]
+fn test_real_parent_at_dir(path: &str, expected: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("A/B/C")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("A/B/_b1", "b1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("A/_B", "B");
+
+    let dir = fs::File::open(farm.absolute("A")).unwrap();
+    let mut resolver = Resolver::new().at_dir(&dir).unwrap();
+
+    // neither renaming the directory nor the current directory affects resolution
+    fs::rename(farm.absolute("A"), farm.absolute("Z")).unwrap();
+    let actual = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| resolver.real_parent(Path::new(path)),
+        &mut resolver,
+    );
+
+    assert_eq!(actual.unwrap(), Path::new(expected));
+}
+
+#[test]
+fn test_real_clean_at_dir() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").dir("A/B").symlink_rel("A/_B", "B");
+
+    let dir = fs::File::open(farm.absolute("A")).unwrap();
+    let mut resolver = Resolver::new().at_dir(&dir).unwrap();
+
+    // the directory outlives the original file descriptor
+    drop(dir);
+    assert_eq!(
+        resolver.real_clean(Path::new("_B/../B")).unwrap(),
+        Path::new("B")
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test_case("B/b1", Some("B"))]
+#[test_case("B/_a1", Some("."))]
+#[test_case(".", Some("."); "dot cannot ascend")]
+#[test_case("B/../..", Some("."); "dotdot cannot ascend")]
+#[test_case("_up", Some("."); "relative symlink cannot ascend")]
+#[test_case("_abs", None; "absolute symlink")]
+#[test_case("/etc", None; "absolute path")]
+fn test_real_parent_in_capability(path: &str, expected: Option<&str>) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .file("A/B/b1")
+        .file("A/x1")
+        .file("x1")
+        .symlink_rel("A/B/_a1", "../a1")
+        // ascending above the capability is clamped, so this is to A/x1
+        .symlink_rel("A/_up", "../../x1")
+        .symlink_rel("A/_abs", farm.absolute("x1"));
+
+    let dir = fs::File::open(farm.absolute("A")).unwrap();
+    let mut resolver = Resolver::new().in_capability(&dir).unwrap();
+
+    let actual = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| resolver.real_parent(Path::new(path)),
+        &mut resolver,
+    );
+
+    match expected {
+        Some(expected) => assert_eq!(actual.unwrap(), Path::new(expected)),
+        None => assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::PermissionDenied),
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test_case("_X/x1"; "file")]
+#[test_case("_X/_x1"; "symlink")]
+#[test_case("_X/_x1/y"; "below symlink")]
+#[test_case("B/_X/x1"; "nested")]
+fn test_real_parent_in_capability_intermediate_symlink(path: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("X")
+        .file("X/x1")
+        .symlink_rel("X/_x1", "x1")
+        // each an intermediate component of a path, to a directory outside the capability
+        .symlink_rel("A/_X", farm.absolute("X"))
+        .symlink_rel("A/B/_X", farm.absolute("X"));
+
+    let dir = fs::File::open(farm.absolute("A")).unwrap();
+    let mut resolver = Resolver::new().in_capability(&dir).unwrap();
+
+    let actual = resolver.real_parent(Path::new(path));
+    assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;
@@ -0,0 +1,122 @@
+// Property test that `real_parent` agrees with `canonicalize` on generated link farms.
+//
+// Symlinks are created with arbitrary targets, so may dangle or form cycles, and generated paths may traverse files,
+// so only those paths which canonicalize successfully are checked.
+#![cfg(not(target_family = "windows"))]
+
+use std::path::{Path, PathBuf};
+
+use proptest::{prelude::*, sample::Index};
+use real_parent::PathExt;
+
+#[derive(Debug)]
+struct FarmSpec {
+    // each dir is within the top of the farm or an earlier dir
+    dirs: Vec<Index>,
+    // each file is within the top of the farm or a dir
+    files: Vec<Index>,
+    // each symlink is within the top of the farm or a dir, with target components drawn from all names
+    symlinks: Vec<(Index, Vec<Index>)>,
+    // paths to check, with components drawn from all names
+    paths: Vec<Vec<Index>>,
+}
+
+fn farm_spec() -> impl Strategy<Value = FarmSpec> {
+    let components = || prop::collection::vec(any::<Index>(), 1..5);
+
+    (
+        prop::collection::vec(any::<Index>(), 0..6),
+        prop::collection::vec(any::<Index>(), 0..4),
+        prop::collection::vec((any::<Index>(), components()), 0..6),
+        prop::collection::vec(components(), 1..16),
+    )
+        .prop_map(|(dirs, files, symlinks, paths)| FarmSpec {
+            dirs,
+            files,
+            symlinks,
+            paths,
+        })
+}
+
+// Create the farm, returning all names and the relative paths of all entries.
+fn create_farm(farm: &LinkFarm, spec: &FarmSpec) -> (Vec<String>, Vec<PathBuf>) {
+    // index 0 is the top of the farm
+    let mut dirs = vec![PathBuf::new()];
+    let mut names = vec![".".to_string(), "..".to_string()];
+    let mut entries = Vec::new();
+
+    for (i, parent) in spec.dirs.iter().enumerate() {
+        let path = dirs[parent.index(dirs.len())].join(format!("D{}", i));
+        farm.dir(&path);
+        names.push(format!("D{}", i));
+        entries.push(path.clone());
+        dirs.push(path);
+    }
+
+    for (i, parent) in spec.files.iter().enumerate() {
+        let path = dirs[parent.index(dirs.len())].join(format!("f{}", i));
+        farm.file(&path);
+        names.push(format!("f{}", i));
+        entries.push(path);
+    }
+
+    let symlink_names = (0..spec.symlinks.len()).map(|i| format!("_s{}", i));
+    names.extend(symlink_names);
+
+    for (i, (parent, target)) in spec.symlinks.iter().enumerate() {
+        let path = dirs[parent.index(dirs.len())].join(format!("_s{}", i));
+        let target = target
+            .iter()
+            .map(|c| names[c.index(names.len())].as_str())
+            .collect::<PathBuf>();
+        farm.symlink_rel(&path, target);
+        entries.push(path);
+    }
+
+    (names, entries)
+}
+
+fn check_real_parent_canonicalize(path: &Path) -> Result<(), TestCaseError> {
+    let Ok(canonical) = path.canonicalize() else {
+        return Ok(());
+    };
+    let Some(expected) = canonical.parent() else {
+        return Ok(());
+    };
+
+    let real_parent = path
+        .real_parent()
+        .map_err(|e| TestCaseError::fail(format!("real_parent({:?}): {}", path, e)))?;
+    let actual = real_parent.canonicalize().map_err(|e| {
+        TestCaseError::fail(format!(
+            "canonicalize(real_parent({:?})) = canonicalize({:?}): {}",
+            path, real_parent, e
+        ))
+    })?;
+
+    prop_assert_eq!(actual, expected, "path {:?}", path);
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn test_real_parent_canonicalize(spec in farm_spec()) {
+        let farm = LinkFarm::new();
+        let (names, entries) = create_farm(&farm, &spec);
+
+        let generated = spec.paths.iter().map(|components| {
+            components
+                .iter()
+                .map(|c| names[c.index(names.len())].as_str())
+                .collect::<PathBuf>()
+        });
+
+        for path in entries.into_iter().chain(generated) {
+            check_real_parent_canonicalize(&farm.absolute(path))?;
+        }
+    }
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;
@@ -0,0 +1,81 @@
+// Changing the current directory affects the whole process, so this test is isolated in its own test binary.
+
+use std::{
+    env::{current_dir, set_current_dir},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use real_parent::Resolver;
+
+const THREADS: usize = 4;
+const RESOLUTIONS: usize = 2000;
+
+#[test]
+fn test_capture_cwd_under_contention() {
+    let farm = LinkFarm::new();
+
+    // the same relative path resolves differently in X and Y
+    farm.dir("X")
+        .dir("X/A")
+        .dir("X/A/B")
+        .file("X/A/B/b1")
+        .symlink_rel("X/A/_b1", "B/b1")
+        .dir("Y")
+        .dir("Y/A")
+        .dir("Y/A/C")
+        .file("Y/A/C/c1")
+        .symlink_rel("Y/A/_b1", "C/c1");
+
+    let original_cwd = current_dir().unwrap();
+
+    // each resolver captures X before contention begins
+    set_current_dir(farm.absolute("X")).unwrap();
+    let resolvers = (0..THREADS)
+        .map(|_| Resolver::new().capture_cwd().unwrap())
+        .collect::<Vec<_>>();
+    let expected = farm.absolute("X/A/B");
+
+    let done = Arc::new(AtomicBool::new(false));
+    let changer = {
+        let done = done.clone();
+        let (x, y) = (farm.absolute("X"), farm.absolute("Y"));
+        thread::spawn(move || {
+            for cwd in [&x, &y].into_iter().cycle() {
+                if done.load(Ordering::Relaxed) {
+                    break;
+                }
+                set_current_dir(cwd).unwrap();
+            }
+        })
+    };
+
+    let handles = resolvers
+        .into_iter()
+        .map(|mut resolver| {
+            thread::spawn(move || {
+                (0..RESOLUTIONS)
+                    .map(|_| resolver.real_parent(Path::new("A/_b1")))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for handle in handles {
+        for actual in handle.join().unwrap() {
+            assert_eq!(actual.unwrap(), expected);
+        }
+    }
+
+    done.store(true, Ordering::Relaxed);
+    changer.join().unwrap();
+    set_current_dir(original_cwd).unwrap();
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;
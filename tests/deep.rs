@@ -0,0 +1,174 @@
+// Regression guard for resolution cost in deeply nested directories, where the number of filesystem calls
+// must not grow with the depth of the tree.
+//
+// Paths this deep exceed the Windows `MAX_PATH` limit, so the test is not run there.
+#![cfg(not(target_family = "windows"))]
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use real_parent::{EntryKind, FileIdentity, FileSystem, PathExt, RealFileSystem, Resolver};
+use test_case::test_case;
+
+const DEPTH: usize = 500;
+
+// calls permitted beyond the single `symlink_metadata` on the leaf
+const MAX_ADDITIONAL_CALLS: usize = 1;
+
+// the real filesystem, counting every call made through it
+#[derive(Clone, Default, Debug)]
+struct CountingFileSystem {
+    calls: Arc<AtomicUsize>,
+}
+
+impl CountingFileSystem {
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    fn count(&self) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl FileSystem for CountingFileSystem {
+    fn entry_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        self.count();
+        RealFileSystem.entry_kind(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.count();
+        RealFileSystem.read_link(path)
+    }
+
+    fn target_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        self.count();
+        RealFileSystem.target_kind(path)
+    }
+
+    fn dir_identity(&self, path: &Path) -> io::Result<Option<FileIdentity>> {
+        self.count();
+        RealFileSystem.dir_identity(path)
+    }
+}
+
+// a farm with a symlink-free directory nested `DEPTH` levels deep, containing a single file,
+// returning the relative path of the deepest directory
+fn deep_farm(farm: &LinkFarm) -> PathBuf {
+    let deepest = (0..DEPTH).map(|_| "D").collect::<PathBuf>();
+    fs::create_dir_all(farm.absolute(&deepest)).unwrap();
+    farm.file(deepest.join("x1"));
+
+    deepest
+}
+
+#[test_case("x1", false; "leaf file")]
+#[test_case("x1", true; "leaf file cached")]
+#[test_case(".", false; "leaf dir")]
+#[test_case(".", true; "leaf dir cached")]
+fn test_real_parent_deep(leaf: &str, cache: bool) {
+    let farm = LinkFarm::new();
+    let deepest = deep_farm(&farm);
+    let path = deepest.join(leaf);
+    let expected = if leaf == "." {
+        deepest.parent().unwrap().to_path_buf()
+    } else {
+        deepest.clone()
+    };
+
+    for (path, expected) in [
+        (path.clone(), expected.clone()),
+        (farm.absolute(&path), farm.absolute(&expected)),
+    ] {
+        let fs = CountingFileSystem::default();
+        let mut resolver = Resolver::new().with_file_system(fs.clone()).cache(cache);
+
+        let actual = with_cwd(farm.absolute(".")).run(
+            |resolver: &mut Resolver| resolver.real_parent(&path),
+            &mut resolver,
+        );
+
+        assert_eq!(actual.unwrap(), expected);
+        assert!(
+            fs.calls() <= 1 + MAX_ADDITIONAL_CALLS,
+            "{} filesystem calls for real_parent at depth {}",
+            fs.calls(),
+            DEPTH
+        );
+    }
+}
+
+#[test]
+fn test_real_parents_trie_deep() {
+    const FILES: usize = 50;
+
+    let farm = LinkFarm::new();
+    let deepest = deep_farm(&farm);
+    // the deepest directory, reached via a symlink, contains symlinks whose targets ascend into a shared directory
+    farm.symlink_rel("_D", &deepest);
+    farm.dir("L");
+    let ascent = (0..DEPTH).map(|_| "..").collect::<PathBuf>();
+    for i in 0..FILES {
+        farm.file(format!("L/l{}", i));
+        farm.symlink_rel(
+            deepest.join(format!("_l{}", i)),
+            ascent.join(format!("L/l{}", i)),
+        );
+    }
+
+    let mut paths = (0..FILES)
+        .map(|i| farm.absolute(format!("_D/_l{}", i)))
+        .collect::<Vec<_>>();
+    paths.push(farm.absolute("_D/x1"));
+    paths.push(farm.absolute("_D/missing"));
+
+    let independent_fs = CountingFileSystem::default();
+    let mut resolver = Resolver::new().with_file_system(independent_fs.clone());
+    let expected = paths
+        .iter()
+        .map(|path| resolver.real_parent(path))
+        .collect::<Vec<_>>();
+
+    let trie_fs = CountingFileSystem::default();
+    let mut resolver = Resolver::new().with_file_system(trie_fs.clone());
+    let actual = resolver.real_parents_trie(&paths);
+
+    assert_eq!(actual.len(), expected.len());
+    for (path, (actual, expected)) in paths.iter().zip(actual.iter().zip(expected.iter())) {
+        match (actual, expected) {
+            (Ok(actual), Ok(expected)) => assert_eq!(actual, expected, "{:?}", path),
+            (Err(actual), Err(expected)) => {
+                assert_eq!(actual.kind(), expected.kind(), "{:?}", path)
+            }
+            _ => panic!("{:?}: {:?} != {:?}", path, actual, expected),
+        }
+    }
+    assert_eq!(actual[0].as_ref().unwrap(), &farm.absolute("L"));
+
+    // each `..` along the shared ascent is resolved only once
+    assert!(trie_fs.calls() < independent_fs.calls() / 10);
+
+    // and the trie is discarded afterwards
+    let calls = trie_fs.calls();
+    resolver.real_parent(&paths[0]).unwrap();
+    assert!(trie_fs.calls() - calls > DEPTH);
+}
+
+#[test]
+fn test_is_real_root_deep() {
+    let farm = LinkFarm::new();
+    let deepest = farm.absolute(deep_farm(&farm));
+
+    assert!(!deepest.is_real_root().unwrap());
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;
@@ -0,0 +1,130 @@
+use std::{ffi::OsString, io, path::Path};
+
+use real_parent::{expand_env_with, Resolver};
+use test_case::test_case;
+
+fn lookup(name: &str) -> Option<OsString> {
+    match name {
+        "HOME" => Some("/home/user".into()),
+        "SUB" => Some("A/B".into()),
+        "X" => Some("x".into()),
+        "EMPTY" => Some("".into()),
+        _ => None,
+    }
+}
+
+#[test_case("$HOME/foo", "/home/user/foo")]
+#[test_case("${HOME}/foo", "/home/user/foo")]
+#[test_case("foo/$SUB/bar", "foo/A/B/bar"; "value with separators")]
+#[test_case("a/$HOME/x", "a/home/user/x"; "absolute value after first component")]
+#[test_case("/$HOME", "/home/user"; "absolute value after root")]
+#[test_case("a/pre$HOME", "a/pre/home/user"; "absolute value embedded")]
+#[test_case("a$X/b${X}c", "ax/bxc"; "embedded")]
+#[test_case("$X$X", "xx"; "adjacent")]
+#[test_case("pre$EMPTY", "pre")]
+#[test_case("cost$", "cost$"; "trailing dollar")]
+#[test_case("$1/$-", "$1/$-"; "not a name")]
+#[test_case("${X", "${X"; "unterminated brace")]
+#[test_case("${}", "${}"; "empty brace")]
+#[test_case("%X%", "%X%"; "windows syntax")]
+#[test_case("plain/path", "plain/path")]
+#[cfg(not(target_family = "windows"))]
+fn test_expand_env_with(path: &str, expected: &str) {
+    let actual = expand_env_with(Path::new(path), lookup).unwrap();
+    assert_eq!(actual, Path::new(expected));
+}
+
+#[test_case(r"%HOME%\foo", r"\home\user\foo")]
+#[test_case(r"foo\%SUB%\bar", r"foo\A\B\bar"; "value with separators")]
+#[test_case(r"a\%HOME%\x", r"a\home\user\x"; "absolute value after first component")]
+#[test_case(r"a%X%b%X%c", r"axbxc"; "embedded")]
+#[test_case(r"50%", r"50%"; "trailing percent")]
+#[test_case(r"%1%", r"%1%"; "not a name")]
+#[test_case(r"%%", r"%%"; "empty name")]
+#[test_case(r"$X", r"$X"; "unix syntax")]
+#[cfg(target_family = "windows")]
+fn test_expand_env_with(path: &str, expected: &str) {
+    let actual = expand_env_with(Path::new(path), lookup).unwrap();
+    assert_eq!(actual, Path::new(expected));
+}
+
+#[test_case("$UNDEFINED/foo")]
+#[test_case("foo/${UNDEFINED}")]
+#[cfg(not(target_family = "windows"))]
+fn test_expand_env_with_undefined(path: &str) {
+    let e = expand_env_with(Path::new(path), lookup).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    assert!(e.to_string().contains("UNDEFINED"), "{}", e);
+}
+
+#[test_case(r"%UNDEFINED%\foo")]
+#[cfg(target_family = "windows")]
+fn test_expand_env_with_undefined(path: &str) {
+    let e = expand_env_with(Path::new(path), lookup).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    assert!(e.to_string().contains("UNDEFINED"), "{}", e);
+}
+
+#[test]
+fn test_resolver_expand_env() {
+    let farm = LinkFarm::new();
+    farm.dir("A").dir("A/B").file("A/B/b1");
+    let path = Path::new(if cfg!(target_family = "windows") {
+        r"%REAL_PARENT_TEST_DIR%\b1"
+    } else {
+        "$REAL_PARENT_TEST_DIR/b1"
+    });
+
+    std::env::set_var("REAL_PARENT_TEST_DIR", farm.absolute("A/B"));
+
+    // without expansion the reference is a literal, nonexistent, filename
+    let mut resolver = Resolver::new().with_base(farm.absolute("."));
+    assert!(resolver.real_parent(path).is_err());
+
+    let mut resolver = Resolver::new().expand_env(true);
+    let actual = resolver.real_parent(path).unwrap();
+    assert_eq!(actual, farm.absolute("A/B"));
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_resolver_expand_link_targets() {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/~")
+        .dir("A/~/something")
+        .dir("H")
+        .dir("H/something")
+        .dir("V")
+        .file("A/~/something/x1")
+        .file("H/something/x1")
+        .file("V/x1")
+        .symlink_rel("A/_h1", "~/something/x1")
+        .symlink_rel("A/_v1", "$REAL_PARENT_TEST_LINK_DIR/x1")
+        .symlink_rel("A/_u1", "$REAL_PARENT_TEST_UNDEFINED/x1");
+
+    std::env::set_var("HOME", farm.absolute("H"));
+    std::env::set_var("REAL_PARENT_TEST_LINK_DIR", farm.absolute("V"));
+
+    // by default targets are literal, as for the OS
+    let mut resolver = Resolver::new();
+    let actual = resolver.real_parent(&farm.absolute("A/_h1")).unwrap();
+    assert_eq!(actual, farm.absolute("A/~/something"));
+
+    let mut resolver = Resolver::new().expand_link_targets(true);
+    let actual = resolver.real_parent(&farm.absolute("A/_h1")).unwrap();
+    assert_eq!(actual, farm.absolute("H/something"));
+    let actual = resolver.real_parent(&farm.absolute("A/_v1")).unwrap();
+    assert_eq!(actual, farm.absolute("V"));
+    let e = resolver.real_parent(&farm.absolute("A/_u1")).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+
+    // expanding the input path is independent
+    let mut resolver = Resolver::new().expand_env(true);
+    let actual = resolver.real_parent(&farm.absolute("A/_h1")).unwrap();
+    assert_eq!(actual, farm.absolute("A/~/something"));
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;
@@ -0,0 +1,195 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use real_parent::{Entry, MemoryFileSystem, Resolver};
+use test_case::test_case;
+
+// A memory filesystem mirroring the link farms used elsewhere, so no real filesystem access is required.
+fn memory_file_system() -> MemoryFileSystem {
+    use Entry::*;
+
+    [
+        ("x1", File),
+        ("A", Dir),
+        ("A/B", Dir),
+        ("A/B/C", Dir),
+        ("A/a1", File),
+        ("A/B/b1", File),
+        ("_x1", Symlink("x1".into())),
+        ("_B", Symlink("A/B".into())),
+        ("A/_dot", Symlink("..".into())),
+        ("A/B/_A", Symlink("..".into())),
+        ("A/B/_B", Symlink(".".into())),
+        ("A/B/_b1", Symlink("b1".into())),
+        ("A/B/_a1", Symlink("../a1".into())),
+        ("A/B/C/_a1", Symlink("../../a1".into())),
+        ("A/B/C/_b1", Symlink("./.././b1".into())),
+        ("A/_c1", Symlink("_c2".into())),
+        ("A/_c2", Symlink("_c1".into())),
+        ("/R", Dir),
+        ("/R/r1", File),
+        ("A/=r1", Symlink("/R/r1".into())),
+        ("A/_empty", Symlink("".into())),
+    ]
+    .into_iter()
+    .map(|(path, entry)| (path.into(), entry))
+    .collect::<BTreeMap<_, _>>()
+    .into()
+}
+
+#[test_case("x1", ".")]
+#[test_case("A/a1", "A")]
+#[test_case("A/B/C", "A/B")]
+#[test_case("A/B/C/.", "A/B"; "trailing dot is ignored")]
+#[test_case("", ".."; "empty path")]
+#[test_case(".", ".."; "bare dot")]
+#[test_case("..", "../.."; "bare dotdot")]
+#[test_case("A/B/_b1", "A/B")]
+#[test_case("A/B/_a1", "A")]
+#[test_case("A/B/C/_a1", "A")]
+#[test_case("A/_dot", "..")]
+#[test_case("A/B/_A", ".")]
+#[test_case("A/B/_B", "A")]
+#[test_case("A/B/C/_b1", "A/B")]
+#[test_case("_B/.", "A")]
+#[test_case("_B/b1", "_B")]
+#[test_case("_x1", ".")]
+#[test_case("A/=r1", "/R")]
+#[test_case("/R/r1", "/R")]
+#[test_case("/R", "/")]
+#[test_case("/", "/")]
+#[cfg(not(target_family = "windows"))]
+fn test_real_parent_memory_file_system(path: &str, expected: &str) {
+    let mut resolver = Resolver::new().with_file_system(memory_file_system());
+
+    let actual = resolver.real_parent(Path::new(path)).unwrap();
+    assert_eq!(actual, Path::new(expected));
+}
+
+#[test_case("A/B/C/..", "A/B")]
+#[test_case("A/../A/B/C", "A/B/C")]
+#[test_case("A/B/_a1/..", "A")]
+#[test_case("A/B/_b1/..", "A/B")]
+#[test_case("A/B/C/_a1/../B", "A/B")]
+#[test_case("A/=r1/..", "/R")]
+#[test_case("/R/..", "/")]
+#[cfg(not(target_family = "windows"))]
+fn test_real_clean_memory_file_system(path: &str, expected: &str) {
+    let mut resolver = Resolver::new().with_file_system(memory_file_system());
+
+    let actual = resolver.real_clean(Path::new(path)).unwrap();
+    assert_eq!(actual, Path::new(expected));
+}
+
+// the top of the filesystem is its own parent, as is the root directory, regardless of the real filesystem
+#[test_case("", true)]
+#[test_case("A/_dot", true; "symlink to top")]
+#[test_case("A/B/_A/..", true; "symlink then dotdot")]
+#[test_case("A", false)]
+#[test_case("_B", false; "symlink to dir")]
+#[test_case("/", true)]
+#[test_case("/R/..", true)]
+#[test_case("/R", false)]
+#[cfg(not(target_family = "windows"))]
+fn test_is_real_root_memory_file_system(path: &str, expected: bool) {
+    let resolver = Resolver::new().with_file_system(memory_file_system());
+
+    let actual = resolver.is_real_root(Path::new(path)).unwrap();
+    assert_eq!(actual, expected);
+}
+
+#[test_case("A/B/_a1", &["A/B/_a1", "A", "."])]
+#[test_case("A/=r1", &["A/=r1", "/R", "/"])]
+#[cfg(not(target_family = "windows"))]
+fn test_real_ancestors_memory_file_system(path: &str, expected: &[&str]) {
+    let mut resolver = Resolver::new().with_file_system(memory_file_system());
+
+    let actual = resolver
+        .real_ancestors(Path::new(path))
+        .collect::<io::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(
+        actual,
+        expected.iter().map(PathBuf::from).collect::<Vec<_>>()
+    );
+}
+
+#[test_case("X")]
+#[test_case("A/y1")]
+#[test_case("A/_c1"; "cycle")]
+#[test_case("x1/y1"; "not a directory")]
+fn test_real_parent_memory_file_system_error(path: &str) {
+    let mut resolver = Resolver::new().with_file_system(memory_file_system());
+
+    assert!(resolver.real_parent(Path::new(path)).is_err());
+}
+
+#[test_case("A/_empty")]
+#[test_case("A/_empty/.")]
+fn test_real_parent_memory_file_system_empty_symlink_target(path: &str) {
+    let mut resolver = Resolver::new().with_file_system(memory_file_system());
+
+    let e = resolver.real_parent(Path::new(path)).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput, "{}", e);
+}
+
+#[test]
+fn test_snapshot() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("C")
+        .file("A/a1")
+        .file("C/c1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("_B", "A/B");
+
+    // canonical, since the tempdir may be below a symlink
+    let root = farm.absolute(".").canonicalize().unwrap();
+    let mut resolver = Resolver::snapshot(&root).unwrap();
+
+    // change the filesystem after the snapshot
+    fs::remove_file(farm.absolute("A/B/_a1")).unwrap();
+    symlink("../../C/c1", farm.absolute("A/B/_a1"));
+    fs::remove_file(farm.absolute("_B")).unwrap();
+
+    // which is not reflected in the results
+    assert_eq!(
+        resolver.real_parent(Path::new("A/B/_a1")).unwrap(),
+        root.join("A")
+    );
+    assert_eq!(
+        resolver.real_clean(Path::new("_B/..")).unwrap(),
+        root.join("A")
+    );
+    assert_eq!(
+        resolver.real_parent(&root.join("_B/.")).unwrap(),
+        root.join("A")
+    );
+
+    // whereas the real filesystem has changed
+    let mut resolver = Resolver::new().with_base(&root);
+    assert_eq!(
+        resolver.real_parent(Path::new("A/B/_a1")).unwrap(),
+        root.join("C")
+    );
+    assert!(resolver.real_clean(Path::new("_B/..")).is_err());
+}
+
+#[cfg(target_family = "unix")]
+fn symlink(original: &str, link: PathBuf) {
+    std::os::unix::fs::symlink(original, link).unwrap();
+}
+
+#[cfg(target_family = "windows")]
+fn symlink(original: &str, link: PathBuf) {
+    std::os::windows::fs::symlink_file(original, link).unwrap();
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;
@@ -0,0 +1,147 @@
+// Property test that resolution never panics, whatever the path and whatever the filesystem, only ever returning `Ok` or `Err`.
+//
+// Paths are built from fragments chosen to provoke edge cases, such as separators, dots, prefixes, and interior nuls,
+// or on Unix are arbitrary bytes.  Filesystems are generated in memory, or are adversarial, claiming every path to be
+// of the same kind, including the root directory, which no real filesystem would.
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+};
+
+use proptest::prelude::*;
+use real_parent::{Entry, EntryKind, FileSystem, MemoryFileSystem, PathExt, Resolver, StepResult};
+
+const FRAGMENTS: &[&str] = &[
+    "/",
+    "\\",
+    ".",
+    "..",
+    "a",
+    "B",
+    "C:",
+    "\\\\?\\",
+    "\\\\.\\",
+    "\\\\server\\share",
+    "~",
+    "$X",
+    "%X%",
+    "\0",
+    " ",
+];
+
+fn fragment_path() -> impl Strategy<Value = PathBuf> {
+    prop::collection::vec(prop::sample::select(FRAGMENTS), 0..8)
+        .prop_map(|fragments| PathBuf::from(fragments.concat()))
+}
+
+#[cfg(target_family = "unix")]
+fn arbitrary_path() -> impl Strategy<Value = PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+
+    prop_oneof![
+        fragment_path(),
+        prop::collection::vec(any::<u8>(), 0..16)
+            .prop_map(|bytes| OsString::from_vec(bytes).into()),
+    ]
+}
+
+#[cfg(not(target_family = "unix"))]
+fn arbitrary_path() -> impl Strategy<Value = PathBuf> {
+    prop_oneof![
+        fragment_path(),
+        any::<String>().prop_map(|s| OsString::from(s).into()),
+    ]
+}
+
+fn entry() -> impl Strategy<Value = Entry> {
+    prop_oneof![
+        Just(Entry::File),
+        Just(Entry::Dir),
+        fragment_path().prop_map(Entry::Symlink),
+    ]
+}
+
+fn entry_kind() -> impl Strategy<Value = EntryKind> {
+    prop_oneof![
+        Just(EntryKind::File),
+        Just(EntryKind::Dir),
+        Just(EntryKind::Symlink),
+    ]
+}
+
+// a filesystem in which every path is of the same kind, and every symlink has the same target
+#[derive(Debug)]
+struct AdversarialFileSystem {
+    kind: EntryKind,
+    target: PathBuf,
+}
+
+impl FileSystem for AdversarialFileSystem {
+    fn entry_kind(&self, _path: &Path) -> io::Result<EntryKind> {
+        Ok(self.kind)
+    }
+
+    fn read_link(&self, _path: &Path) -> io::Result<PathBuf> {
+        Ok(self.target.clone())
+    }
+
+    fn symlink_kind(&self, _path: &Path) -> io::Result<Option<EntryKind>> {
+        Ok(Some(EntryKind::File))
+    }
+}
+
+// exercise the resolver every way we can, where any result is fine, so long as there is no panic
+fn exercise(mut resolver: Resolver, path: &Path) {
+    let _ = resolver.real_parent(path);
+    let _ = resolver.real_clean(path);
+    let _ = resolver.real_parent_opt(path);
+    let _ = resolver.resolve_detailed(path);
+    let _ = resolver.resolve_trace(path);
+    let _ = resolver.real_ancestors(path).take(4).count();
+
+    if let Ok(mut state) = resolver.real_parent_start(path) {
+        for _ in 0..100 {
+            match resolver.real_parent_step(state) {
+                Ok(StepResult::More(next)) => state = next,
+                _ => break,
+            }
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_no_panic_real_filesystem(path in arbitrary_path()) {
+        let _ = path.real_parent();
+        let _ = path.real_clean();
+        let _ = path.real_link_target();
+        let _ = path.real_symlink_free_prefix();
+        let _ = path.try_real_parent();
+        let _ = path.is_real_root();
+        exercise(Resolver::new(), &path);
+    }
+
+    #[test]
+    fn test_no_panic_memory_filesystem(
+        entries in prop::collection::btree_map(fragment_path(), entry(), 0..8),
+        paths in prop::collection::vec(arbitrary_path(), 1..8),
+    ) {
+        let fs = MemoryFileSystem::new(entries.into_iter().collect::<BTreeMap<_, _>>());
+        for path in paths {
+            exercise(Resolver::new().with_file_system(fs.clone()), &path);
+        }
+    }
+
+    #[test]
+    fn test_no_panic_adversarial_filesystem(
+        kind in entry_kind(),
+        target in arbitrary_path(),
+        path in arbitrary_path(),
+    ) {
+        let fs = AdversarialFileSystem { kind, target };
+        exercise(Resolver::new().with_file_system(fs).check_link_types(true), &path);
+    }
+}
@@ -0,0 +1,52 @@
+#![cfg(feature = "glob")]
+
+use std::io;
+
+use real_parent::real_parents_of_glob;
+
+#[test]
+fn test_real_parents_of_glob() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .file("A/B/b1")
+        .file("A/B/b2")
+        .symlink_rel("A/_b1", "B/b1");
+
+    let pattern = farm.absolute("A/*1");
+    let actual = real_parents_of_glob(pattern.to_str().unwrap()).unwrap();
+
+    // the symlink's real parent differs from its lexical parent
+    assert_eq!(
+        actual,
+        vec![
+            (farm.absolute("A/_b1"), farm.absolute("A/B")),
+            (farm.absolute("A/a1"), farm.absolute("A")),
+        ]
+    );
+}
+
+#[test]
+fn test_real_parents_of_glob_no_matches() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A");
+
+    let pattern = farm.absolute("A/*");
+    let actual = real_parents_of_glob(pattern.to_str().unwrap()).unwrap();
+
+    assert!(actual.is_empty());
+}
+
+#[test]
+fn test_real_parents_of_glob_invalid_pattern() {
+    let actual = real_parents_of_glob("A/[");
+
+    assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;
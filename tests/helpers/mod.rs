@@ -107,6 +107,17 @@ impl LinkFarm {
         self.tempdir.path().join(path)
     }
 
+    // return absolute verbatim path within link farm, with any `.` or `..` retained
+    #[cfg(target_family = "windows")]
+    pub fn verbatim<P>(&self, path: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        // separators in verbatim paths must all be backslash
+        let path = self.absolute(path).to_string_lossy().replace('/', r"\");
+        PathBuf::from(format!(r"\\?\{}", path))
+    }
+
     fn contains<P>(&self, path: P) -> bool
     where
         P: AsRef<Path>,
@@ -145,7 +156,9 @@ impl LinkFarm {
     // note the reversed order of parameters
     pub fn symlink_rel<P: AsRef<Path>, Q: AsRef<Path>>(&self, link: P, original: Q) -> &Self {
         let link = self.tempdir.path().join(link);
-        if link.is_dir() {
+        // the link type must match the target type on Windows, so resolve the target relative to the link directory
+        let target = link.parent().unwrap().join(original.as_ref());
+        if target.is_dir() {
             symlink_dir(original, link).unwrap()
         } else {
             symlink_file(original, link).unwrap()
@@ -165,7 +178,7 @@ impl LinkFarm {
     ) -> &mut Self {
         let original = self.tempdir.path().join(original);
         let link = self.tempdir.path().join(link);
-        if link.is_dir() {
+        if original.is_dir() {
             symlink_dir(original, link).unwrap()
         } else {
             symlink_file(original, link).unwrap()
@@ -184,7 +197,7 @@ impl LinkFarm {
         original: Q,
     ) -> &mut Self {
         let link = self.tempdir.path().join(link);
-        if link.is_dir() {
+        if original.as_ref().is_dir() {
             symlink_dir(original, link).unwrap()
         } else {
             symlink_file(original, link).unwrap()
@@ -195,6 +208,27 @@ impl LinkFarm {
         self
     }
 
+    // create a junction to a directory in the link farm, which necessarily has an absolute target
+    // note the reversed order of parameters
+    #[cfg(target_family = "windows")]
+    pub fn junction<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, link: P, original: Q) -> &mut Self {
+        let original = self.tempdir.path().join(original);
+        let link = self.tempdir.path().join(link);
+        let status = std::process::Command::new("cmd")
+            .arg("/C")
+            .arg("mklink")
+            .arg("/J")
+            .arg(&link)
+            .arg(&original)
+            .status()
+            .unwrap();
+        assert!(status.success(), "mklink /J failed for {:?}", link);
+
+        self.contains_absolute_symlinks = true;
+
+        self
+    }
+
     pub fn strip_prefix<'a>(&self, path: &'a Path) -> &'a Path {
         path.strip_prefix(self.tempdir.path()).unwrap_or(path)
     }
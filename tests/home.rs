@@ -0,0 +1,54 @@
+use std::{env, path::Path};
+
+use real_parent::PathExt;
+
+// a single test, since the environment is shared by all threads
+#[test]
+fn test_is_within_home() {
+    let farm = LinkFarm::new();
+    farm.dir("H")
+        .dir("O")
+        .file("H/h1")
+        .file("O/o1")
+        .symlink_rel("_H", "H")
+        .symlink_rel("H/_O", "../O")
+        .symlink_rel("H/_o1", "../O/o1")
+        .symlink_rel("O/_h1", "../H/h1")
+        .symlink_rel("H/_x1", "x1");
+
+    let home = if cfg!(target_family = "windows") {
+        "USERPROFILE"
+    } else {
+        "HOME"
+    };
+    // the home directory may itself be a symlink
+    env::set_var(home, farm.absolute("_H"));
+
+    for (path, expected) in [
+        ("H", true),
+        ("H/h1", true),
+        ("_H/h1", true),
+        ("O/_h1", true),
+        ("H/X/x1", true),
+        ("H/_O", false),
+        ("H/_o1", false),
+        ("H/_O/x1", false),
+        ("H/_x1", false),
+        ("H/..", false),
+        ("O/o1", false),
+    ] {
+        let actual = farm.absolute(path).is_within_home().unwrap();
+        assert_eq!(actual, expected, "{}", path);
+    }
+
+    let actual = with_cwd(farm.absolute("H")).run(Path::is_within_home, Path::new("h1"));
+    assert!(actual.unwrap());
+
+    env::remove_var(home);
+    let e = farm.absolute("H").is_within_home().unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;
@@ -0,0 +1,85 @@
+#![cfg(feature = "directories")]
+
+use real_parent::{real_clean_in_config_dir, real_clean_in_data_dir};
+
+// on Windows, the directories are known folders, which can't be redirected by the environment
+#[cfg(target_family = "windows")]
+#[test]
+fn test_real_clean_in_known_dirs() {
+    use real_parent::PathExt;
+
+    let dirs = directories::BaseDirs::new().unwrap();
+
+    assert_eq!(
+        real_clean_in_config_dir("x/./y").unwrap(),
+        dirs.config_dir().join("x/y").real_clean().unwrap()
+    );
+    assert_eq!(
+        real_clean_in_data_dir("..").unwrap(),
+        dirs.data_dir().join("..").real_clean().unwrap()
+    );
+}
+
+// a single test, since the environment is shared by all threads
+#[cfg(not(target_family = "windows"))]
+#[test]
+fn test_real_clean_in_known_dirs() {
+    use std::env;
+
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/config")
+        .dir("A/data")
+        .file("A/a1")
+        .symlink_rel("_config", "A/config")
+        .symlink_rel("_data", "A/data");
+
+    let (config_dir, data_dir) = if cfg!(target_os = "macos") {
+        farm.dir("H")
+            .dir("H/Library")
+            .symlink_rel("H/Library/Application Support", "../../A/config");
+        env::set_var("HOME", farm.absolute("H"));
+        (
+            "H/Library/Application Support",
+            "H/Library/Application Support",
+        )
+    } else {
+        env::set_var("XDG_CONFIG_HOME", farm.absolute("_config"));
+        env::set_var("XDG_DATA_HOME", farm.absolute("_data"));
+        ("_config", "_data")
+    };
+
+    assert_eq!(
+        real_clean_in_config_dir("x/./y").unwrap(),
+        farm.absolute(config_dir).join("x/y")
+    );
+    assert_eq!(
+        real_clean_in_data_dir("x").unwrap(),
+        farm.absolute(data_dir).join("x")
+    );
+
+    // dotdot is relative to the real directory, not the symlink
+    assert_eq!(
+        real_clean_in_config_dir("../a1").unwrap(),
+        farm.absolute("A/a1")
+    );
+    assert_eq!(real_clean_in_data_dir("..").unwrap(), farm.absolute("A"));
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        // relative XDG directories are ignored in favour of $HOME
+        farm.dir("H").dir("H/.config");
+        env::set_var("HOME", farm.absolute("H"));
+        env::set_var("XDG_CONFIG_HOME", "relative");
+        assert_eq!(
+            real_clean_in_config_dir("x").unwrap(),
+            farm.absolute("H/.config/x")
+        );
+    }
+}
+
+#[cfg(not(target_family = "windows"))]
+#[allow(dead_code)]
+mod helpers;
+#[cfg(not(target_family = "windows"))]
+use helpers::*;
@@ -0,0 +1,58 @@
+use std::path::Path;
+
+use real_parent::{lexical_normalize, lexical_parent};
+use test_case::test_case;
+
+// Lexical functions don't touch the filesystem, so no link farm is required for these tests.
+
+#[test_case("x1", ".")]
+#[test_case("A/a1", "A")]
+#[test_case("A/B/C", "A/B")]
+#[test_case("A/B/C/..", "A/B/C/../..")]
+#[test_case("A/B/C/.", "A/B"; "trailing dot is ignored")]
+#[test_case("A/./B/C", "A/B"; "intermediate dot is ignored")]
+#[test_case("A/../A/B/C", "A/../A/B"; "intermediate dotdot remains")]
+#[test_case("", ".."; "empty path")]
+#[test_case(".", ".."; "bare dot")]
+#[test_case("..", "../.."; "bare dotdot")]
+#[test_case("/", "/"; "root")]
+#[test_case("/A", "/"; "child of root")]
+#[test_case("/A/B", "/A"; "absolute")]
+#[cfg(not(target_family = "windows"))]
+fn test_lexical_parent(path: &str, expected: &str) {
+    assert_eq!(lexical_parent(Path::new(path)), Path::new(expected));
+}
+
+#[test_case("x1", "x1")]
+#[test_case("A/B/C/..", "A/B")]
+#[test_case("./A/B/b1", "A/B/b1"; "initial dot removed")]
+#[test_case("A/B/C/.", "A/B/C"; "trailing dot is ignored")]
+#[test_case("A/./B/C", "A/B/C"; "intermediate dot removed")]
+#[test_case("A/../A/B/C", "A/B/C"; "intermediate dotdot folded away")]
+#[test_case("A/..", "."; "folded to nothing")]
+#[test_case("", "."; "empty path")]
+#[test_case(".", "."; "bare dot")]
+#[test_case("..", ".."; "bare dotdot")]
+#[test_case("../A/../..", "../.."; "leading dotdot remains")]
+#[test_case("/", "/"; "root")]
+#[test_case("/..", "/"; "parent of root")]
+#[test_case("/A/../../B", "/B"; "absolute")]
+#[cfg(not(target_family = "windows"))]
+fn test_lexical_normalize(path: &str, expected: &str) {
+    assert_eq!(lexical_normalize(Path::new(path)), Path::new(expected));
+}
+
+#[test_case(r"C:\", r"C:\"; "root")]
+#[test_case(r"C:\A\B", r"C:\A"; "absolute")]
+#[test_case(r"\\server\share\A", r"\\server\share\"; "unc")]
+#[cfg(target_family = "windows")]
+fn test_lexical_parent_windows(path: &str, expected: &str) {
+    assert_eq!(lexical_parent(Path::new(path)), Path::new(expected));
+}
+
+#[test_case(r"C:\..", r"C:\"; "parent of root")]
+#[test_case(r"C:\A\..\..\B", r"C:\B"; "absolute")]
+#[cfg(target_family = "windows")]
+fn test_lexical_normalize_windows(path: &str, expected: &str) {
+    assert_eq!(lexical_normalize(Path::new(path)), Path::new(expected));
+}
@@ -0,0 +1,94 @@
+// Paths longer than the Windows `MAX_PATH` limit, which must be resolvable without the verbatim prefix.
+#![cfg(target_family = "windows")]
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use real_parent::PathExt;
+use test_case::test_case;
+
+const MAX_PATH: usize = 260;
+
+// a directory name long enough that few levels of nesting exceed `MAX_PATH`
+const LONG_NAME: &str = "a_directory_name_which_is_long_enough_to_exceed_max_path_quickly";
+
+// a farm with a directory nested deeply enough that its absolute path exceeds `MAX_PATH`, containing a single file,
+// returning the relative path of the deepest directory
+fn long_farm(farm: &LinkFarm) -> PathBuf {
+    let deepest = (0..(MAX_PATH / LONG_NAME.len() + 1))
+        .map(|_| LONG_NAME)
+        .collect::<PathBuf>();
+    fs::create_dir_all(farm.absolute(&deepest)).unwrap();
+    farm.file(deepest.join("x1"));
+
+    assert!(farm.absolute(&deepest).as_os_str().len() > MAX_PATH);
+    deepest
+}
+
+// the path without its final component, or the same if it ends in `..`
+fn expected_parent(path: &Path) -> PathBuf {
+    if path.ends_with("..") {
+        path.parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .to_path_buf()
+    } else {
+        path.parent().unwrap().to_path_buf()
+    }
+}
+
+#[test_case("x1")]
+#[test_case("."; "trailing dot")]
+#[test_case(".."; "trailing dotdot")]
+fn test_real_parent_long_path_windows(leaf: &str) {
+    let farm = LinkFarm::new();
+    let deepest = farm.absolute(long_farm(&farm));
+    let path = deepest.join(leaf);
+
+    let expected = match leaf {
+        "." => expected_parent(&deepest),
+        _ => expected_parent(&path),
+    };
+
+    let actual = path.real_parent().unwrap();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_real_clean_long_path_windows() {
+    let farm = LinkFarm::new();
+    let deepest = farm.absolute(long_farm(&farm));
+
+    let actual = deepest
+        .join("..")
+        .join(LONG_NAME)
+        .join("x1")
+        .real_clean()
+        .unwrap();
+    assert_eq!(actual, deepest.join("x1"));
+}
+
+#[test]
+fn test_is_real_root_long_path_windows() {
+    let farm = LinkFarm::new();
+    let deepest = farm.absolute(long_farm(&farm));
+
+    assert!(!deepest.is_real_root().unwrap());
+
+    // every ancestor is checked for being the root, and iteration ends at the root
+    let ancestors = deepest
+        .real_ancestors()
+        .collect::<io::Result<Vec<_>>>()
+        .unwrap();
+    assert!(ancestors.last().unwrap().is_real_root().unwrap());
+    assert!(ancestors.contains(&farm.absolute(".")));
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;
@@ -0,0 +1,122 @@
+#![cfg(target_os = "linux")]
+
+use std::{
+    fs::{self, File},
+    io,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use real_parent::{EntryKind, FileSystem, MagicLinks, PathExt, RealFileSystem, Resolver};
+
+// the magic symlink for an open file descriptor
+fn fd_path<F>(file: &F) -> PathBuf
+where
+    F: AsRawFd,
+{
+    PathBuf::from(format!("/proc/self/fd/{}", file.as_raw_fd()))
+}
+
+#[test]
+fn test_magic_link_follow() {
+    let farm = LinkFarm::new();
+    farm.dir("A").file("A/a1");
+
+    let file = File::open(farm.absolute("A/a1")).unwrap();
+    let path = fd_path(&file);
+
+    assert_eq!(
+        path.real_parent().unwrap(),
+        farm.absolute("A").canonicalize().unwrap()
+    );
+}
+
+// the real filesystem, counting the symlinks read through it
+#[derive(Clone, Default, Debug)]
+struct ReadCountingFileSystem {
+    reads: Arc<AtomicUsize>,
+}
+
+impl FileSystem for ReadCountingFileSystem {
+    fn entry_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        RealFileSystem.entry_kind(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        RealFileSystem.read_link(path)
+    }
+}
+
+#[test]
+fn test_magic_link_read_once() {
+    let farm = LinkFarm::new();
+    farm.dir("A").file("A/a1");
+
+    let file = File::open(farm.absolute("A/a1")).unwrap();
+    let fs = ReadCountingFileSystem::default();
+    let mut resolver = Resolver::new().with_file_system(fs.clone());
+
+    assert_eq!(
+        resolver.real_parent(&fd_path(&file)).unwrap(),
+        farm.absolute("A").canonicalize().unwrap()
+    );
+    // the target read in deciding to follow the magic link is the one followed
+    assert_eq!(fs.reads.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn test_magic_link_opaque() {
+    let farm = LinkFarm::new();
+    farm.dir("A").file("A/a1");
+
+    let file = File::open(farm.absolute("A/a1")).unwrap();
+    let mut resolver = Resolver::new().magic_links(MagicLinks::Opaque);
+
+    assert_eq!(
+        resolver.real_parent(&fd_path(&file)).unwrap(),
+        Path::new("/proc/self/fd")
+    );
+}
+
+#[test]
+fn test_magic_link_deleted() {
+    let farm = LinkFarm::new();
+    farm.dir("A").file("A/a1");
+
+    let file = File::open(farm.absolute("A/a1")).unwrap();
+    fs::remove_file(farm.absolute("A/a1")).unwrap();
+    // a file whose name matches the deleted target, which must not be mistaken for it
+    farm.file("A/a1 (deleted)");
+
+    let path = fd_path(&file);
+    assert!(path.read_link().unwrap().ends_with("a1 (deleted)"));
+
+    let actual = path.real_parent();
+    assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::NotFound);
+
+    // an opaque magic link doesn't care
+    let mut resolver = Resolver::new().magic_links(MagicLinks::Opaque);
+    assert_eq!(
+        resolver.real_parent(&path).unwrap(),
+        Path::new("/proc/self/fd")
+    );
+}
+
+#[test]
+fn test_magic_link_not_a_path() {
+    let (reader, _writer) = io::pipe().unwrap();
+    let path = fd_path(&reader);
+
+    // the target is something like `pipe:[1234]`, which is never followed
+    assert!(!path.read_link().unwrap().is_absolute());
+    assert_eq!(path.real_parent().unwrap(), Path::new("/proc/self/fd"));
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;
@@ -1,6 +1,13 @@
-use std::path::Path;
-
-use real_parent::PathExt;
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    path::{Path, PathBuf},
+};
+
+use real_parent::{
+    real_clean_from_exe_dir, real_clean_from_unix_str, real_clean_in_first, real_clean_path_list,
+    CleanChanges, PathExt, RealParent, RealParentError,
+};
 use test_case::test_case;
 
 // Naming for files and directories in the link farms is as follows:
@@ -16,6 +23,7 @@ use test_case::test_case;
 #[test_case("A/B/C", "A/B")]
 #[test_case("A/B/C/..", "A/B/C/../..")]
 #[test_case("A/B/C/.", "A/B"; "trailing dot is ignored")]
+#[test_case("A/B/C/", "A/B"; "trailing separator is ignored")]
 #[test_case("A/./B/C", "A/./B"; "intermediate dot remains")]
 #[test_case("A/../A/B/C", "A/../A/B"; "intermediate dotdot remains")]
 #[test_case("A/.D", "A" ; "hidden directory")]
@@ -50,6 +58,23 @@ fn test_real_parent_root_dir() {
     check_path_ok(&farm, None, &path, expected, Path::real_parent);
 }
 
+#[test_case(".."; "dotdot")]
+#[test_case("../.."; "dotdot dotdot")]
+#[test_case("../."; "dotdot dot")]
+#[test_case("./.."; "dot dotdot")]
+fn test_real_parent_root_dir_dotdot(suffix: &str) {
+    let farm = LinkFarm::new();
+
+    // the root directory is its own parent, so these are all simply the root directory
+    let path = root_dir().join(suffix);
+    let expected = root_dir();
+    check_path_ok(&farm, None, &path, &expected, Path::real_parent);
+    check_path_ok(&farm, None, &path, &expected, Path::real_clean);
+
+    let actual = path.real_parent().unwrap();
+    assert_eq!(actual.as_os_str(), expected.as_os_str());
+}
+
 #[test_case("A/B/_b1", "A/B")]
 #[test_case("A/B/_a1", "A")]
 #[test_case("A/B/C/_a1", "A")]
@@ -60,6 +85,8 @@ fn test_real_parent_root_dir() {
 #[test_case("_B/.", "A")]
 #[test_case("_B/..", "_B/../..")] // we don't attempt to fold away dotdot in base path
 #[test_case("_x1", ".")]
+#[test_case("_B/", "A"; "trailing separator")]
+#[test_case("A/B/_A/.", "."; "trailing dot")]
 fn test_real_parent_rel_symlinks(path: &str, expected: &str) {
     let farm = LinkFarm::new();
 
@@ -83,6 +110,87 @@ fn test_real_parent_rel_symlinks(path: &str, expected: &str) {
     check_path_ok(&farm, None, path, expected, Path::real_parent);
 }
 
+// Within a current directory reached via a symlink, `..` ascends physically, from the target of the symlink,
+// so the results of resolving pure `..` chains are only correct if left unfolded.
+#[test_case("_B", ".", "..", "A")]
+#[test_case("_B", "..", "../..", "."; "dotdot")]
+#[test_case("_B", "../..", "../../..", ".."; "dotdot dotdot")]
+#[test_case("_C", "..", "../..", "A"; "nested")]
+#[test_case("__C", "../..", "../../..", "."; "indirect")]
+fn test_dotdot_in_symlinked_cwd(cwd: &str, path: &str, expected: &str, expected_physical: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("X")
+        .dir("X/A")
+        .dir("X/A/B")
+        .dir("X/A/B/C")
+        .symlink_rel("X/_B", "A/B")
+        .symlink_rel("X/_C", "A/B/C")
+        .symlink_rel("X/__C", "_C");
+
+    let cwd = farm.absolute("X").join(cwd);
+    let expected_physical = farm
+        .absolute("X")
+        .join(expected_physical)
+        .canonicalize()
+        .unwrap();
+
+    let (parent, clean, parent_physical) = with_cwd(&cwd).run(
+        |path: &Path| {
+            let parent = path.real_parent().unwrap();
+            let parent_physical = parent.canonicalize().unwrap();
+            (parent, path.real_clean().unwrap(), parent_physical)
+        },
+        Path::new(path),
+    );
+
+    assert_eq!(parent, Path::new(expected));
+    assert_eq!(parent_physical, expected_physical);
+    // leading `..` can never be folded away
+    assert_eq!(clean, Path::new(path));
+}
+
+// A symlink to `.` refers to the directory containing it, so its real parent is the parent of that directory,
+// however many times it is traversed.
+#[test_case("_self", "..")]
+#[test_case("A/_self", ".")]
+#[test_case("A/B/_self", "A")]
+#[test_case("A/B/_self/.", "A"; "trailing dot")]
+#[test_case("A/B/_self/_self", "A"; "repeated")]
+#[test_case("A/B/_self/_self/_self/.", "A"; "repeated with trailing dot")]
+#[test_case("A/B/__self", "A"; "indirect")]
+#[test_case("A/B/_self/b1", "A/B/_self"; "file within")]
+#[test_case("A/B/_self/..", "A/B/_self/../.."; "dotdot")] // as ever, dotdot in the base path is not folded away
+fn test_real_parent_symlink_to_dot(path: &str, expected: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/B/b1")
+        .symlink_rel("_self", ".")
+        .symlink_rel("A/_self", ".")
+        .symlink_rel("A/B/_self", ".")
+        .symlink_rel("A/B/__self", "_self");
+
+    check_path_ok(&farm, None, path, expected, Path::real_parent);
+}
+
+#[test_case("A/B/_self/..", "A")]
+#[test_case("A/B/_self/_self/..", "A"; "repeated")]
+#[test_case("A/B/_self/../_self/..", "."; "interleaved")]
+#[test_case("A/B/__self/..", "A"; "indirect")]
+fn test_real_clean_symlink_to_dot(path: &str, expected: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .symlink_rel("A/_self", ".")
+        .symlink_rel("A/B/_self", ".")
+        .symlink_rel("A/B/__self", "_self");
+
+    check_path_ok(&farm, None, path, expected, Path::real_clean);
+}
+
 #[test_case("_B/b1", "_B")]
 #[cfg(not(target_family = "windows"))]
 fn test_real_parent_rel_symlinks_not_windows(path: &str, expected: &str) {
@@ -106,6 +214,25 @@ fn test_real_parent_rel_symlinks_not_windows(path: &str, expected: &str) {
     check_path_ok(&farm, None, path, expected, Path::real_parent);
 }
 
+#[test_case("X/_t1", "X")]
+#[test_case("Y/_t1", "Y")]
+#[test_case("X/_t1/x1", "X/_t1")]
+fn test_real_parent_typed_symlinks(path: &str, expected: &str) {
+    let farm = LinkFarm::new();
+
+    // a directory symlink and a file symlink with the same target name, which on Windows are of different types,
+    // so here the naming convention is necessarily broken for the directory t1
+    farm.dir("X")
+        .dir("X/t1")
+        .file("X/t1/x1")
+        .dir("Y")
+        .file("Y/t1")
+        .symlink_rel("X/_t1", "t1")
+        .symlink_rel("Y/_t1", "t1");
+
+    check_path_ok(&farm, None, path, expected, Path::real_parent);
+}
+
 #[test_case("A/B/__c", "A/B/C")]
 fn test_real_parent_rel_indirect_symlinks(path: &str, expected: &str) {
     let farm = LinkFarm::new();
@@ -169,6 +296,156 @@ fn test_real_parent_abs_symlinks(path: &str, expected: &str) {
     );
 }
 
+#[test_case("_X", 1; "external directory")]
+#[test_case("A/_x1", 0; "external file")]
+#[test_case("A/__X", 1; "indirect external directory")]
+#[cfg(not(target_family = "windows"))]
+fn test_real_parent_external_symlinks_not_windows(path: &str, levels_up: usize) {
+    use std::env::current_exe;
+
+    // the directory containing the test executable is absolute, real, and outside any tempdir
+    let external_dir = current_exe().unwrap().parent().unwrap().to_path_buf();
+    let external_file = current_exe().unwrap();
+    let mut farm = LinkFarm::new();
+
+    farm.dir("A");
+
+    farm.symlink_external("_X", &external_dir)
+        .symlink_external("A/_x1", &external_file)
+        .symlink_rel("A/__X", "../_X");
+
+    let expected = external_dir.ancestors().nth(levels_up).unwrap();
+    check_path_ok(&farm, None, path, expected, Path::real_parent);
+
+    // and the result is absolute
+    let actual = with_cwd(farm.absolute(".")).run(Path::real_parent, Path::new(path));
+    assert!(actual.unwrap().is_absolute());
+}
+
+#[test_case("A/_b1", "A")]
+#[test_case("A/_A/.", ".")]
+#[test_case("A/_A/..", ".."; "dotdot after escape")]
+fn test_real_parent_escaping_symlinks(path: &str, expected_external: &str) {
+    let external = LinkFarm::new();
+    external
+        .dir("A")
+        .dir("A/B")
+        .file("A/b1")
+        .symlink_rel("_B", "A/B");
+
+    // the target ascends out of the farm into the sibling external farm, and through a symlink there,
+    // so that folding `..` lexically would give the non-existent external b1 rather than A/b1
+    let external_name = external
+        .absolute("A")
+        .parent()
+        .unwrap()
+        .file_name()
+        .unwrap()
+        .to_owned();
+    let escape = Path::new("../..").join(&external_name);
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .symlink_rel("A/_b1", escape.join("_B/../b1"))
+        .symlink_rel("A/_A", escape.join("_B/.."));
+
+    let expected = external.absolute(expected_external);
+    check_real_parent_canonical(&farm, path, &expected);
+}
+
+#[test]
+#[cfg(not(target_family = "windows"))]
+fn test_real_parent_symlink_via_root() {
+    use std::env::current_exe;
+
+    // the directory containing the test executable is absolute, real, and outside any tempdir
+    let external_dir = current_exe().unwrap().parent().unwrap().to_path_buf();
+    let farm = LinkFarm::new();
+
+    // ascend beyond the root directory, whose parent is itself, then descend to the external directory
+    let ascent = (0..farm.depth_below_root() + 3)
+        .map(|_| "..")
+        .collect::<PathBuf>();
+    let descent = external_dir.strip_prefix(root_dir()).unwrap();
+    farm.dir("A").symlink_rel("A/_X", ascent.join(descent));
+
+    check_real_parent_canonical(&farm, "A/_X/.", external_dir.parent().unwrap());
+}
+
+// check the real parent of both relative and absolute paths refers to the expected directory,
+// where the result, although correct, is not in any predictable form
+fn check_real_parent_canonical(farm: &LinkFarm, path: &str, expected: &Path) {
+    let expected = expected.canonicalize().unwrap();
+
+    // relative paths
+    let actual = with_cwd(farm.absolute(".")).run(
+        |path: &Path| path.real_parent().and_then(|parent| parent.canonicalize()),
+        Path::new(path),
+    );
+    assert_eq!(actual.unwrap(), expected);
+
+    // absolute paths
+    let actual = farm.absolute(path).real_parent().unwrap();
+    assert_eq!(actual.canonicalize().unwrap(), expected);
+}
+
+#[test_case("A/B/b1", "A/B")]
+#[test_case("A/B/C/..", "A")]
+#[test_case("A/B/C/.", "A/B"; "trailing dot")]
+#[test_case("A/./B/b1", "A/B"; "intermediate dot")]
+#[test_case("A/../A/B/b1", "A/B"; "intermediate dotdot")]
+#[test_case("A/B/_a1", "A"; "symlink")]
+#[cfg(target_family = "windows")]
+fn test_real_parent_verbatim_windows(path: &str, expected: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("A/B/C")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("A/B/_a1", "../a1");
+
+    // Windows doesn't interpret `.` or `..` in verbatim paths, so these must be folded away in the result
+    let actual = farm.verbatim(path).real_parent().unwrap();
+    assert_eq!(actual, farm.verbatim(expected));
+}
+
+#[test_case("_B", "A"; "junction")]
+#[test_case("_B/b1", "_B"; "file via junction")]
+#[test_case("_B/C/..", "A/B"; "dotdot via junction")]
+#[test_case("A/_C/..", "A/B"; "nested junction")]
+#[cfg(target_family = "windows")]
+fn test_real_parent_junction_windows(path: &str, expected: &str) {
+    let mut farm = LinkFarm::new();
+
+    farm.dir("A").dir("A/B").dir("A/B/C").file("A/B/b1");
+    farm.junction("_B", "A/B").junction("A/_C", "A/B/C");
+
+    // junctions are reparse points which the standard library reports as symlinks
+    assert!(farm.absolute("_B").symlink_metadata().unwrap().is_symlink());
+
+    let expected = farm.absolute(expected).canonicalize().unwrap();
+
+    let actual = with_cwd(farm.absolute("."))
+        .run(|path: &Path| path.real_parent(), Path::new(path))
+        .unwrap();
+    assert_eq!(farm.absolute(actual).canonicalize().unwrap(), expected);
+
+    let actual = farm.absolute(path).real_parent().unwrap();
+    assert_eq!(actual.canonicalize().unwrap(), expected);
+}
+
+#[test_case(r"\\.\NUL")]
+#[test_case(r"\\.\pipe\")]
+#[cfg(target_family = "windows")]
+fn test_real_parent_device_namespace_error_windows(path: &str) {
+    let e = Path::new(path).real_parent().unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+
+    let e = Path::new(path).join("..").real_clean().unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+}
+
 #[test_case("A/_a1")]
 #[test_case("A/B/_b1")]
 fn test_real_parent_symlink_cycle_error(path: &str) {
@@ -188,6 +465,367 @@ fn test_real_parent_symlink_cycle_error(path: &str) {
     check_path_err(&farm, path, Path::real_parent);
 }
 
+#[test_case("b1", Some("A/B/b1"); "in later base")]
+#[test_case("../a1", Some("A/a1"); "dotdot after symlinked base")]
+#[test_case("B/../a1", Some("A/a1"); "after missing base")]
+#[test_case("x1", None; "missing")]
+fn test_real_clean_in_first(rel: &str, expected: Option<&str>) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .dir("A/Q")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("_Q", "A/Q");
+
+    let bases = ["X", "A", "_Q", "A/B"].map(|base| farm.absolute(base));
+    let actual = real_clean_in_first(&bases, rel).unwrap();
+    assert_eq!(actual, expected.map(|p| farm.absolute(p)));
+}
+
+#[test]
+fn test_real_clean_path_list() {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .dir("C")
+        .symlink_rel("_A", "A")
+        .symlink_rel("C/_B", "../A/B");
+
+    let sep = if cfg!(target_family = "windows") {
+        ';'
+    } else {
+        ':'
+    };
+    let list = ["A", "A/B/..", "_A", "X/..", "C/./_B", "A/B", "C", "X/.."].join(&sep.to_string());
+
+    let actual = with_cwd(farm.absolute(".")).run(
+        |list: &OsStr| real_clean_path_list(list, sep),
+        OsStr::new(&list),
+    );
+
+    // duplicates are dropped by identity, but errors are all retained
+    let actual = actual
+        .unwrap()
+        .into_iter()
+        .map(|result| result.ok())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        actual,
+        vec![
+            Some(PathBuf::from("A")),
+            None,
+            Some(PathBuf::from("C/_B")),
+            Some(PathBuf::from("C")),
+            None,
+        ]
+    );
+}
+
+#[test]
+fn test_real_clean_path_list_separator_not_ascii() {
+    let e = real_clean_path_list(OsStr::new("A→B"), '→').unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test_case("A/a1", "A/a1")]
+#[test_case("_B/../a1", "A/a1"; "through symlink")]
+#[test_case("A/B/../C d/%1", "A/C%20d/%251"; "percent-encoded")]
+fn test_real_file_url(path: &str, expected: &str) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .dir("A/C d")
+        .file("A/a1")
+        .file("A/C d/%1")
+        .symlink_rel("_B", "A/B");
+
+    let actual = farm.absolute(path).real_file_url().unwrap();
+
+    // the link farm itself is in a temporary directory, which we assume needs no encoding
+    let farm_dir = farm
+        .absolute(".")
+        .components()
+        .collect::<PathBuf>()
+        .to_string_lossy()
+        .replace('\\', "/");
+    let farm_url = if farm_dir.starts_with('/') {
+        format!("file://{}", farm_dir)
+    } else {
+        format!("file:///{}", farm_dir)
+    };
+    assert_eq!(actual, format!("{}/{}", farm_url, expected));
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_real_file_url_not_unicode() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = Path::new(OsStr::from_bytes(b"/tmp/\xff"));
+    assert_eq!(
+        path.real_file_url().unwrap_err().kind(),
+        std::io::ErrorKind::InvalidData
+    );
+}
+
+#[test_case("a1", RealParent::Parent(".".into()))]
+#[test_case("B/b1", RealParent::Parent("B".into()))]
+#[test_case("B/_a1", RealParent::Parent(".".into()); "symlink within tree")]
+#[test_case("../x1", RealParent::Parent("..".into()); "already above tree")]
+#[test_case("", RealParent::AboveRoot("..".into()); "empty")]
+#[test_case(".", RealParent::AboveRoot("..".into()); "dot")]
+#[test_case("..", RealParent::AboveRoot("../..".into()); "dotdot")]
+#[test_case("B/..", RealParent::AboveRoot("B/../..".into()); "unfolded dotdot")]
+#[test_case("_x1", RealParent::AboveRoot("..".into()); "symlink above tree")]
+#[test_case("/", RealParent::RootItself("/".into()); "root")]
+#[test_case("/..", RealParent::RootItself("/".into()); "root dotdot")]
+fn test_real_parent_classified(path: &str, expected: RealParent) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .file("A/B/b1")
+        .file("x1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("A/_x1", "../x1");
+
+    // the tree is A, so that paths may ascend above it
+    let actual = with_cwd(farm.absolute("A")).run(Path::real_parent_classified, Path::new(path));
+    assert_eq!(actual.unwrap(), expected);
+}
+
+#[cfg(not(target_family = "windows"))]
+#[test]
+fn test_real_parent_classified_real_root() {
+    let mut farm = LinkFarm::new();
+    farm.dir("A");
+    farm.symlink_external("A/_root", root_dir());
+
+    let classified = |path: &Path| {
+        with_cwd(farm.absolute("A"))
+            .run(Path::real_parent_classified, path)
+            .unwrap()
+    };
+    let is_root_itself = |path: &Path| matches!(classified(path), RealParent::RootItself(parent) if parent.is_real_root().unwrap());
+
+    // via a symlink
+    assert_eq!(
+        classified(Path::new("_root")),
+        RealParent::RootItself(root_dir())
+    );
+    assert!(is_root_itself(Path::new("_root/..")));
+
+    // via `..` from below the root, whether absolute or relative
+    let up = (0..=farm.depth_below_root())
+        .map(|_| "..")
+        .collect::<PathBuf>();
+    let real_a = farm.absolute("A").canonicalize().unwrap();
+    assert!(is_root_itself(&real_a.join(&up)));
+    assert!(is_root_itself(&up));
+
+    // but not a child of the root directory
+    let top = real_a.ancestors().nth(farm.depth_below_root()).unwrap();
+    assert_eq!(classified(top), RealParent::Parent(root_dir()));
+}
+
+#[test_case("A/B/_a1", "A"; "resolved")]
+#[test_case("A/_c1", "A"; "fallback on cycle")]
+#[test_case("A/B/_c1/b1", "A/B/_c1"; "fallback not symlink correct")]
+fn test_real_parent_or_lexical(path: &str, expected: &str) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("A/_c1", "_c2")
+        .symlink_rel("A/_c2", "_c1")
+        .symlink_rel("A/B/_c1", "../_c1");
+
+    let actual = farm.absolute(path).real_parent_or_lexical();
+    assert_eq!(
+        actual.components().collect::<PathBuf>(),
+        farm.absolute(expected).components().collect::<PathBuf>()
+    );
+}
+
+#[test_case("A/B/_a1", Some("A"); "resolved")]
+#[test_case("A/_d1", None; "dangling symlink")]
+#[test_case("A/x1", None; "missing")]
+#[test_case("A/X/..", None; "missing component")]
+#[test_case("A/X/a1", None; "missing directory")]
+fn test_try_real_parent(path: &str, expected: Option<&str>) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("A/_d1", "B/x1");
+
+    let actual = farm.absolute(path).try_real_parent().unwrap();
+    assert_eq!(
+        actual.map(|p| p.components().collect::<PathBuf>()),
+        expected.map(|p| farm.absolute(p).components().collect::<PathBuf>())
+    );
+}
+
+#[test]
+fn test_try_real_parent_cycle() {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .symlink_rel("A/_a1", "_a2")
+        .symlink_rel("A/_a2", "_a1");
+
+    let e = farm.absolute("A/_a1").try_real_parent().unwrap_err();
+    assert!(matches!(e, RealParentError::Cycle(_)), "{:?}", e);
+    assert!(e.to_string().contains("cycle"), "{}", e);
+}
+
+#[test]
+fn test_real_parent_error_send_sync() {
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+    assert_send_sync::<std::io::Error>();
+
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .symlink_rel("A/_a1", "_a2")
+        .symlink_rel("A/_a2", "_a1");
+
+    // failures may be sent back from a background thread, with the wrapped error intact
+    let path = farm.absolute("A/_a1");
+    let e = std::thread::spawn(move || path.real_parent())
+        .join()
+        .unwrap()
+        .unwrap_err();
+    let inner = e.into_inner().unwrap();
+    assert!(inner.to_string().contains("cycle"), "{}", inner);
+}
+
+#[test_case("A/a\0b", "NUL")]
+#[test_case("A/a\0b/..", "NUL")]
+#[cfg_attr(target_family = "windows", test_case("A/a|b", "`|`"))]
+#[cfg_attr(target_family = "windows", test_case("A/a?b/..", "`?`"))]
+#[cfg_attr(target_family = "windows", test_case("A/a*b", "`*`"))]
+fn test_illegal_characters(path: &str, reason: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A");
+
+    for actual in [
+        farm.absolute(path).real_parent(),
+        farm.absolute(path).real_clean(),
+    ] {
+        let e = actual.unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput, "{}", e);
+        assert!(e.to_string().contains(reason), "{}", e);
+    }
+}
+
+#[test]
+#[cfg(target_family = "windows")]
+fn test_alternate_data_stream_not_illegal() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").file("A/a1");
+
+    assert_eq!(
+        farm.absolute("A/a1:stream").real_parent().unwrap(),
+        farm.absolute("A")
+    );
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_real_parent_permission_denied_error_path() {
+    use std::{
+        fs::{read_dir, set_permissions, Permissions},
+        os::unix::fs::PermissionsExt,
+    };
+
+    let farm = LinkFarm::new();
+
+    farm.dir("A").dir("A/B").file("A/B/b1");
+
+    set_permissions(farm.absolute("A"), Permissions::from_mode(0o000)).unwrap();
+
+    // privileged users are not subject to permissions, so there's nothing to test
+    let privileged = read_dir(farm.absolute("A")).is_ok();
+
+    let rel_actual = with_cwd(farm.absolute(".")).run(Path::real_parent, Path::new("A/B/b1"));
+    let abs_actual = farm.absolute("A/B/b1").real_parent();
+
+    // restore permissions so the farm may be removed
+    set_permissions(farm.absolute("A"), Permissions::from_mode(0o755)).unwrap();
+
+    if !privileged {
+        // the error refers to the inaccessible directory rather than the original path
+        let e = rel_actual.unwrap_err();
+        assert!(
+            e.to_string().ends_with(" on A (while resolving A/B/b1)"),
+            "{}",
+            e
+        );
+
+        let e = abs_actual.unwrap_err();
+        assert!(
+            e.to_string().ends_with(&format!(
+                " on {} (while resolving {})",
+                farm.absolute("A").to_string_lossy(),
+                farm.absolute("A/B/b1").to_string_lossy()
+            )),
+            "{}",
+            e
+        );
+    }
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_real_parent_empty_symlink_target() {
+    use std::{io, os::unix::fs::symlink};
+
+    let farm = LinkFarm::new();
+
+    farm.dir("A");
+
+    // some platforms, including Linux, refuse to create a symlink with an empty target, so there's nothing to test
+    if symlink("", farm.absolute("A/_empty")).is_err() {
+        return;
+    }
+
+    for path in ["A/_empty", "A/_empty/."] {
+        let e = with_cwd(farm.absolute("."))
+            .run(Path::real_parent, Path::new(path))
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput, "{}", e);
+    }
+
+    let e = farm.absolute("A/_empty").real_link_target().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput, "{}", e);
+}
+
+#[test]
+fn test_real_parent_error_context() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").symlink_rel("A/_x1", "../X/x1");
+
+    // the error arises on the symlink target, and refers also to the original path
+    let e = with_cwd(farm.absolute("."))
+        .run(Path::real_parent, Path::new("A/_x1"))
+        .unwrap_err();
+    let message = e.to_string();
+    assert!(message.contains(" on X/x1 "), "{}", message);
+    assert!(message.ends_with("(while resolving A/_x1)"), "{}", message);
+
+    // but there's no need to repeat the original path where that was in error
+    let e = with_cwd(farm.absolute("."))
+        .run(Path::real_parent, Path::new("A/y1"))
+        .unwrap_err();
+    let message = e.to_string();
+    assert!(message.ends_with(" on A/y1"), "{}", message);
+}
+
 #[test_case("X")]
 #[test_case("X/y1")]
 #[test_case("A/y1")]
@@ -221,6 +859,19 @@ fn test_real_parent_symlink_cycle_look_alikes(path: &str, expected: &str) {
     check_path_ok(&farm, None, path, expected, Path::real_parent);
 }
 
+// a symlink traversed again once it has been resolved is not a cycle
+#[test_case("A/_b", ".")]
+#[test_case("_a/_b", ".")]
+fn test_real_parent_symlink_traversed_twice(path: &str, expected: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .symlink_rel("_a", "A")
+        .symlink_rel("A/_b", "../_a/../_a");
+
+    check_path_ok(&farm, None, path, expected, Path::real_parent);
+}
+
 #[test_case("x1", "x1")]
 #[test_case("A", "A")]
 #[test_case("A/a1", "A/a1")]
@@ -263,6 +914,21 @@ fn test_real_clean_root_dir() {
     check_path_ok(&farm, None, &path, expected, Path::real_clean);
 }
 
+#[test_case("", "")]
+#[test_case(".", "")]
+#[test_case("A/..", ""; "collapses to nothing")]
+#[test_case("A/B/../..", "")]
+#[test_case("A/B/..", "A")]
+#[test_case("..", "..")]
+fn test_try_real_clean_raw(path: &str, expected: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").dir("A/B");
+
+    let actual = with_cwd(farm.absolute(".")).run(Path::try_real_clean_raw, Path::new(path));
+    assert_eq!(actual.unwrap(), Path::new(expected));
+}
+
 #[test_case("C/..", "A/B", ".")]
 #[test_case("../..", "A/B/C", "../..")]
 #[test_case("../C/../../B", "A/B/C", "../../B")]
@@ -341,6 +1007,98 @@ fn test_real_clean_abs_symlinks(path: &str, expected: &str) {
     check_path_ok(&farm, None, path, farm.absolute(expected), Path::real_clean);
 }
 
+#[test_case("A/B/b1", "A/B/b1", 0, 0, 0, false; "unchanged")]
+#[test_case("", ".", 0, 0, 0, true; "empty path")]
+#[test_case(".", ".", 0, 0, 0, false; "bare dot")]
+#[test_case("./A/B/b1", "A/B/b1", 0, 1, 0, true; "initial dot removed")]
+#[test_case("A/./B/./C/.", "A/B/C", 0, 3, 0, true; "dots removed")]
+#[test_case("A/B/C/..", "A/B", 1, 0, 0, true; "dotdot folded")]
+#[test_case("..", "..", 0, 0, 0, false; "bare dotdot")]
+#[test_case("A/B/_a1/..", "A", 2, 0, 1, true; "symlink resolved")]
+#[test_case("A/B/C/_B/../B/C/..", "A/B", 3, 0, 1, true; "symlink target dotdot folded")]
+fn test_real_clean_diff(
+    path: &str,
+    expected: &str,
+    dotdots_folded: usize,
+    dots_removed: usize,
+    symlinks_resolved: usize,
+    changed: bool,
+) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("A/B/C")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("A/B/C/_B", "./..");
+
+    check_path_ok(&farm, None, path, expected, |path| {
+        path.real_clean_diff().map(|(clean, _)| clean)
+    });
+
+    let (_, actual) = with_cwd(farm.absolute("."))
+        .run(|path: &Path| path.real_clean_diff(), Path::new(path))
+        .unwrap();
+    assert_eq!(
+        actual,
+        CleanChanges {
+            dotdots_folded,
+            dots_removed,
+            symlinks_resolved,
+            changed
+        }
+    );
+}
+
+#[test_case("A/B/b1", true)]
+#[test_case("A/B/_a1", true; "symlink")]
+#[test_case(".", true; "bare dot")]
+#[test_case("..", true; "bare dotdot")]
+#[test_case("../../A", true; "leading dotdots")]
+#[test_case("", false; "empty path")]
+#[test_case("./A/B", false; "initial dot")]
+#[test_case("A/./B", false; "inner dot")]
+#[test_case("A/B/.", false; "trailing dot")]
+#[test_case("A//B", false; "repeated separator")]
+#[test_case("A/B/", false; "trailing separator")]
+#[test_case("A/B/..", false; "dotdot folded")]
+#[test_case("A/B/_a1/..", false; "symlink resolved")]
+#[test_case("A/../..", false; "dotdot after dotdot folded")]
+fn test_is_real_minimal(path: &str, expected: bool) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("A/B/_a1", "../a1");
+
+    // native separators, since those are what cleaning produces
+    let path = path.replace('/', std::path::MAIN_SEPARATOR_STR);
+    let (actual, clean) = with_cwd(farm.absolute(".")).run(
+        |path: &Path| (path.is_real_minimal(), path.real_clean()),
+        Path::new(&path),
+    );
+    assert_eq!(actual.unwrap(), expected);
+    assert_eq!(clean.unwrap().as_os_str() == path.as_str(), expected);
+
+    let path = farm.absolute(&path);
+    assert_eq!(
+        path.is_real_minimal().unwrap(),
+        path.real_clean().unwrap().as_os_str() == path.as_os_str()
+    );
+}
+
+#[test]
+fn test_is_real_minimal_root_dir() {
+    let root_dir = root_dir();
+
+    assert!(root_dir.is_real_minimal().unwrap());
+    assert!(!root_dir.join("..").is_real_minimal().unwrap());
+}
+
 #[test]
 fn test_is_real_root_root_dir() {
     let root_dir = root_dir();
@@ -523,5 +1281,694 @@ fn test_is_real_root_not_abs_symlinks_not_windows(path: &str) {
     check_is_real_root_ok(&farm, path, false);
 }
 
+#[test_case("A/B/b1", "A/B/b1", "")]
+#[test_case("A/B/C/D", "A/B", "C/D")]
+#[test_case("_B/C", "_B", "C")]
+#[test_case("_B/../X/Y", "A", "X/Y")]
+#[test_case("A/./B/../X", "A", "X")]
+#[test_case("A/X/../Y", "A", "X/../Y"; "tail is not cleaned")]
+#[test_case("X", ".", "X")]
+#[test_case("", ".", "")]
+#[test_case("../X", "..", "X")]
+fn test_real_existing_prefix(path: &str, expected_existing: &str, expected_tail: &str) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/B/b1")
+        .symlink_rel("_B", "A/B");
+
+    // relative paths
+    let (existing, tail) = with_cwd(farm.absolute("."))
+        .run(|path: &Path| path.real_existing_prefix(), Path::new(path))
+        .unwrap();
+    assert_eq!(existing, Path::new(expected_existing));
+    assert_eq!(tail, Path::new(expected_tail));
+
+    // absolute paths
+    let (existing, tail) = farm.absolute(path).real_existing_prefix().unwrap();
+    let expected_existing = farm.absolute(expected_existing).real_clean().unwrap();
+    assert_eq!(existing, expected_existing);
+    assert_eq!(tail, Path::new(expected_tail));
+}
+
+#[test]
+fn test_real_existing_prefix_not_a_directory() {
+    let farm = LinkFarm::new();
+    farm.dir("A").file("A/a1");
+
+    let result = with_cwd(farm.absolute(".")).run(
+        |path: &Path| path.real_existing_prefix(),
+        Path::new("A/a1/X"),
+    );
+    assert!(result.is_err());
+}
+
+#[test_case("A/B/b1", "A/B/b1", "")]
+#[test_case("A/B/_b1", "A/B", "_b1")]
+#[test_case("_B/b1", ".", "_B/b1")]
+#[test_case("A/_B/b1", "A", "_B/b1")]
+#[test_case("A/./B/../_B/b1", "A/./B/..", "_B/b1"; "prefix is not cleaned")]
+#[test_case("A/B/../../_B", "A/B/../..", "_B")]
+#[test_case("", ".", "")]
+fn test_real_symlink_free_prefix(path: &str, expected_prefix: &str, expected_tail: &str) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/B/b1")
+        .symlink_rel("A/B/_b1", "b1")
+        .symlink_rel("A/_B", "B")
+        .symlink_rel("_B", "A/B");
+
+    // relative paths
+    let (prefix, tail) = with_cwd(farm.absolute("."))
+        .run(
+            |path: &Path| path.real_symlink_free_prefix(),
+            Path::new(path),
+        )
+        .unwrap();
+    assert_eq!(prefix, Path::new(expected_prefix));
+    assert_eq!(tail, Path::new(expected_tail));
+
+    // absolute paths
+    let (prefix, tail) = farm.absolute(path).real_symlink_free_prefix().unwrap();
+    assert_eq!(prefix, farm.absolute(expected_prefix));
+    assert_eq!(tail, Path::new(expected_tail));
+}
+
+#[test]
+fn test_real_symlink_free_prefix_not_found() {
+    let farm = LinkFarm::new();
+    farm.dir("A");
+
+    let result = with_cwd(farm.absolute(".")).run(
+        |path: &Path| path.real_symlink_free_prefix(),
+        Path::new("A/X/_y"),
+    );
+    assert!(result.is_err());
+}
+
+#[test_case("A/B/b1", "A/B", Some("b1"))]
+#[test_case("A/B/x1", "A/B", Some("x1"); "leaf need not exist")]
+#[test_case("_B/b1", "A/B", Some("b1"))]
+#[test_case("_B", ".", Some("_B"); "leaf symlink not followed")]
+#[test_case("_B/../x1", "A", Some("x1"))]
+#[test_case("A/B/.", "A", Some("B"); "trailing dot")]
+#[test_case("_B/..", "A", None)]
+#[test_case("", ".", None)]
+fn test_real_anchor(path: &str, expected_dir: &str, expected_leaf: Option<&str>) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/B/b1")
+        .symlink_rel("_B", "A/B");
+
+    let expected_dir = farm.absolute(expected_dir).canonicalize().unwrap();
+    let expected = match expected_leaf {
+        Some(leaf) => expected_dir.join(leaf),
+        None => expected_dir,
+    };
+
+    // relative paths
+    let actual = with_cwd(farm.absolute("."))
+        .run(|path: &Path| path.real_anchor(), Path::new(path))
+        .unwrap();
+    assert_eq!(actual, expected);
+
+    // absolute paths
+    let actual = farm.absolute(path).real_anchor().unwrap();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_real_anchor_missing_dir() {
+    let farm = LinkFarm::new();
+    farm.dir("A");
+
+    let result = farm.absolute("A/X/x1").real_anchor();
+    assert!(result.is_err());
+}
+
+#[test_case("A/B/b1", "A/B/b1")]
+#[test_case("_B/b1", "_B/b1"; "symlink not resolved unless required")]
+#[test_case("_B/..", "A")]
+#[test_case("_B/../B/./b1", "A/B/b1")]
+#[test_case("A/B/../..", ".")]
+#[test_case(".", ".")]
+fn test_real_absolute(path: &str, expected: &str) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/B/b1")
+        .symlink_rel("_B", "A/B");
+
+    // relative paths, where the current directory is as reported by the OS
+    let cwd = farm.absolute(".").canonicalize().unwrap();
+    let actual = with_cwd(farm.absolute("."))
+        .run(|path: &Path| path.real_absolute(), Path::new(path))
+        .unwrap();
+    assert_eq!(actual, cwd.join(expected).components().collect::<PathBuf>());
+
+    // absolute paths
+    let actual = farm.absolute(path).real_absolute().unwrap();
+    assert_eq!(
+        actual,
+        farm.absolute(expected).components().collect::<PathBuf>()
+    );
+}
+
+#[test]
+fn test_real_absolute_contrast_std() {
+    let farm = LinkFarm::new();
+    farm.dir("A").dir("A/B").symlink_rel("_B", "A/B");
+
+    let path = farm.absolute("_B/..");
+    let real = path.real_absolute().unwrap();
+    let lexical = std::path::absolute(&path).unwrap();
+
+    // the physical parent of the symlinked directory is A, whereas lexically it is the farm root
+    assert_eq!(real, farm.absolute("A"));
+    assert_ne!(real, lexical);
+    #[cfg(target_family = "unix")]
+    assert_eq!(lexical, path);
+    #[cfg(target_family = "windows")]
+    assert_eq!(lexical, farm.absolute("."));
+}
+
+#[test_case("A/B/b1", vec!["A", "B", "b1"], vec!["A", "A/B", "A/B/b1"])]
+#[test_case("_B/b1", vec!["A", "_B", "b1"], vec!["A", "_B", "_B/b1"]; "symlinked dir")]
+#[test_case("A/_C/c1", vec!["A", "B", "_C", "c1"], vec!["A", "A/B", "A/_C", "A/_C/c1"]; "symlinked subdirectory")]
+#[test_case("_B/..", vec!["A"], vec!["A"])]
+fn test_real_breadcrumbs(path: &str, expected_names: Vec<&str>, expected_paths: Vec<&str>) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .dir("A/B/C")
+        .file("A/B/b1")
+        .file("A/B/C/c1")
+        .symlink_rel("_B", "A/B")
+        .symlink_rel("A/_C", "B/C");
+
+    let check = |actual: Vec<(OsString, PathBuf)>, top: PathBuf| {
+        // the root comes first, and each breadcrumb is the real parent of the next
+        assert!(actual[0].1.is_real_root().unwrap());
+        for pair in actual.windows(2) {
+            assert_eq!(pair[1].1.real_parent().unwrap(), pair[0].1);
+        }
+
+        let top_index = actual.iter().position(|(_, path)| *path == top).unwrap();
+        let (names, paths): (Vec<_>, Vec<_>) = actual.into_iter().skip(top_index + 1).unzip();
+        assert_eq!(names, expected_names);
+        assert_eq!(
+            paths,
+            expected_paths
+                .iter()
+                .map(|p| top.join(p))
+                .collect::<Vec<_>>()
+        );
+    };
+
+    // relative paths, where the current directory is as reported by the OS
+    let actual = with_cwd(farm.absolute("."))
+        .run(|path: &Path| path.real_breadcrumbs(), Path::new(path))
+        .unwrap();
+    check(actual, farm.absolute(".").canonicalize().unwrap());
+
+    // absolute paths
+    let actual = farm.absolute(path).real_breadcrumbs().unwrap();
+    check(actual, farm.absolute(".").components().collect::<PathBuf>());
+}
+
+#[test]
+fn test_real_absolute_empty() {
+    let actual = Path::new("").real_absolute();
+    assert_eq!(
+        actual.unwrap_err().kind(),
+        std::path::absolute("").unwrap_err().kind()
+    );
+}
+
+#[test_case("A/B/b1", "A/B/b1")]
+#[test_case("A//B/", "A/B"; "repeated and trailing separators")]
+#[test_case("./A/./B", "A/B"; "dots")]
+#[test_case("_B/../B/b1", "A/B/b1"; "dotdot after symlink")]
+#[test_case("", "."; "empty")]
+fn test_real_clean_from_unix_str(s: &str, expected: &str) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/B/b1")
+        .symlink_rel("_B", "A/B");
+
+    let actual = with_cwd(farm.absolute(".")).run(|s: &str| real_clean_from_unix_str(s), s);
+    assert_eq!(actual.unwrap(), expected.split('/').collect::<PathBuf>());
+}
+
+#[test_case("x1", "x1")]
+#[test_case("../x1", "../x1"; "sibling of exe dir")]
+#[test_case("./x1", "x1"; "dot")]
+fn test_real_clean_from_exe_dir(path: &str, expected: &str) {
+    let exe_dir = std::env::current_exe().unwrap().real_parent().unwrap();
+    let expected = exe_dir.join(expected).real_clean().unwrap();
+
+    let actual = real_clean_from_exe_dir(path).unwrap();
+    assert_eq!(actual, expected);
+    assert!(actual.is_absolute());
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_real_clean_from_unix_str_absolute() {
+    let farm = LinkFarm::new();
+    farm.dir("A").dir("A/B").symlink_rel("_B", "A/B");
+
+    let s = format!("{}//_B/..", farm.absolute(".").to_str().unwrap());
+    let actual = real_clean_from_unix_str(&s).unwrap();
+    assert_eq!(actual, farm.absolute("A"));
+}
+
+#[test_case("x1", None)]
+#[test_case("A", None)]
+#[test_case("A/B/_b1", Some("b1"))]
+#[test_case("A/B/_a1", Some("../a1"))]
+#[test_case("A/B/C/_b1", Some("../b1"); "dots in target")]
+#[test_case("A/B/_A", Some(".."))]
+#[test_case("A/B/_B", Some("."))]
+#[test_case("A/_dot", Some(".."))]
+#[test_case("A/B/__a1", Some("../a1"); "indirect")]
+#[test_case("A/B/C/__b1", Some("../b1"); "indirect via parent")]
+#[test_case("_B/_a1", Some("../a1"); "via symlinked directory")]
+fn test_real_link_target(path: &str, expected: Option<&str>) {
+    let farm = LinkFarm::new();
+    farm.file("x1")
+        .dir("A")
+        .dir("A/B")
+        .dir("A/B/C")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("_B", "A/B")
+        .symlink_rel("A/_dot", "..")
+        .symlink_rel("A/B/_A", "..")
+        .symlink_rel("A/B/_B", ".")
+        .symlink_rel("A/B/_b1", "b1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("A/B/__a1", "_a1")
+        .symlink_rel("A/B/C/_b1", "./.././b1")
+        .symlink_rel("A/B/C/__b1", "../_b1");
+
+    let expected = expected.map(PathBuf::from);
+
+    // relative paths
+    let actual = with_cwd(farm.absolute("."))
+        .run(|path: &Path| path.real_link_target(), Path::new(path))
+        .unwrap();
+    assert_eq!(actual, expected);
+
+    // absolute paths
+    let actual = farm.absolute(path).real_link_target().unwrap();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_real_link_target_abs_symlink() {
+    let mut farm = LinkFarm::new();
+    farm.dir("A").dir("A/B").file("A/a1");
+    farm.symlink_abs("A/B/=a1", "A/a1");
+
+    // an absolute target can't be made relative to a relative path
+    let actual = with_cwd(farm.absolute("."))
+        .run(|path: &Path| path.real_link_target(), Path::new("A/B/=a1"));
+    assert_eq!(actual.unwrap(), Some(farm.absolute("A/a1")));
+
+    let actual = farm.absolute("A/B/=a1").real_link_target().unwrap();
+    assert_eq!(actual, Some(PathBuf::from("../a1")));
+}
+
+#[test]
+fn test_real_link_target_cycle() {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .symlink_rel("A/_c1", "_c2")
+        .symlink_rel("A/_c2", "_c1");
+
+    check_path_err(&farm, "A/_c1", |path| {
+        path.real_link_target().map(Option::unwrap_or_default)
+    });
+}
+
+#[test_case("A/B/b1", "A", Some("B/b1"))]
+#[test_case("A/B/b1", "A/B/b1", Some(""))]
+#[test_case("A/B/C/..", "A/B", Some(""); "dotdot folded")]
+#[test_case("A/B/b1", "A/C", None)]
+#[test_case("A/Bb", "A/B", None; "partial component")]
+#[test_case("_B/b1", "A/B", Some("b1"); "via symlink")]
+#[test_case("A/B/b1", "_B", Some("b1"); "base via symlink")]
+#[test_case("A/X", "A/B", None; "nonexistent")]
+fn test_real_strip_prefix(path: &str, base: &str, expected: Option<&str>) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .dir("A/B/C")
+        .dir("A/C")
+        .file("A/B/b1")
+        .file("A/Bb")
+        .symlink_rel("_B", "A/B");
+
+    let expected = expected.map(PathBuf::from);
+
+    let actual = with_cwd(farm.absolute("."))
+        .run(|path: &Path| path.real_strip_prefix(base), Path::new(path));
+    assert_eq!(actual.unwrap(), expected);
+
+    let actual = with_cwd(farm.absolute("."))
+        .run(|path: &Path| path.real_starts_with(base), Path::new(path));
+    assert_eq!(actual.unwrap(), expected.is_some());
+
+    // mixed relative and absolute paths
+    let actual = with_cwd(farm.absolute(".")).run(
+        |path: &Path| path.real_starts_with(farm.absolute(base)),
+        Path::new(path),
+    );
+    assert_eq!(actual.unwrap(), expected.is_some());
+}
+
+#[test_case("A/B/b1", "a/b")]
+#[test_case("A/B/b1", "A/b/B1")]
+#[cfg(any(target_family = "windows", target_os = "macos"))]
+fn test_real_starts_with_case_insensitive(path: &str, base: &str) {
+    let farm = LinkFarm::new();
+    farm.dir("A").dir("A/B").file("A/B/b1");
+
+    let actual = farm.absolute(path).real_starts_with(farm.absolute(base));
+    assert!(actual.unwrap());
+
+    let actual = with_cwd(farm.absolute("."))
+        .run(|path: &Path| path.real_starts_with(base), Path::new(path));
+    assert!(actual.unwrap());
+}
+
+#[test_case("A/B/b1", "a/b")]
+#[test_case("A/B/b1", "A/b/B1")]
+#[cfg(not(any(target_family = "windows", target_os = "macos")))]
+fn test_real_starts_with_case_sensitive(path: &str, base: &str) {
+    let farm = LinkFarm::new();
+    farm.dir("A").dir("A/B").file("A/B/b1");
+
+    let actual = farm.absolute(path).real_starts_with(farm.absolute(base));
+    assert!(!actual.unwrap());
+
+    let actual = with_cwd(farm.absolute("."))
+        .run(|path: &Path| path.real_starts_with(base), Path::new(path));
+    assert!(!actual.unwrap());
+}
+
+#[test_case("A/B", "A/B", true)]
+#[test_case("A/B", "_B", true; "via symlink")]
+#[test_case("A/B/b1", "_B/_b1", true; "via symlinks")]
+#[test_case("A/B/../B", "A/B", true; "dotdot")]
+#[test_case("A", "A/B", false)]
+#[test_case("A/B/b1", "A/b1", false; "same name")]
+fn test_real_same_file(path: &str, other: &str, expected: bool) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/b1")
+        .file("A/B/b1")
+        .symlink_rel("_B", "A/B")
+        .symlink_rel("A/B/_b1", "b1");
+
+    let actual =
+        with_cwd(farm.absolute(".")).run(|path: &Path| path.real_same_file(other), Path::new(path));
+    assert_eq!(actual.unwrap(), expected);
+
+    let actual = farm.absolute(path).real_same_file(farm.absolute(other));
+    assert_eq!(actual.unwrap(), expected);
+}
+
+#[test]
+fn test_real_key() {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/b1")
+        .file("A/B/b1")
+        .symlink_rel("_B", "A/B")
+        .symlink_rel("A/B/_b1", "b1");
+
+    let keys = ["A/B/b1", "_B/b1", "A/B/../B/b1", "A/B/_b1", "A/b1", "_B"]
+        .map(|path| farm.absolute(path).real_key().unwrap())
+        .into_iter()
+        .collect::<std::collections::HashSet<_>>();
+    assert_eq!(keys.len(), 3);
+
+    // relative and absolute spellings agree
+    let relative = with_cwd(farm.absolute("A")).run(Path::real_key, Path::new("B/b1"));
+    assert_eq!(
+        relative.unwrap(),
+        farm.absolute("_B/b1").real_key().unwrap()
+    );
+
+    assert!(farm.absolute("A/x1").real_key().is_err());
+}
+
+#[test_case("R/S/r1", Some("S/r1"); "within repo")]
+#[test_case("R", Some("."); "root")]
+#[test_case("R/S/../S/r1", Some("S/r1"); "dotdot")]
+#[test_case("_S/r1", Some("S/r1"); "via symlink")]
+#[test_case("R/_T/t1", Some("t1"); "via symlink to another repo")]
+#[test_case("W/w1", Some("w1"); "worktree")]
+#[test_case("O/o1", None; "outside")]
+fn test_real_repo_relative(path: &str, expected: Option<&str>) {
+    let farm = LinkFarm::new();
+    farm.dir("R")
+        .dir("R/.git")
+        .dir("R/S")
+        .dir("T")
+        .dir("T/.git")
+        .dir("W")
+        .dir("O")
+        .file("R/S/r1")
+        .file("T/t1")
+        .file("W/.git")
+        .file("W/w1")
+        .file("O/o1")
+        .symlink_rel("_S", "R/S")
+        .symlink_rel("R/_T", "../T");
+
+    let expected = expected.map(PathBuf::from);
+    let actual = farm.absolute(path).real_repo_relative().unwrap();
+    assert_eq!(actual, expected);
+
+    let actual = with_cwd(farm.absolute(".")).run(Path::real_repo_relative, Path::new(path));
+    assert_eq!(actual.unwrap(), expected);
+}
+
+#[test_case("A/a1", 1; "single")]
+#[test_case("A/b1", 2; "hard linked")]
+#[test_case("A/B/b1", 2; "other hard link")]
+#[test_case("A/B/_b1", 2; "via symlink")]
+#[test_case("_B/../b1", 2; "dotdot after symlink")]
+fn test_real_hardlink_count(path: &str, expected: u64) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .file("A/b1")
+        .symlink_rel("_B", "A/B")
+        .symlink_rel("A/B/_b1", "b1");
+    std::fs::hard_link(farm.absolute("A/b1"), farm.absolute("A/B/b1")).unwrap();
+
+    let actual = farm.absolute(path).real_hardlink_count().unwrap();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_real_dir_key() {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .dir("C")
+        .file("A/B/b1")
+        .file("A/B/b2")
+        .file("C/c1")
+        .symlink_rel("_B", "A/B")
+        .symlink_rel("C/_B", "../A/B")
+        .symlink_rel("C/_b1", "../A/B/b1");
+
+    let key = farm.absolute("A/B/b1").real_dir_key().unwrap();
+    for path in ["A/B/b2", "_B/b1", "C/_B/b2", "C/_b1", "A/B/../B/b1"] {
+        assert_eq!(farm.absolute(path).real_dir_key().unwrap(), key, "{}", path);
+    }
+    assert_eq!(farm.absolute("A/B").real_key().unwrap(), key);
+
+    let other = farm.absolute("C/c1").real_dir_key().unwrap();
+    assert_ne!(other, key);
+
+    // unaffected by renaming a symlink by which the directory was reached
+    std::fs::rename(farm.absolute("_B"), farm.absolute("_B_renamed")).unwrap();
+    assert_eq!(farm.absolute("_B_renamed/b1").real_dir_key().unwrap(), key);
+}
+
+#[test_case("A/B/b1", "A/B", Some(1))]
+#[test_case("A/B/b1", "A", Some(2))]
+#[test_case("A/B/b1", "A/B/b1", Some(0); "self")]
+#[test_case("A/B", "_B", Some(0); "self via symlink")]
+#[test_case("_B/b1", "A", Some(2); "via symlinked dir")]
+#[test_case("_B/b1", ".", Some(3); "via symlinked dir to its lexical parent")]
+#[test_case("A/B/_b1", "A", Some(2); "via symlinked file")]
+#[test_case("A", "A/B", None; "descendant")]
+#[test_case("A/B/b1", "X", None; "sibling")]
+fn test_real_updepth_to(path: &str, ancestor: &str, expected: Option<usize>) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("X")
+        .file("A/B/b1")
+        .symlink_rel("A/B/_b1", "b1")
+        .symlink_rel("_B", "A/B");
+
+    let actual = with_cwd(farm.absolute(".")).run(
+        |path: &Path| path.real_updepth_to(ancestor),
+        Path::new(path),
+    );
+    assert_eq!(actual.unwrap(), expected);
+
+    let actual = farm.absolute(path).real_updepth_to(farm.absolute(ancestor));
+    assert_eq!(actual.unwrap(), expected);
+}
+
+#[test]
+fn test_real_ancestors() {
+    let farm = LinkFarm::new();
+    let farm_depth = farm.depth_below_root();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/B/b1")
+        .symlink_rel("_b1", "A/B/b1");
+
+    // relative paths ascend via `..` until root
+    let ancestors = with_cwd(farm.absolute(".")).run(
+        |path: &Path| path.real_ancestors().collect::<Result<Vec<_>, _>>(),
+        Path::new("_b1"),
+    );
+    let ancestors = ancestors.unwrap();
+    assert_eq!(ancestors.len(), farm_depth + 4);
+    assert_eq!(
+        &ancestors[..5],
+        &["_b1", "A/B", "A", ".", ".."].map(PathBuf::from)
+    );
+    assert!(ancestors.last().unwrap().is_real_root().unwrap());
+
+    // absolute paths simply ascend
+    let ancestors = farm
+        .absolute("A/B/b1")
+        .real_ancestors()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(ancestors.len(), farm_depth + 4);
+    assert_eq!(
+        &ancestors[..4],
+        &["A/B/b1", "A/B", "A", "."].map(|p| farm.absolute(p))
+    );
+    assert!(ancestors.last().unwrap().is_real_root().unwrap());
+}
+
+#[test]
+fn test_real_ancestors_with_metadata() {
+    let farm = LinkFarm::new();
+    let farm_depth = farm.depth_below_root();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/B/b1")
+        .symlink_rel("_b1", "A/B/b1");
+
+    let ancestors = farm
+        .absolute("_b1")
+        .real_ancestors_with_metadata()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(ancestors.len(), farm_depth + 4);
+    assert_eq!(
+        ancestors[..3]
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>(),
+        ["_b1", "A/B", "A"].map(|p| farm.absolute(p))
+    );
+
+    // the metadata of the path itself is that of the symlink target
+    assert!(ancestors[0].1.is_file());
+    assert!(ancestors[1..].iter().all(|(_, metadata)| metadata.is_dir()));
+}
+
+// longer than `PATH_MAX` on both Linux and MacOS
+#[cfg(not(target_family = "windows"))]
+const LONG_DEPTH: usize = 40;
+#[cfg(not(target_family = "windows"))]
+const LONG_NAME_LEN: usize = 200;
+
+#[cfg(not(target_family = "windows"))]
+fn long_name() -> String {
+    "D".repeat(LONG_NAME_LEN)
+}
+
+#[cfg(not(target_family = "windows"))]
+fn long_path() -> PathBuf {
+    (0..LONG_DEPTH).map(|_| long_name()).collect::<PathBuf>()
+}
+
+// create a directory hierarchy deeper than `PATH_MAX` below the current directory,
+// which can only be done one relative step at a time
+#[cfg(not(target_family = "windows"))]
+fn create_long_path_in_cwd() {
+    use std::{env::set_current_dir, fs::create_dir};
+
+    for _i in 0..LONG_DEPTH {
+        create_dir(long_name()).unwrap();
+        set_current_dir(long_name()).unwrap();
+    }
+}
+
+#[test]
+#[cfg(not(target_family = "windows"))]
+fn test_real_parent_long_path_error() {
+    use std::env::set_current_dir;
+
+    let farm = LinkFarm::new();
+
+    with_cwd(farm.absolute(".")).run(
+        |path: &Path| {
+            create_long_path_in_cwd();
+            set_current_dir(farm.absolute(".")).unwrap();
+
+            assert!(path.as_os_str().len() > 4096);
+            assert!(path.real_parent().is_err());
+            assert!(path.join("..").real_clean().is_err());
+            assert!(path.is_real_root().is_err());
+        },
+        long_path().as_path(),
+    );
+}
+
+#[test]
+#[cfg(not(target_family = "windows"))]
+fn test_real_parent_long_cwd() {
+    let farm = LinkFarm::new();
+
+    with_cwd(farm.absolute(".")).run(
+        |path: &Path| {
+            create_long_path_in_cwd();
+
+            // canonicalize may fail here, depending on the platform, but we don't rely on it
+            assert_eq!(path.real_parent().unwrap(), Path::new(".."));
+            assert_eq!(path.join("../..").real_clean().unwrap(), Path::new("../.."));
+            assert!(!path.is_real_root().unwrap());
+        },
+        Path::new("."),
+    );
+}
+
 mod helpers;
 use helpers::*;
@@ -0,0 +1,2090 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use real_parent::{
+    real_aliases_of, real_dirname_lines, real_parent_in, real_resolve_abs, real_retarget_symlink,
+    real_symlink_target_for, traversed_before_error, CacheStats, CleanOptions, Entry, EntryKind,
+    Explanation, FileSystem, FollowPolicy, InternStats, MemoryFileSystem, OutputStyle, PathExt,
+    Resolution, ResolveStats, ResolvedStep, Resolver, RootParentPolicy, StepResult,
+    DIRNAME_ERROR_MARKER,
+};
+use test_case::test_case;
+
+#[test]
+fn test_resolver_interned() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/B/b1")
+        .file("A/B/b2")
+        .file("A/a1")
+        .symlink_rel("_b1", "A/B/b1");
+
+    let mut resolver = Resolver::new().intern(true);
+
+    let (b1, b2, a1, via_symlink) = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| {
+            (
+                resolver.real_parent_interned(Path::new("A/B/b1")).unwrap(),
+                resolver.real_parent_interned(Path::new("A/B/b2")).unwrap(),
+                resolver.real_parent_interned(Path::new("A/a1")).unwrap(),
+                resolver.real_clean_interned(Path::new("_b1/..")).unwrap(),
+            )
+        },
+        &mut resolver,
+    );
+
+    assert_eq!(b1.as_ref(), Path::new("A/B"));
+    assert!(Arc::ptr_eq(&b1, &b2));
+    assert!(Arc::ptr_eq(&b1, &via_symlink));
+    assert_eq!(a1.as_ref(), Path::new("A"));
+    assert_eq!(
+        resolver.intern_stats(),
+        Some(InternStats {
+            paths: 2,
+            names: 2,
+            name_hits: 5,
+            hits: 2,
+            misses: 2,
+        })
+    );
+}
+
+#[test]
+fn test_resolver_interned_names() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/src")
+        .dir("B")
+        .dir("B/src")
+        .file("A/src/a1")
+        .file("B/src/b1");
+
+    let mut resolver = Resolver::new().intern(true);
+
+    let (a, b) = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| {
+            (
+                resolver
+                    .real_parent_interned(Path::new("A/src/a1"))
+                    .unwrap(),
+                resolver
+                    .real_parent_interned(Path::new("B/src/b1"))
+                    .unwrap(),
+            )
+        },
+        &mut resolver,
+    );
+
+    assert_eq!(a.as_ref(), Path::new("A/src"));
+    assert_eq!(b.as_ref(), Path::new("B/src"));
+    // the name `src` is stored once, though the paths differ
+    let stats = resolver.intern_stats().unwrap();
+    assert_eq!((stats.paths, stats.names, stats.name_hits), (2, 3, 1));
+}
+
+#[test]
+fn test_resolver_not_interned() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").file("A/a1").file("A/a2");
+
+    let mut resolver = Resolver::new();
+
+    let (a1, a2) = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| {
+            (
+                resolver.real_parent_interned(Path::new("A/a1")).unwrap(),
+                resolver.real_parent_interned(Path::new("A/a2")).unwrap(),
+            )
+        },
+        &mut resolver,
+    );
+
+    assert_eq!(a1, a2);
+    assert!(!Arc::ptr_eq(&a1, &a2));
+    assert_eq!(resolver.intern_stats(), None);
+}
+
+#[cfg(feature = "camino")]
+#[test]
+fn test_resolver_arc_str() {
+    use camino::Utf8Path;
+
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/B/b1")
+        .file("A/B/b2")
+        .symlink_rel("_b1", "A/B/b1");
+
+    let mut resolver = Resolver::new().intern(true);
+
+    let mut arc_str = |path: &str| {
+        let path = farm.absolute(path);
+        resolver
+            .real_parent_arc_str(Utf8Path::from_path(&path).unwrap())
+            .unwrap()
+    };
+    let b1 = arc_str("A/B/b1");
+    let b2 = arc_str("A/B/b2");
+    let via_symlink = arc_str("_b1");
+
+    assert_eq!(b1.as_ref(), farm.absolute("A/B").to_str().unwrap());
+    assert!(Arc::ptr_eq(&b1, &b2));
+    assert!(Arc::ptr_eq(&b1, &via_symlink));
+    assert_eq!(resolver.intern_stats().unwrap().paths, 1);
+}
+
+#[test]
+#[cfg(all(feature = "camino", target_family = "unix"))]
+fn test_resolver_arc_str_not_unicode() {
+    use camino::Utf8Path;
+    use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt};
+
+    let farm = LinkFarm::new();
+
+    let dir = farm.absolute(".").join(OsStr::from_bytes(b"\xff"));
+    fs::create_dir(&dir).unwrap();
+    fs::write(dir.join("x1"), "").unwrap();
+    // the path is UTF-8, but its real parent is not
+    std::os::unix::fs::symlink(dir.join("x1"), farm.absolute("_x1")).unwrap();
+
+    let path = farm.absolute("_x1");
+    let e = Resolver::new()
+        .intern(true)
+        .real_parent_arc_str(Utf8Path::from_path(&path).unwrap())
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_resolver_cached() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("C")
+        .symlink_rel("_B1", "A/B")
+        .symlink_rel("C/_B2", "../A/B");
+
+    let mut resolver = Resolver::new().cache(true);
+
+    let (b1, b2, b1_again) = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| {
+            (
+                resolver.real_parent(Path::new("_B1/.")).unwrap(),
+                resolver.real_parent(Path::new("C/_B2/.")).unwrap(),
+                resolver.real_parent(Path::new("_B1/.")).unwrap(),
+            )
+        },
+        &mut resolver,
+    );
+
+    assert_eq!(b1, Path::new("A"));
+    assert_eq!(b2, b1);
+    assert_eq!(b1_again, b1);
+
+    // directory identity is only available on unix, elsewhere the aliases are resolved separately
+    let identity_hits = if cfg!(target_family = "unix") { 1 } else { 0 };
+    assert_eq!(
+        resolver.cache_stats(),
+        Some(CacheStats {
+            path_hits: 1,
+            identity_hits,
+            misses: 2 - identity_hits,
+        })
+    );
+}
+
+#[test_case(false, "A"; "stale")]
+#[test_case(true, "C"; "validated")]
+fn test_resolver_validate_cache_mtime(validate: bool, expected: &str) {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .dir("C")
+        .dir("C/D")
+        .symlink_rel("_X", "A/B");
+
+    let mut resolver = Resolver::new().cache(true).validate_cache_mtime(validate);
+    let path = farm.absolute("_X");
+    assert_eq!(resolver.real_parent(&path).unwrap(), farm.absolute("A"));
+
+    // retarget the symlink, after long enough for the modification time to differ
+    std::thread::sleep(Duration::from_millis(20));
+    if cfg!(target_family = "windows") {
+        std::fs::remove_dir(&path).unwrap();
+    } else {
+        std::fs::remove_file(&path).unwrap();
+    }
+    farm.symlink_rel("_X", "C/D");
+
+    assert_eq!(
+        resolver.real_parent(&path).unwrap(),
+        farm.absolute(expected)
+    );
+}
+
+#[test]
+fn test_resolver_not_cached() {
+    let mut resolver = Resolver::new();
+
+    resolver.real_parent(Path::new("/")).unwrap();
+    assert_eq!(resolver.cache_stats(), None);
+}
+
+#[test_case(".", "A/B", "A"; "dot is base")]
+#[test_case("", "A/B", "A"; "empty is base")]
+#[test_case("b1", "A/B", "A/B")]
+#[test_case("C/.", "A/B", "A/B"; "trailing dot")]
+#[test_case("..", "A/B", "A/B/../.."; "dotdot")]
+#[test_case(".", "_B", "A"; "symlinked base")]
+#[test_case("b1", "_B", "_B")]
+#[test_case("_a1", "A/B", "A"; "symlink in base")]
+#[test_case(".", ".", ".."; "dot base")]
+#[test_case(".", "..", "../.."; "dotdot base")]
+fn test_real_parent_in(path: &str, base: &str, expected: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("A/B/C")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("_B", "A/B")
+        .symlink_rel("A/B/_a1", "../a1");
+
+    let actual = with_cwd(farm.absolute(".")).run(|path| real_parent_in(path, base), path);
+    assert_eq!(actual.unwrap(), Path::new(expected));
+}
+
+#[test]
+fn test_real_parent_in_absolute() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").dir("A/B").dir("C");
+
+    // an absolute path ignores the base
+    let actual = real_parent_in(farm.absolute("A/B"), "C").unwrap();
+    assert_eq!(actual, farm.absolute("A"));
+
+    // an absolute base means the current directory is irrelevant
+    let actual = real_parent_in(".", farm.absolute("A/B")).unwrap();
+    assert_eq!(actual, farm.absolute("A"));
+}
+
+#[test]
+fn test_real_aliases_of() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("C")
+        .file("A/a1")
+        .symlink_rel("_B", "A/B")
+        .symlink_rel("C/_B", "../A/B")
+        .symlink_rel("C/__B", "../_B")
+        .symlink_rel("C/_A", "../A")
+        .symlink_rel("C/_x", "../X")
+        .symlink_rel("A/_dot", "..");
+
+    let candidates = ["_B", "C/_B", "C/__B", "C/_A", "C/_x", "A/B", "A/a1", "Y"].map(PathBuf::from);
+
+    let actual = with_cwd(farm.absolute(".")).run(
+        |candidates: &[PathBuf]| real_aliases_of("A/B", candidates),
+        candidates.as_slice(),
+    );
+    assert_eq!(actual.unwrap(), ["_B", "C/_B", "C/__B"].map(PathBuf::from));
+}
+
+#[test]
+fn test_real_dirname_lines() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("_b1", "A/B/b1");
+
+    let input = "A/B/b1\nX/y1\n_b1\nA/a1\n\n";
+    let mut output = Vec::new();
+
+    with_cwd(farm.absolute("."))
+        .run(
+            |output: &mut Vec<u8>| {
+                real_dirname_lines(input.as_bytes(), output, &mut Resolver::new())
+            },
+            &mut output,
+        )
+        .unwrap();
+
+    // the bad line results in the error marker, distinct from the result for an empty line
+    let output = String::from_utf8(output).unwrap();
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 5, "{:?}", lines);
+    assert_eq!(lines[0], "A/B");
+    let error = lines[1].strip_prefix(DIRNAME_ERROR_MARKER).unwrap();
+    assert!(error.contains("X"), "{}", error);
+    assert_eq!(lines[2..], ["A/B", "A", ".."]);
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_real_dirname_lines_not_unicode() {
+    use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt};
+
+    let farm = LinkFarm::new();
+
+    let dir = farm.absolute(".").join(OsStr::from_bytes(b"\xff"));
+    fs::create_dir(&dir).unwrap();
+    fs::write(dir.join("x1"), "").unwrap();
+
+    let input = b"\xff/x1\nA/a1\r\n\xff/x1";
+    let mut output = Vec::new();
+
+    with_cwd(farm.absolute("."))
+        .run(
+            |output: &mut Vec<u8>| real_dirname_lines(&input[..], output, &mut Resolver::new()),
+            &mut output,
+        )
+        .unwrap();
+
+    // the missing file results in the error marker, and the final line needn't be terminated
+    let lines = output.split(|b| *b == b'\n').collect::<Vec<_>>();
+    assert_eq!(lines.len(), 4, "{:?}", lines);
+    assert_eq!(
+        (lines[0], lines[2], lines[3]),
+        (&b"\xff"[..], &b"\xff"[..], &b""[..])
+    );
+    assert!(lines[1].starts_with(DIRNAME_ERROR_MARKER.as_bytes()));
+}
+
+#[test]
+fn test_real_ancestors_cancellable() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").dir("A/B").dir("A/B/C");
+
+    let mut resolver = Resolver::new();
+    let levels = Arc::new(AtomicUsize::new(0));
+    let cancelled = Arc::new(AtomicBool::new(false));
+
+    let should_continue = {
+        let levels = levels.clone();
+        let cancelled = cancelled.clone();
+        move || {
+            levels.fetch_add(1, Ordering::Relaxed);
+            !cancelled.load(Ordering::Relaxed)
+        }
+    };
+
+    let path = farm.absolute("A/B/C");
+    let mut ancestors = resolver.real_ancestors_cancellable(&path, should_continue);
+
+    assert_eq!(ancestors.next().unwrap().unwrap(), path);
+    assert_eq!(ancestors.next().unwrap().unwrap(), farm.absolute("A/B"));
+    cancelled.store(true, Ordering::Relaxed);
+    let e = ancestors.next().unwrap().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::Interrupted);
+    assert!(ancestors.next().is_none());
+    assert_eq!(levels.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn test_stop_at_marker() {
+    let farm = LinkFarm::new();
+
+    farm.file("top.toml")
+        .dir("R")
+        .file("R/.repo-root")
+        .dir("R/A")
+        .file("R/A/cfg.toml")
+        .dir("R/A/B")
+        .dir("R/A/B/C")
+        .symlink_rel("_C", "R/A/B/C");
+
+    with_cwd(farm.absolute(".")).run(
+        |_| {
+            let mut resolver = Resolver::new().stop_at_marker(".repo-root");
+
+            let ancestors = resolver
+                .real_ancestors(Path::new("_C"))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(ancestors, ["_C", "R/A/B", "R/A", "R"].map(PathBuf::from));
+
+            assert_eq!(
+                resolver.marker_root(Path::new("_C")).unwrap(),
+                Some(PathBuf::from("R"))
+            );
+            assert_eq!(
+                resolver.find_upwards(Path::new("_C"), "cfg.toml").unwrap(),
+                Some(PathBuf::from("R/A/cfg.toml"))
+            );
+            // beyond the marker root
+            assert_eq!(
+                resolver.find_upwards(Path::new("_C"), "top.toml").unwrap(),
+                None
+            );
+            // no marker among ancestors
+            assert_eq!(resolver.marker_root(Path::new("..")).unwrap(), None);
+
+            let mut resolver = Resolver::new();
+            assert_eq!(
+                resolver.find_upwards(Path::new("_C"), "top.toml").unwrap(),
+                Some(PathBuf::from("./top.toml"))
+            );
+            assert_eq!(resolver.marker_root(Path::new("_C")).unwrap(), None);
+        },
+        (),
+    );
+}
+
+#[test_case("A/B/_a1", FollowPolicy::All, "A")]
+#[test_case("A/B/_a1", FollowPolicy::FilesOnly, "A")]
+#[test_case("A/B/_a1", FollowPolicy::DirsOnly, "A/B")]
+#[test_case("A/B/_a1", FollowPolicy::None, "A/B")]
+#[test_case("_B/.", FollowPolicy::All, "A")]
+#[test_case("_B/.", FollowPolicy::FilesOnly, ".")]
+#[test_case("_B/.", FollowPolicy::DirsOnly, "A")]
+#[test_case("_B/.", FollowPolicy::None, ".")]
+fn test_real_parent_follow_policy(path: &str, follow: FollowPolicy, expected: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("_B", "A/B");
+
+    let mut resolver = Resolver::new().follow_policy(follow);
+    let actual = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| resolver.real_parent(Path::new(path)),
+        &mut resolver,
+    );
+    assert_eq!(actual.unwrap(), Path::new(expected));
+}
+
+#[test_case("_B/..", FollowPolicy::All, "A")]
+#[test_case("_B/..", FollowPolicy::FilesOnly, ".")]
+#[test_case("_B/..", FollowPolicy::DirsOnly, "A")]
+#[test_case("_B/..", FollowPolicy::None, ".")]
+#[test_case("A/B/_a1/..", FollowPolicy::FilesOnly, "A")]
+#[test_case("A/B/_a1/..", FollowPolicy::None, "A/B")]
+fn test_real_clean_follow_policy(path: &str, follow: FollowPolicy, expected: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("_B", "A/B");
+
+    let mut resolver = Resolver::new().follow_policy(follow);
+    let actual = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| resolver.real_clean(Path::new(path)),
+        &mut resolver,
+    );
+    assert_eq!(actual.unwrap(), Path::new(expected));
+}
+
+// resolve stepwise, returning the result and the number of steps taken
+fn real_parent_stepwise(resolver: &mut Resolver, path: &Path) -> io::Result<(PathBuf, usize)> {
+    let mut state = resolver.real_parent_start(path)?;
+    let mut steps = 0;
+
+    loop {
+        steps += 1;
+        match resolver.real_parent_step(state)? {
+            StepResult::Done(parent) => return Ok((parent, steps)),
+            StepResult::More(next) => state = next,
+        }
+    }
+}
+
+#[test_case("x1", false)]
+#[test_case("A/B/C", false)]
+#[test_case("A/B/C/.", false)]
+#[test_case("..", false)]
+#[test_case("", false)]
+#[test_case("A/B/_b1", true)]
+#[test_case("A/B/_a1", true)]
+#[test_case("A/B/C/_b1", true)]
+#[test_case("_B/.", true)]
+#[test_case("__b", true)]
+fn test_real_parent_step(path: &str, via_symlink: bool) {
+    let farm = LinkFarm::new();
+
+    farm.file("x1")
+        .dir("A")
+        .dir("A/B")
+        .dir("A/B/C")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("_B", "A/B")
+        .symlink_rel("A/B/_b1", "b1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("A/B/C/_b1", "./.././b1")
+        .symlink_rel("A/B/C/_b", "../../../_B/b1")
+        .symlink_rel("__b", "A/B/C/_b");
+
+    let mut resolver = Resolver::new();
+    let (actual, steps, expected) = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| {
+            let (actual, steps) = real_parent_stepwise(resolver, Path::new(path)).unwrap();
+            (actual, steps, Path::new(path).real_parent().unwrap())
+        },
+        &mut resolver,
+    );
+
+    assert_eq!(actual, expected);
+    // a single level of parent resolution, and delivering the result, each take one step
+    if via_symlink {
+        assert!(steps > 2, "{} steps", steps);
+    } else {
+        assert_eq!(steps, 2);
+    }
+}
+
+#[test]
+fn test_real_parent_step_cycle() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .symlink_rel("A/_c1", "_c2")
+        .symlink_rel("A/_c2", "_c1");
+
+    let mut resolver = Resolver::new().with_base(farm.absolute("."));
+    assert!(real_parent_stepwise(&mut resolver, Path::new("A/_c1")).is_err());
+}
+
+// a resolver with every option which affects the result of `real_parent`
+fn fully_optioned(resolver: Resolver) -> Resolver {
+    let resolver = resolver
+        .output_style(OutputStyle::RelativeTo("A".into()))
+        .clean_options(CleanOptions::new().preserve_leading_parent(false))
+        .keep_dangling_symlinks(true)
+        .max_resolved_len(12)
+        .collect_trace_on_error(true);
+
+    #[cfg(target_family = "unix")]
+    let resolver = resolver.stay_on_device(true);
+
+    resolver
+}
+
+// the outcome of resolution, comparable between stepwise resolution and otherwise
+fn outcome(
+    result: io::Result<PathBuf>,
+) -> Result<PathBuf, (io::ErrorKind, String, Option<Vec<PathBuf>>)> {
+    result.map_err(|e| {
+        let traversed = traversed_before_error(&e).map(<[PathBuf]>::to_vec);
+        (e.kind(), e.to_string(), traversed)
+    })
+}
+
+#[test_case("A/B/_a1", Ok("."))]
+#[test_case("A/B/C/_B/..", Ok("B/C/_B/../.."); "symlink then dotdot")]
+#[test_case("A/_dangling", Ok("."); "kept dangling symlink")]
+#[test_case("A/_twice", Ok("../_a/B"); "symlink traversed twice")]
+#[test_case("_d", Err("exceeds maximum length"); "too long")]
+#[test_case(".", Err("ascends above base"); "leading parent")]
+// found by the filesystem itself in checking whether the symlink dangles
+#[test_case("A/_c1", Err("A/_c1"); "cycle")]
+fn test_real_parent_step_equivalent(path: &str, expected: Result<&str, &str>) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("A/B/C")
+        .dir("A/B/C/DDDDDDDDDDDD")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("_a", "A")
+        .symlink_rel("_d", "A/B/C/DDDDDDDDDDDD/..")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("A/B/C/_B", "..")
+        .symlink_rel("A/_dangling", "B/missing")
+        .symlink_rel("A/_twice", "../_a/../_a/B/b1")
+        .symlink_rel("A/_c1", "_c2")
+        .symlink_rel("A/_c2", "_c1");
+
+    let mut resolver = fully_optioned(Resolver::new());
+    let (actual, stepwise) = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| {
+            let path = Path::new(path);
+            let actual = outcome(resolver.real_parent(path));
+            let stepwise = outcome(real_parent_stepwise(resolver, path).map(|(parent, _)| parent));
+            (actual, stepwise)
+        },
+        &mut resolver,
+    );
+
+    assert_eq!(stepwise, actual);
+    match expected {
+        Ok(expected) => assert_eq!(actual.unwrap(), Path::new(expected)),
+        Err(message) => {
+            let (_, actual, _) = actual.unwrap_err();
+            assert!(actual.contains(message), "{}", actual);
+        }
+    }
+}
+
+#[test_case("/", Err("has no parent"))]
+#[test_case("/..", Err("has no parent"))]
+#[test_case("A", Ok("."))]
+fn test_real_parent_step_root_parent(path: &str, expected: Result<&str, &str>) {
+    let farm = LinkFarm::new();
+    farm.dir("A");
+
+    let mut resolver = Resolver::new().root_parent(RootParentPolicy::Error);
+    let (actual, stepwise) = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| {
+            let path = Path::new(path);
+            let actual = outcome(resolver.real_parent(path));
+            let stepwise = outcome(real_parent_stepwise(resolver, path).map(|(parent, _)| parent));
+            (actual, stepwise)
+        },
+        &mut resolver,
+    );
+
+    assert_eq!(stepwise, actual);
+    match expected {
+        Ok(expected) => assert_eq!(actual.unwrap(), Path::new(expected)),
+        Err(message) => {
+            let (_, actual, _) = actual.unwrap_err();
+            assert!(actual.contains(message), "{}", actual);
+        }
+    }
+}
+
+#[test]
+fn test_real_parent_step_equivalent_trace() {
+    let path = Path::new("A/_b/C/c1/../..");
+    let mut resolver = fully_optioned(Resolver::new().with_file_system(DenyingFileSystem::new()))
+        .max_resolved_len(100);
+
+    let actual = outcome(resolver.real_parent(path));
+    let stepwise = outcome(real_parent_stepwise(&mut resolver, path).map(|(parent, _)| parent));
+
+    assert_eq!(stepwise, actual);
+    assert_eq!(
+        actual.unwrap_err().2.unwrap(),
+        vec![PathBuf::from("A"), PathBuf::from("A/_b")]
+    );
+}
+
+#[test]
+fn test_resolve_trace() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .symlink_rel("_B", "A/B")
+        .symlink_rel("A/B/_a1", "../a1");
+
+    let mut resolver = Resolver::new();
+    let actual = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| resolver.resolve_trace(Path::new("./_B/../B/_a1")),
+        &mut resolver,
+    );
+
+    let step = |input_component: &str, resolved_to: &str, target: Option<&str>| ResolvedStep {
+        input_component: input_component.into(),
+        resolved_to: resolved_to.into(),
+        was_symlink: target.is_some(),
+        target: target.map(PathBuf::from),
+    };
+    assert_eq!(
+        actual.unwrap(),
+        vec![
+            step(".", ".", None),
+            step("_B", "_B", Some("A/B")),
+            step("..", "A", None),
+            step("B", "A/B", None),
+            step("_a1", "A/B/_a1", Some("../a1")),
+        ]
+    );
+}
+
+#[test_case("A/B/c1", "A/B/c1", 0, 0, &[])]
+#[test_case("./A/./B/.", "A/B", 3, 0, &[])]
+#[test_case("A/_B/../c1", "A/c1", 0, 1, &["A/_B"])]
+#[test_case("A/B/../../C/..", ".", 0, 3, &["A/B", "A", "C"])]
+#[test_case("../A/..", "..", 0, 1, &["../A"])]
+#[test_case("../..", "../..", 0, 0, &[])]
+#[test_case("/..", "/", 0, 1, &[]; "root dotdot")]
+fn test_explain_lexical(
+    path: &str,
+    normalized: &str,
+    dots_removed: usize,
+    dotdots_folded: usize,
+    maybe_symlinks: &[&str],
+) {
+    // no farm, since the filesystem is never touched
+    let resolver = Resolver::new();
+
+    let actual = resolver.explain_lexical(Path::new(path));
+
+    assert_eq!(
+        actual,
+        Explanation {
+            normalized: normalized.into(),
+            dots_removed,
+            dotdots_folded,
+            maybe_symlinks: maybe_symlinks.iter().map(PathBuf::from).collect(),
+        }
+    );
+    assert_eq!(actual.requires_io(), !maybe_symlinks.is_empty());
+}
+
+#[test]
+fn test_explain_lexical_with_base() {
+    let resolver = Resolver::new().with_base("X/Y");
+
+    let actual = resolver.explain_lexical(Path::new("../Z"));
+
+    assert_eq!(actual.normalized, Path::new("X/Z"));
+    assert_eq!(actual.maybe_symlinks, vec![PathBuf::from("X/Y")]);
+}
+
+#[test_case("../X", Some("../X"))]
+#[test_case("A/../..", Some(".."))]
+#[test_case("_B/../../..", Some(".."); "via symlink")]
+#[test_case("_B/../..", None; "via symlink within base")]
+#[test_case("A/..", None)]
+#[test_case("A/B", None)]
+fn test_real_clean_preserve_leading_parent(path: &str, leading_parent: Option<&str>) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").dir("A/B").symlink_rel("_B", "A/B");
+
+    for preserve in [true, false] {
+        let mut resolver = Resolver::new()
+            .clean_options(CleanOptions::new().preserve_leading_parent(preserve))
+            .with_base(farm.absolute("."));
+        let expected = resolver.real_clean(&farm.absolute(path)).unwrap();
+
+        let mut resolver =
+            Resolver::new().clean_options(CleanOptions::new().preserve_leading_parent(preserve));
+        let actual = with_cwd(farm.absolute(".")).run(
+            |resolver: &mut Resolver| resolver.real_clean(Path::new(path)),
+            &mut resolver,
+        );
+
+        match (leading_parent, preserve) {
+            (Some(expected), true) => assert_eq!(actual.unwrap(), Path::new(expected)),
+            (Some(_), false) => {
+                assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::InvalidInput)
+            }
+            // an absolute path never has a leading `..`
+            (None, _) => assert_eq!(farm.absolute(actual.unwrap()), expected),
+        }
+    }
+}
+
+#[test_case(".", Some(".."))]
+#[test_case("..", Some("../.."))]
+#[test_case("_B/..", None; "trailing dotdot is not leading")]
+#[test_case("A", None)]
+#[test_case("_B/.", None; "symlink")]
+fn test_real_parent_preserve_leading_parent(path: &str, leading_parent: Option<&str>) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").dir("A/B").symlink_rel("_B", "A/B");
+
+    for preserve in [true, false] {
+        let mut resolver =
+            Resolver::new().clean_options(CleanOptions::new().preserve_leading_parent(preserve));
+        let actual = with_cwd(farm.absolute(".")).run(
+            |resolver: &mut Resolver| resolver.real_parent(Path::new(path)),
+            &mut resolver,
+        );
+
+        match (leading_parent, preserve) {
+            (Some(expected), true) => assert_eq!(actual.unwrap(), Path::new(expected)),
+            (Some(_), false) => {
+                assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::InvalidInput)
+            }
+            (None, _) => assert!(actual.is_ok()),
+        }
+    }
+}
+
+// a memory filesystem whose first few calls fail with the given kind of error
+#[derive(Debug)]
+struct FlakyFileSystem {
+    inner: MemoryFileSystem,
+    failures: usize,
+    kind: io::ErrorKind,
+    calls: Arc<AtomicUsize>,
+}
+
+impl FlakyFileSystem {
+    fn new(failures: usize, kind: io::ErrorKind, calls: Arc<AtomicUsize>) -> Self {
+        let inner = [
+            ("A", Entry::Dir),
+            ("A/B", Entry::Dir),
+            ("A/a1", Entry::File),
+            ("A/B/_a1", Entry::Symlink("../a1".into())),
+        ]
+        .into_iter()
+        .map(|(path, entry)| (path.into(), entry))
+        .collect::<BTreeMap<_, _>>()
+        .into();
+
+        Self {
+            inner,
+            failures,
+            kind,
+            calls,
+        }
+    }
+
+    fn fail(&self) -> io::Result<()> {
+        if self.calls.fetch_add(1, Ordering::Relaxed) < self.failures {
+            Err(self.kind.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl FileSystem for FlakyFileSystem {
+    fn entry_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        self.fail()?;
+        self.inner.entry_kind(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.fail()?;
+        self.inner.read_link(path)
+    }
+}
+
+#[test_case(io::ErrorKind::StaleNetworkFileHandle, 2, 3, true; "stale")]
+#[test_case(io::ErrorKind::Interrupted, 2, 3, true; "interrupted")]
+#[test_case(io::ErrorKind::StaleNetworkFileHandle, 3, 3, false; "too many failures")]
+#[test_case(io::ErrorKind::StaleNetworkFileHandle, 2, 1, false; "no retry")]
+#[test_case(io::ErrorKind::NotFound, 1, 3, false; "not found")]
+#[test_case(io::ErrorKind::PermissionDenied, 1, 3, false; "permission denied")]
+fn test_retry(kind: io::ErrorKind, failures: usize, attempts: u32, ok: bool) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut resolver = Resolver::new()
+        .with_file_system(FlakyFileSystem::new(failures, kind, calls.clone()))
+        .retry(attempts, Duration::ZERO);
+
+    let actual = resolver.real_parent(Path::new("A/B/_a1"));
+
+    if ok {
+        assert_eq!(actual.unwrap(), Path::new("A"));
+    } else {
+        assert!(actual.is_err());
+        // the failing call is made no more than once unless the error is transient
+        let transient = kind != io::ErrorKind::NotFound && kind != io::ErrorKind::PermissionDenied;
+        let expected_calls = match kind {
+            // then each ancestor of A/B/_a1 is probed for the inaccessible one
+            io::ErrorKind::PermissionDenied => 1 + 3,
+            _ if transient => attempts as usize,
+            _ => 1,
+        };
+        assert_eq!(calls.load(Ordering::Relaxed), expected_calls);
+    }
+}
+
+#[test_case("A/B/_a1"; "symlink")]
+#[test_case("A/B/../a1"; "dotdot")]
+fn test_max_io_ops(path: &str) {
+    let path = Path::new(path);
+
+    // the calls required, as counted by the filesystem itself
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut resolver = Resolver::new().with_file_system(FlakyFileSystem::new(
+        0,
+        io::ErrorKind::Other,
+        calls.clone(),
+    ));
+    let expected = resolver.real_parent(path).unwrap();
+    let required = calls.load(Ordering::Relaxed);
+    assert!(required > 0);
+
+    let mut resolver = Resolver::new()
+        .with_file_system(FlakyFileSystem::new(
+            0,
+            io::ErrorKind::Other,
+            Arc::new(AtomicUsize::new(0)),
+        ))
+        .max_io_ops(required);
+    // the budget is for each resolution, not for the resolver as a whole
+    for _ in 0..3 {
+        assert_eq!(resolver.real_parent(path).unwrap(), expected);
+    }
+
+    let mut resolver = Resolver::new()
+        .with_file_system(FlakyFileSystem::new(
+            0,
+            io::ErrorKind::Other,
+            Arc::new(AtomicUsize::new(0)),
+        ))
+        .max_io_ops(required - 1);
+    assert_eq!(
+        resolver.real_parent(path).unwrap_err().kind(),
+        io::ErrorKind::QuotaExceeded
+    );
+}
+
+// a memory filesystem where every call takes a while
+#[derive(Debug)]
+struct SlowFileSystem {
+    inner: MemoryFileSystem,
+    delay: Duration,
+}
+
+impl FileSystem for SlowFileSystem {
+    fn entry_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        std::thread::sleep(self.delay);
+        self.inner.entry_kind(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        std::thread::sleep(self.delay);
+        self.inner.read_link(path)
+    }
+}
+
+#[test_case(Duration::from_millis(10), Duration::from_secs(10), true; "fast")]
+#[test_case(Duration::from_millis(500), Duration::from_millis(10), false; "slow")]
+fn test_timeout(delay: Duration, timeout: Duration, ok: bool) {
+    let inner = [
+        ("A", Entry::Dir),
+        ("A/B", Entry::Dir),
+        ("A/a1", Entry::File),
+        ("A/B/_a1", Entry::Symlink("../a1".into())),
+    ]
+    .into_iter()
+    .map(|(path, entry)| (path.into(), entry))
+    .collect::<BTreeMap<_, _>>()
+    .into();
+    let mut resolver = Resolver::new()
+        .with_file_system(SlowFileSystem { inner, delay })
+        .timeout(timeout);
+
+    let actual = resolver.real_parent(Path::new("A/B/_a1"));
+
+    if ok {
+        assert_eq!(actual.unwrap(), Path::new("A"));
+    } else {
+        assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+}
+
+// a long acyclic chain of directory symlinks, alternating between two directories so that each link is distinct,
+// must be followed to its end without a false-positive cycle
+#[test_case(3)]
+#[test_case(30)]
+fn test_real_parent_long_symlink_chain(length: usize) {
+    let farm = LinkFarm::new();
+    farm.dir("A").dir("A/B").dir("C").dir("D");
+
+    let link = |i: usize| format!("{}/_l{}", if i.is_multiple_of(2) { "C" } else { "D" }, i);
+
+    // create from the end of the chain, since on Windows the target must exist to type the link
+    farm.symlink_rel(link(length - 1), "../A/B");
+    for i in (0..length - 1).rev() {
+        farm.symlink_rel(link(i), format!("../{}", link(i + 1)));
+    }
+
+    let mut resolver = Resolver::new();
+    let (actual, stats) = resolver
+        .real_parent_metered(&farm.absolute(link(0)))
+        .unwrap();
+
+    assert_eq!(
+        actual.components().collect::<PathBuf>(),
+        farm.absolute("A").components().collect::<PathBuf>()
+    );
+    assert_eq!(stats.symlink_hops, length);
+}
+
+// the behaviour tabulated in the README, where a based expectation is relative to the link farm
+#[test_case("", false, "..", "."; "empty")]
+#[test_case(".", false, "..", "."; "dot")]
+#[test_case("..", false, "../..", ".."; "dotdot")]
+#[test_case("", true, ".", "A"; "empty based")]
+#[test_case(".", true, ".", "A"; "dot based")]
+#[test_case("..", true, "A/../..", "."; "dotdot based")]
+fn test_empty_dot_dotdot(path: &str, based: bool, expected_parent: &str, expected_clean: &str) {
+    let farm = LinkFarm::new();
+    farm.dir("A").dir("A/B");
+
+    let path = Path::new(path);
+    let (mut resolver, expected_parent, expected_clean) = if based {
+        (
+            Resolver::new().with_base(farm.absolute("A")),
+            farm.absolute(expected_parent),
+            farm.absolute(expected_clean),
+        )
+    } else {
+        (
+            Resolver::new(),
+            PathBuf::from(expected_parent),
+            PathBuf::from(expected_clean),
+        )
+    };
+
+    let (actual_parent, actual_clean) = with_cwd(farm.absolute("A/B")).run(
+        |resolver: &mut Resolver| (resolver.real_parent(path), resolver.real_clean(path)),
+        &mut resolver,
+    );
+    assert_eq!(
+        actual_parent.unwrap(),
+        expected_parent.components().collect::<PathBuf>()
+    );
+    assert_eq!(
+        actual_clean.unwrap(),
+        expected_clean.components().collect::<PathBuf>()
+    );
+
+    if !based {
+        let (actual_parent, actual_clean) = with_cwd(farm.absolute("A/B"))
+            .run(|path: &Path| (path.real_parent(), path.real_clean()), path);
+        assert_eq!(actual_parent.unwrap(), expected_parent);
+        assert_eq!(actual_clean.unwrap(), expected_clean);
+    }
+}
+
+#[test_case("A/B/b1", "A/B", 0, 1, 0)]
+#[test_case("A/B/_b1", "A/B", 1, 2, 0)]
+#[test_case("A/B/_a1", "A", 1, 3, 1)]
+#[test_case("A/B/__a1", "A", 2, 4, 1; "chained")]
+#[test_case("_B/.", "A", 1, 2, 0; "symlinked directory")]
+fn test_real_parent_metered(
+    path: &str,
+    expected: &str,
+    symlink_hops: usize,
+    dirs_stated: usize,
+    dotdots_resolved: usize,
+) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("A/B/_b1", "b1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("A/B/__a1", "_a1")
+        .symlink_rel("_B", "A/B");
+
+    let mut resolver = Resolver::new();
+    let (actual, stats) = with_cwd(farm.absolute("."))
+        .run(
+            |resolver: &mut Resolver| resolver.real_parent_metered(Path::new(path)),
+            &mut resolver,
+        )
+        .unwrap();
+
+    assert_eq!(actual, Path::new(expected));
+    assert_eq!(
+        stats,
+        ResolveStats {
+            symlink_hops,
+            dirs_stated,
+            dotdots_resolved,
+        }
+    );
+}
+
+#[test_case("A/B/b1", "A/B", false, 0; "plain")]
+#[test_case("A/B/_a1", "A", true, 1; "symlink")]
+#[test_case("A/B/__a1", "A", true, 2; "chained")]
+fn test_resolve_detailed(path: &str, expected: &str, crossed_symlink: bool, hops: usize) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("A/B/__a1", "_a1");
+
+    let mut resolver = Resolver::new().with_base(farm.absolute("."));
+    let path = Path::new(path);
+    let actual = resolver.resolve_detailed(path).unwrap();
+
+    assert_eq!(
+        actual,
+        Resolution {
+            input: path.to_path_buf(),
+            real_parent: farm.absolute(expected),
+            crossed_symlink,
+            hops,
+            crossed_mount: false,
+            crossed_reparse: cfg!(target_family = "windows") && crossed_symlink,
+        }
+    );
+    assert_eq!(actual.real_parent, resolver.real_parent(path).unwrap());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_resolve_detailed_crossed_mount() {
+    let farm = LinkFarm::new();
+
+    farm.symlink_rel("_cpuinfo", "/proc/cpuinfo");
+
+    let mut resolver = Resolver::new().with_base(farm.absolute("."));
+    let actual = resolver.resolve_detailed(Path::new("_cpuinfo")).unwrap();
+
+    assert_eq!(actual.real_parent, Path::new("/proc"));
+    assert!(actual.crossed_mount);
+    assert!(!actual.crossed_reparse);
+
+    // no mount is crossed merely by following a symlink within the farm
+    farm.dir("A").symlink_rel("_A", "A").file("A/a1");
+    let actual = resolver.resolve_detailed(Path::new("_A/a1")).unwrap();
+    assert!(!actual.crossed_mount);
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn test_resolve_detailed_serde() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").file("A/a1").symlink_rel("_a1", "A/a1");
+
+    let mut resolver = Resolver::new().with_base(farm.absolute("."));
+    let resolution = resolver.resolve_detailed(Path::new("_a1")).unwrap();
+
+    let json = serde_json::to_value(&resolution).unwrap();
+    assert_eq!(json["input"], "_a1");
+    assert_eq!(json["crossed_symlink"], true);
+    assert_eq!(json["hops"], 1);
+    assert_eq!(json["crossed_mount"], false);
+
+    let actual = serde_json::from_value::<Resolution>(json).unwrap();
+    assert_eq!(actual, resolution);
+}
+
+#[test_case("A/B/b1", None, "A/B"; "as resolved")]
+#[test_case("A/B/b1", Some("C"), "../A/B")]
+#[test_case("A/B/b1", Some("A/B"), ".")]
+#[test_case("A/B/b1", Some("_B"), "../B"; "relative to symlink")]
+#[test_case("_B/b1", Some("A"), "../_B"; "symlink relative to directory")]
+fn test_output_style_relative(path: &str, relative_to: Option<&str>, expected: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("C")
+        .file("A/B/b1")
+        .symlink_rel("_B", "A/B");
+
+    let output_style = match relative_to {
+        Some(dir) => OutputStyle::RelativeTo(dir.into()),
+        None => OutputStyle::AsResolved,
+    };
+    let mut resolver = Resolver::new().output_style(output_style.clone());
+    let actual = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| resolver.real_parent(Path::new(path)),
+        &mut resolver,
+    );
+    assert_eq!(actual.unwrap(), Path::new(expected));
+
+    // the same for absolute paths
+    let mut resolver = Resolver::new().output_style(match output_style {
+        OutputStyle::RelativeTo(dir) => OutputStyle::RelativeTo(farm.absolute(dir)),
+        style => style,
+    });
+    let actual = resolver.real_parent(&farm.absolute(path)).unwrap();
+    let expected = match relative_to {
+        Some(_) => PathBuf::from(expected),
+        None => farm.absolute(expected),
+    };
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_output_style_absolute() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").dir("A/B").symlink_rel("_B", "A/B");
+
+    let mut resolver = Resolver::new().output_style(OutputStyle::Absolute);
+    let (parent, clean) = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| {
+            (
+                resolver.real_parent(Path::new("_B/.")).unwrap(),
+                resolver.real_clean(Path::new("_B/..")).unwrap(),
+            )
+        },
+        &mut resolver,
+    );
+
+    assert!(parent.is_absolute());
+    assert_eq!(
+        parent.canonicalize().unwrap(),
+        farm.absolute("A").canonicalize().unwrap()
+    );
+    assert!(clean.is_absolute());
+    assert_eq!(
+        clean.canonicalize().unwrap(),
+        farm.absolute("A").canonicalize().unwrap()
+    );
+}
+
+#[test_case("a1", "A", "A/a1")]
+#[test_case("B/../a1", "A", "A/a1")]
+#[test_case("_B/b1", "A", "A/B/b1"; "symlink within")]
+#[test_case("b1", "_B", "A/B/b1"; "symlinked base")]
+#[test_case("_B/../a1", "A", "A/a1"; "dotdot via symlink")]
+#[test_case(".", "A", "A")]
+fn test_real_resolve_abs(path: &str, base: &str, expected: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("C")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("A/_B", "B")
+        .symlink_rel("_B", "A/B");
+
+    // the current directory is irrelevant
+    let actual = with_cwd(farm.absolute("C"))
+        .run(
+            |path: &Path| real_resolve_abs(farm.absolute(base), path),
+            Path::new(path),
+        )
+        .unwrap();
+
+    assert_eq!(actual, farm.absolute(expected).canonicalize().unwrap());
+}
+
+#[test_case("..", "A", io::ErrorKind::PermissionDenied)]
+#[test_case("B/../../c1", "A", io::ErrorKind::PermissionDenied; "dotdot")]
+#[test_case("_C/c1", "A", io::ErrorKind::PermissionDenied; "symlink")]
+#[test_case("../a1", "A/B", io::ErrorKind::PermissionDenied; "dotdot to existing")]
+#[test_case("../a1", "_B", io::ErrorKind::PermissionDenied; "dotdot from symlinked base")]
+#[test_case("x1", "A", io::ErrorKind::NotFound; "not found")]
+fn test_real_resolve_abs_err(path: &str, base: &str, expected: io::ErrorKind) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("C")
+        .file("c1")
+        .file("A/a1")
+        .file("C/c1")
+        .symlink_rel("A/_C", "../C")
+        .symlink_rel("_B", "A/B");
+
+    let actual = real_resolve_abs(farm.absolute(base), path);
+
+    assert_eq!(actual.unwrap_err().kind(), expected);
+}
+
+#[test]
+fn test_real_resolve_abs_invalid() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").file("A/a1");
+
+    let relative_base = real_resolve_abs("A", "a1");
+    assert_eq!(
+        relative_base.unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+
+    let absolute_path = real_resolve_abs(farm.absolute("."), farm.absolute("A/a1"));
+    assert_eq!(
+        absolute_path.unwrap_err().kind(),
+        io::ErrorKind::PermissionDenied
+    );
+}
+
+#[test_case("A/B/_a1", "A/B", "../a1"; "same directory")]
+#[test_case("A/B/_a1", "A", "a1"; "parent directory")]
+#[test_case("A/B/_a1", "C", "../A/a1")]
+#[test_case("A/B/_a1", "C/D", "../../A/a1")]
+#[test_case("A/B/__a1", "C", "../A/a1"; "indirect")]
+#[test_case("A/B/_a1", "_D", "../../A/a1"; "symlinked directory")]
+#[test_case("A/B/_a1", ".", "A/a1"; "current directory")]
+#[test_case("_B/_a1", "C", "../A/a1"; "via symlinked directory")]
+fn test_real_retarget_symlink(link: &str, new_dir: &str, expected: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("C")
+        .dir("C/D")
+        .file("A/a1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("A/B/__a1", "_a1")
+        .symlink_rel("_B", "A/B")
+        .symlink_rel("_D", "C/D");
+
+    // relative paths
+    let actual = with_cwd(farm.absolute(".")).run(
+        |new_dir: &Path| real_retarget_symlink(link, new_dir),
+        Path::new(new_dir),
+    );
+    assert_eq!(actual.unwrap(), Path::new(expected));
+
+    // absolute paths, including mixed
+    for (link, new_dir) in [
+        (farm.absolute(link), farm.absolute(new_dir)),
+        (farm.absolute(link), PathBuf::from(new_dir)),
+        (PathBuf::from(link), farm.absolute(new_dir)),
+    ] {
+        let actual = with_cwd(farm.absolute(".")).run(
+            |new_dir: &Path| real_retarget_symlink(&link, new_dir),
+            new_dir.as_path(),
+        );
+        assert_eq!(actual.unwrap(), Path::new(expected));
+    }
+
+    // and the new target refers to the same file
+    let recreated = Path::new(new_dir).join("_new");
+    farm.symlink_rel(&recreated, expected);
+    assert_eq!(
+        farm.absolute(recreated).canonicalize().unwrap(),
+        farm.absolute("A/a1").canonicalize().unwrap()
+    );
+}
+
+#[test_case("A/_a1", "A/a1", "a1"; "same directory")]
+#[test_case("C/_a1", "A/a1", "../A/a1")]
+#[test_case("C/D/_a1", "A/a1", "../../A/a1")]
+#[test_case("_D/_a1", "A/a1", "../../A/a1"; "symlinked directory")]
+#[test_case("C/_a1", "_B/../a1", "../A/a1"; "target via symlinked directory")]
+#[test_case("C/_B", "_B", "../_B"; "symlink target")]
+#[test_case("_a1", "A/a1", "A/a1"; "current directory")]
+#[test_case("C/_x", "A/a1", "../A/a1"; "replacing symlink")]
+fn test_real_symlink_target_for(link: &str, target: &str, expected: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("C")
+        .dir("C/D")
+        .file("A/a1")
+        .symlink_rel("_B", "A/B")
+        .symlink_rel("_D", "C/D")
+        .symlink_rel("C/_x", "../A/B");
+
+    // relative paths
+    let actual = with_cwd(farm.absolute(".")).run(
+        |target: &Path| real_symlink_target_for(link, target),
+        Path::new(target),
+    );
+    assert_eq!(actual.unwrap(), Path::new(expected));
+
+    // absolute paths, including mixed
+    for (link, target) in [
+        (farm.absolute(link), farm.absolute(target)),
+        (farm.absolute(link), PathBuf::from(target)),
+        (PathBuf::from(link), farm.absolute(target)),
+    ] {
+        let actual = with_cwd(farm.absolute(".")).run(
+            |target: &Path| real_symlink_target_for(&link, target),
+            target.as_path(),
+        );
+        assert_eq!(actual.unwrap(), Path::new(expected));
+    }
+
+    // and a link created with that target refers to the target
+    let created = Path::new(link).with_file_name("_new");
+    farm.symlink_rel(&created, expected);
+    assert_eq!(
+        farm.absolute(created).canonicalize().unwrap(),
+        farm.absolute(target).canonicalize().unwrap()
+    );
+}
+
+#[test]
+fn test_real_symlink_target_for_no_file_name() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").file("A/a1");
+
+    let actual = real_symlink_target_for(farm.absolute("A/.."), farm.absolute("A/a1"));
+    assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_real_retarget_symlink_absolute() {
+    let mut farm = LinkFarm::new();
+
+    farm.dir("A").dir("C").file("A/a1");
+    farm.symlink_abs("A/=a1", "A/a1");
+
+    let actual = real_retarget_symlink(farm.absolute("A/=a1"), farm.absolute("C"));
+    assert_eq!(actual.unwrap(), farm.absolute("A/a1"));
+}
+
+#[test]
+fn test_real_retarget_symlink_not_symlink() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").dir("C").file("A/a1");
+
+    let actual = real_retarget_symlink(farm.absolute("A/a1"), farm.absolute("C"));
+    assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_stay_on_device() {
+    use std::os::unix::fs::MetadataExt;
+
+    let farm = LinkFarm::new();
+
+    farm.dir("A").dir("A/B").symlink_rel("_B", "A/B");
+
+    // within the farm, which is all on the same device
+    let mut resolver = Resolver::new().stay_on_device(true);
+    let actual = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| resolver.real_clean(Path::new("_B/../B/..")),
+        &mut resolver,
+    );
+    assert_eq!(actual.unwrap(), Path::new("A"));
+
+    // across a mount point, where there is one
+    let mount = Path::new("/proc");
+    let root_device = Path::new("/").metadata().unwrap().dev();
+    if mount
+        .metadata()
+        .map(|m| m.dev() != root_device)
+        .unwrap_or(false)
+    {
+        let path = mount.join("..");
+        assert_eq!(Resolver::new().real_clean(&path).unwrap(), Path::new("/"));
+
+        let e = resolver.real_clean(&path).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::CrossesDevices, "{}", e);
+    }
+}
+
+#[test_case("A", "B/b1")]
+#[test_case("A", "B/_a1"; "trailing symlink")]
+#[test_case("A/B", "_a1/.."; "symlink then dotdot")]
+#[test_case("_B", "../a1"; "symlinked base")]
+#[test_case("A/_B/..", "a1"; "dotdot in base")]
+#[test_case("_B", "_a1/../.."; "symlinks throughout")]
+#[test_case(".", "_B/.."; "current directory")]
+#[test_case("C", "../_B/../a1"; "ascending above base")]
+#[test_case("A/B", "../../.."; "ascending above farm")]
+#[test_case("A", ""; "empty")]
+fn test_real_clean_under(base: &str, rel: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("C")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("_B", "A/B")
+        .symlink_rel("A/_B", "B")
+        .symlink_rel("A/B/_a1", "../a1");
+
+    let mut resolver = Resolver::new();
+
+    // relative paths
+    let (expected, actual) = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| {
+            let expected = resolver.real_clean(&Path::new(base).join(rel)).unwrap();
+            let resolved_base = resolver.real_clean(Path::new(base)).unwrap();
+            let actual = resolver
+                .real_clean_under(&resolved_base, Path::new(rel))
+                .unwrap();
+            (expected, actual)
+        },
+        &mut resolver,
+    );
+    assert_eq!(actual, expected);
+
+    // absolute paths
+    let expected = resolver.real_clean(&farm.absolute(base).join(rel)).unwrap();
+    let resolved_base = resolver.real_clean(&farm.absolute(base)).unwrap();
+    let actual = resolver
+        .real_clean_under(&resolved_base, Path::new(rel))
+        .unwrap();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_virtual_root() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("A/B/C")
+        .file("A/B/C/c1")
+        .symlink_rel("A/_C", "B/C");
+
+    let root = farm.absolute("A");
+    let mut resolver = Resolver::new().virtual_root(&root);
+
+    assert!(resolver.is_real_root(&root).unwrap());
+    assert!(!resolver.is_real_root(&farm.absolute("A/B")).unwrap());
+    assert_eq!(resolver.real_parent(&root).unwrap(), root);
+    assert_eq!(
+        resolver.real_clean(&farm.absolute("A/B/../..")).unwrap(),
+        root
+    );
+
+    // without the virtual root, ascent continues beyond it
+    assert_eq!(
+        Resolver::new().real_parent(&root).unwrap(),
+        farm.absolute(".")
+    );
+}
+
+#[test_case("A/B/C/c1", vec!["A/B/C/c1", "A/B/C", "A/B", "A"] ; "plain")]
+#[test_case("A/_C/c1", vec!["A/_C/c1", "A/_C", "A/B", "A"] ; "via symlink")]
+fn test_virtual_root_ancestors(path: &str, expected: Vec<&str>) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("A/B/C")
+        .file("A/B/C/c1")
+        .symlink_rel("A/_C", "B/C");
+
+    let mut resolver = Resolver::new().virtual_root(farm.absolute("A"));
+    let actual = resolver
+        .real_ancestors(&farm.absolute(path))
+        .collect::<io::Result<Vec<_>>>()
+        .unwrap();
+    let expected = expected
+        .into_iter()
+        .map(|p| farm.absolute(p))
+        .collect::<Vec<_>>();
+
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_root_predicate() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("A/B/C")
+        .file("A/B/C/c1")
+        .file("A/a1")
+        .file("x1")
+        .symlink_rel("A/_C", "B/C");
+
+    let root = farm.absolute("A");
+    let predicate_root = root.clone();
+    let mut resolver = Resolver::new().root_predicate(Box::new(move |path| path == predicate_root));
+
+    // real_parent
+    assert!(resolver.is_real_root(&root).unwrap());
+    assert!(!resolver.is_real_root(&farm.absolute("A/B")).unwrap());
+    assert_eq!(resolver.real_parent(&root).unwrap(), root);
+    assert_eq!(
+        resolver.real_clean(&farm.absolute("A/B/../..")).unwrap(),
+        root
+    );
+
+    // real_ancestors
+    let actual = resolver
+        .real_ancestors(&farm.absolute("A/_C/c1"))
+        .collect::<io::Result<Vec<_>>>()
+        .unwrap();
+    let expected = ["A/_C/c1", "A/_C", "A/B", "A"]
+        .into_iter()
+        .map(|p| farm.absolute(p))
+        .collect::<Vec<_>>();
+    assert_eq!(actual, expected);
+
+    // find_upwards
+    assert_eq!(
+        resolver
+            .find_upwards(&farm.absolute("A/B/C"), "a1")
+            .unwrap(),
+        Some(farm.absolute("A/a1"))
+    );
+    assert_eq!(
+        resolver
+            .find_upwards(&farm.absolute("A/B/C"), "x1")
+            .unwrap(),
+        None
+    );
+
+    // without the predicate, ascent continues beyond it
+    assert_eq!(
+        Resolver::new()
+            .find_upwards(&farm.absolute("A/B/C"), "x1")
+            .unwrap(),
+        Some(farm.absolute("x1"))
+    );
+}
+
+#[test]
+fn test_virtual_root_relative() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").dir("A/B").dir("A/B/C");
+
+    // the virtual root is recognised by identity when reached via `..`
+    let mut resolver = Resolver::new().virtual_root(farm.absolute("A"));
+    let actual = with_cwd(farm.absolute("A/B/C")).run(
+        |resolver: &mut Resolver| {
+            resolver
+                .real_ancestors(Path::new("."))
+                .collect::<io::Result<Vec<_>>>()
+        },
+        &mut resolver,
+    );
+    assert_eq!(
+        actual.unwrap(),
+        vec![
+            PathBuf::from("."),
+            PathBuf::from(".."),
+            PathBuf::from("../.."),
+        ]
+    );
+
+    let actual = with_cwd(farm.absolute("A/B")).run(
+        |resolver: &mut Resolver| resolver.real_clean(Path::new("../../..")),
+        &mut resolver,
+    );
+    assert_eq!(actual.unwrap(), Path::new(".."));
+}
+
+#[test_case("SRC/main1", "Src", "SRC")]
+#[test_case("src/Lib/lib1", "Src/lib", "src/Lib")]
+#[test_case("Src/main1", "Src", "Src"; "already correct")]
+#[cfg(any(target_family = "windows", target_os = "macos"))]
+fn test_correct_case(path: &str, expected: &str, expected_uncorrected: &str) {
+    let farm = LinkFarm::new();
+    farm.dir("Src")
+        .dir("Src/lib")
+        .file("Src/main1")
+        .file("Src/lib/lib1");
+
+    let path = farm.absolute(path);
+    let actual = Resolver::new().real_parent(&path).unwrap();
+    assert_eq!(actual, farm.absolute(expected_uncorrected));
+
+    let actual = Resolver::new()
+        .correct_case(true)
+        .real_parent(&path)
+        .unwrap();
+    assert_eq!(actual, farm.absolute(expected));
+}
+
+#[test]
+#[cfg(not(any(target_family = "windows", target_os = "macos")))]
+fn test_correct_case_case_sensitive() {
+    let farm = LinkFarm::new();
+    farm.dir("Src")
+        .dir("src")
+        .file("Src/main1")
+        .file("src/main1");
+
+    // distinct directories differing only in case are left alone
+    let mut resolver = Resolver::new().correct_case(true);
+    for dir in ["Src", "src"] {
+        let actual = resolver
+            .real_parent(&farm.absolute(dir).join("main1"))
+            .unwrap();
+        assert_eq!(actual, farm.absolute(dir));
+    }
+}
+
+#[test]
+#[cfg(target_family = "windows")]
+fn test_expand_short_names() {
+    use std::os::windows::process::CommandExt;
+
+    let farm = LinkFarm::new();
+    farm.dir("Long Directory Name")
+        .file("Long Directory Name/a1");
+
+    let long = farm.absolute("Long Directory Name");
+    let output = std::process::Command::new("cmd")
+        .raw_arg(format!(
+            "/C for %I in (\"{}\") do @echo %~sI",
+            long.display()
+        ))
+        .output()
+        .unwrap();
+    let short = PathBuf::from(String::from_utf8(output.stdout).unwrap().trim());
+    if short.file_name() == long.file_name() {
+        // short name generation is disabled on this volume
+        return;
+    }
+
+    let path = short.join("a1");
+    let actual = Resolver::new().real_parent(&path).unwrap();
+    assert_eq!(actual, short);
+
+    let actual = Resolver::new()
+        .expand_short_names(true)
+        .real_parent(&path)
+        .unwrap();
+    assert!(!actual.to_string_lossy().contains('~'), "{:?}", actual);
+    assert!(actual.ends_with("Long Directory Name"));
+    assert_eq!(actual.canonicalize().unwrap(), long.canonicalize().unwrap());
+}
+
+#[test_case(RootParentPolicy::SelfSame, Ok(Some(())); "self same")]
+#[test_case(RootParentPolicy::None, Ok(None); "none")]
+#[test_case(RootParentPolicy::Error, Err(io::ErrorKind::InvalidInput); "error")]
+fn test_root_parent(policy: RootParentPolicy, expected: Result<Option<()>, io::ErrorKind>) {
+    let farm = LinkFarm::new();
+    farm.dir("A");
+    let root = farm.absolute("A").ancestors().last().unwrap().to_path_buf();
+
+    for path in [root.clone(), root.join("..")] {
+        let mut resolver = Resolver::new().root_parent(policy);
+        let actual = resolver.real_parent_opt(&path);
+        assert_eq!(
+            actual
+                .as_ref()
+                .map(|parent| parent.as_ref().map(|_| ()))
+                .map_err(io::Error::kind),
+            expected,
+            "{:?}",
+            path
+        );
+        if let Ok(Some(parent)) = actual {
+            assert_eq!(parent, root);
+        }
+
+        // real_parent can't return None
+        let actual = resolver.real_parent(&path).map_err(|e| e.kind());
+        match expected {
+            Ok(_) => assert_eq!(actual.unwrap(), root),
+            Err(kind) => assert_eq!(actual.unwrap_err(), kind),
+        }
+    }
+
+    // other paths are unaffected
+    let mut resolver = Resolver::new().root_parent(policy);
+    let actual = resolver.real_parent_opt(&farm.absolute("A")).unwrap();
+    assert_eq!(actual, Some(farm.absolute(".")));
+}
+
+#[test]
+fn test_real_parent_opt() {
+    let farm = LinkFarm::new();
+    farm.dir("A");
+    let root = farm.absolute("A").ancestors().last().unwrap().to_path_buf();
+
+    assert_eq!(root.real_parent_opt().unwrap(), None);
+    assert_eq!(
+        farm.absolute("A").real_parent_opt().unwrap(),
+        Some(farm.absolute("."))
+    );
+}
+
+#[test]
+#[cfg(target_family = "windows")]
+fn test_check_link_types() {
+    use std::os::windows::fs::symlink_file;
+
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/B/b1")
+        .symlink_rel("A/_B", "B");
+    // a file symlink to a directory, which resolves just the same
+    symlink_file("B", farm.absolute("A/_B_file")).unwrap();
+
+    let mut resolver = Resolver::new();
+    let actual = resolver.real_parent(&farm.absolute("A/_B_file")).unwrap();
+    assert_eq!(actual, farm.absolute("A"));
+
+    let mut resolver = Resolver::new().check_link_types(true);
+    let actual = resolver.real_parent(&farm.absolute("A/_B")).unwrap();
+    assert_eq!(actual, farm.absolute("A"));
+
+    let e = resolver
+        .real_parent(&farm.absolute("A/_B_file"))
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    assert!(e.to_string().contains("typed as file"), "{}", e);
+}
+
+#[test_case("A/_b", "A")]
+#[test_case("A/_c", "A"; "via another symlink")]
+#[test_case("_b", "."; "in current directory")]
+#[cfg(target_family = "unix")]
+fn test_keep_dangling_symlinks(path: &str, expected: &str) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .symlink_rel("A/_b", "X/x1")
+        .symlink_rel("A/_c", "_b")
+        .symlink_rel("_b", "x1");
+
+    let actual = with_cwd(farm.absolute(".")).run(
+        |path: &Path| Resolver::new().real_parent(path),
+        Path::new(path),
+    );
+    assert!(actual.is_err());
+
+    let mut resolver = Resolver::new().keep_dangling_symlinks(true);
+    let actual = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| resolver.real_parent(Path::new(path)),
+        &mut resolver,
+    );
+    assert_eq!(actual.unwrap(), Path::new(expected));
+
+    let actual = resolver.real_parent(&farm.absolute(path)).unwrap();
+    assert_eq!(
+        actual,
+        farm.absolute(expected).components().collect::<PathBuf>()
+    );
+
+    // only a dangling symlink as the final component is kept
+    let actual = resolver.real_clean(&farm.absolute(path).join(".."));
+    assert!(actual.is_err());
+}
+
+#[test_case(None, Some("A/B/C/D"); "unlimited")]
+#[test_case(Some(11), Some("A/B/C/D"); "within limit")]
+#[test_case(Some(10), None; "beyond limit")]
+fn test_max_resolved_len(max: Option<usize>, expected: Option<&str>) {
+    let farm = LinkFarm::new();
+
+    // each symlink in the chain leads further down
+    farm.dir("A")
+        .dir("A/B")
+        .dir("A/B/C")
+        .dir("A/B/C/D")
+        .file("A/B/C/D/x1")
+        .symlink_rel("_1", "A/_2")
+        .symlink_rel("A/_2", "B/_3")
+        .symlink_rel("A/B/_3", "C/D/x1");
+
+    // the limit is beyond the length of the base directory, where `/A/B/C/D/x1` is the longest path resolved
+    let base = farm.absolute(".").components().collect::<PathBuf>();
+    let mut resolver = Resolver::new().with_base(&base);
+    if let Some(max) = max {
+        resolver = resolver.max_resolved_len(max + base.as_os_str().len());
+    }
+
+    let actual = resolver.real_parent(Path::new("_1"));
+    match expected {
+        Some(expected) => assert_eq!(actual.unwrap(), farm.absolute(expected)),
+        None => assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::InvalidFilename),
+    }
+}
+
+#[test_case("A/_b1", Some("A/B"); "allowed")]
+#[test_case("A/B/_a1", Some("A"); "allowed with dotdot")]
+#[test_case("A/_x1", None; "forbidden")]
+#[test_case("A/_c1", None; "forbidden via allowed")]
+#[test_case("X/_a1", Some("A"); "allowed from outside")]
+fn test_allowed_target_prefixes(path: &str, expected: Option<&str>) {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .dir("X")
+        .file("A/a1")
+        .file("A/B/b1")
+        .file("X/x1")
+        .symlink_rel("A/_b1", "B/b1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("A/_x1", "../X/x1")
+        .symlink_rel("A/_c1", "_x1")
+        .symlink_rel("X/_a1", "../A/a1");
+
+    let mut resolver = Resolver::new().allowed_target_prefixes(vec![PathBuf::from("A")]);
+    let actual = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| resolver.real_parent(Path::new(path)),
+        &mut resolver,
+    );
+    match expected {
+        Some(expected) => assert_eq!(actual.unwrap(), Path::new(expected)),
+        None => assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::PermissionDenied),
+    }
+
+    let mut resolver = Resolver::new().allowed_target_prefixes(vec![farm.absolute("A")]);
+    let actual = resolver.real_parent(&farm.absolute(path));
+    match expected {
+        Some(expected) => assert_eq!(actual.unwrap(), farm.absolute(expected)),
+        None => assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::PermissionDenied),
+    }
+}
+
+#[test]
+fn test_allowed_target_prefixes_clean() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A").dir("X").symlink_rel("A/_X", "../X");
+
+    let mut resolver = Resolver::new().allowed_target_prefixes(vec![farm.absolute("A")]);
+
+    // a symlink which is not followed is not checked
+    let actual = resolver.real_clean(&farm.absolute("A/_X/."));
+    assert_eq!(actual.unwrap(), farm.absolute("A/_X"));
+
+    let actual = resolver.real_clean(&farm.absolute("A/_X/.."));
+    assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+}
+
+#[test]
+fn test_on_symlink() {
+    let farm = LinkFarm::new();
+
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .file("A/B/b1")
+        .symlink_rel("A/B/_a1", "../a1")
+        .symlink_rel("A/B/__a1", "_a1")
+        .symlink_rel("_B", "A/B");
+
+    let followed = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut resolver = Resolver::new().on_symlink({
+        let followed = followed.clone();
+        Box::new(move |link: &Path, target: &Path| {
+            followed
+                .lock()
+                .unwrap()
+                .push((link.to_path_buf(), target.to_path_buf()))
+        })
+    });
+
+    // once per hop, including the symlinked directory, since the target of `_B/_a1` ascends from it
+    let actual = with_cwd(farm.absolute(".")).run(
+        |resolver: &mut Resolver| resolver.real_parent(Path::new("_B/__a1")),
+        &mut resolver,
+    );
+    assert_eq!(actual.unwrap(), Path::new("A"));
+    assert_eq!(
+        *followed.lock().unwrap(),
+        vec![
+            (PathBuf::from("_B/__a1"), PathBuf::from("_a1")),
+            (PathBuf::from("_B/_a1"), PathBuf::from("../a1")),
+            (PathBuf::from("_B"), PathBuf::from("A/B")),
+        ]
+    );
+
+    // and not at all where no symlink is followed
+    followed.lock().unwrap().clear();
+    let actual = resolver.real_parent(&farm.absolute("_B/b1"));
+    assert_eq!(actual.unwrap(), farm.absolute("_B"));
+    assert!(followed.lock().unwrap().is_empty());
+}
+
+// a memory filesystem where the contents of any directory named `C` are inaccessible
+#[derive(Debug)]
+struct DenyingFileSystem {
+    inner: MemoryFileSystem,
+}
+
+impl DenyingFileSystem {
+    fn new() -> Self {
+        let inner = [
+            ("A", Entry::Dir),
+            ("A/B", Entry::Dir),
+            ("A/B/C", Entry::Dir),
+            ("A/B/C/c1", Entry::File),
+            ("A/_b", Entry::Symlink("B".into())),
+            ("A/_c1", Entry::Symlink("B/C/c1".into())),
+        ]
+        .into_iter()
+        .map(|(path, entry)| (path.into(), entry))
+        .collect::<BTreeMap<_, _>>()
+        .into();
+
+        Self { inner }
+    }
+
+    fn deny(&self, path: &Path) -> io::Result<()> {
+        let mut components = path.components().rev().skip(1);
+        if components.any(|c| c.as_os_str() == "C") {
+            Err(io::ErrorKind::PermissionDenied.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl FileSystem for DenyingFileSystem {
+    fn entry_kind(&self, path: &Path) -> io::Result<EntryKind> {
+        self.deny(path)?;
+        self.inner.entry_kind(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.deny(path)?;
+        self.inner.read_link(path)
+    }
+}
+
+#[test_case("A/_b/C/c1", &["A", "A/_b"]; "symlinked dir")]
+#[test_case("A/_b/C/c1/../..", &["A", "A/_b"]; "dotdot")]
+#[test_case("A/_c1", &["A", "A/B"]; "symlink target")]
+fn test_collect_trace_on_error(path: &str, expected: &[&str]) {
+    let path = Path::new(path);
+
+    let mut resolver = Resolver::new().with_file_system(DenyingFileSystem::new());
+    let e = resolver.real_parent(path).unwrap_err();
+    assert!(traversed_before_error(&e).is_none());
+
+    let mut resolver = Resolver::new()
+        .with_file_system(DenyingFileSystem::new())
+        .collect_trace_on_error(true);
+    let e = resolver.real_parent(path).unwrap_err();
+    assert_eq!(
+        traversed_before_error(&e).unwrap(),
+        expected.iter().map(PathBuf::from).collect::<Vec<_>>()
+    );
+    assert!(e.to_string().contains(" after traversing A, "), "{}", e);
+
+    // other errors are unaffected
+    let e = resolver.real_parent(Path::new("A/missing")).unwrap_err();
+    assert!(traversed_before_error(&e).is_none());
+}
+
+#[test]
+fn test_denied_path_via_file_system() {
+    // the inaccessible directory is located using the configured filesystem, not the real one
+    let mut resolver = Resolver::new().with_file_system(DenyingFileSystem::new());
+    let e = resolver.real_parent(Path::new("A/_c1")).unwrap_err();
+    assert!(
+        e.to_string().starts_with("permission denied on A/B/C "),
+        "{}",
+        e
+    );
+
+    // locating it is counted against the budget, and abandoned once that is exhausted
+    let mut resolver = Resolver::new()
+        .with_file_system(DenyingFileSystem::new())
+        .max_io_ops(3);
+    let e = resolver.real_parent(Path::new("A/_c1")).unwrap_err();
+    assert!(
+        e.to_string().starts_with("permission denied on A/B/C/c1 "),
+        "{}",
+        e
+    );
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;
@@ -0,0 +1,49 @@
+use std::env;
+
+use real_parent::PathExt;
+
+// a single test, since the environment is shared by all threads
+#[test]
+fn test_is_within_temp() {
+    let farm = LinkFarm::new();
+
+    // the farm is below the real temporary directory, which on macOS is reached via a symlink,
+    // whereas farm paths are canonical
+    assert!(farm.absolute(".").is_within_temp().unwrap());
+
+    farm.dir("T")
+        .dir("O")
+        .file("T/t1")
+        .file("O/o1")
+        .symlink_rel("_T", "T")
+        .symlink_rel("T/_O", "../O")
+        .symlink_rel("O/_t1", "../T/t1");
+
+    let tmp = if cfg!(target_family = "windows") {
+        "TMP"
+    } else {
+        "TMPDIR"
+    };
+    // as on macOS, the temporary directory may itself be reached via a symlink
+    env::set_var(tmp, farm.absolute("_T"));
+
+    for (path, expected) in [
+        ("T", true),
+        ("T/t1", true),
+        ("_T/t1", true),
+        ("O/_t1", true),
+        ("T/X/x1", true),
+        ("T/_O", false),
+        ("T/..", false),
+        ("O/o1", false),
+    ] {
+        let actual = farm.absolute(path).is_within_temp().unwrap();
+        assert_eq!(actual, expected, "{}", path);
+    }
+
+    env::remove_var(tmp);
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;
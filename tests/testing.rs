@@ -0,0 +1,50 @@
+#![cfg(feature = "testing")]
+
+use real_parent::{testing::assert_real_eq, PathExt};
+use test_case::test_case;
+
+#[test_case("A/B", "A/B"; "logically equal")]
+#[test_case("A/./B", "A/B"; "logically equal after dot")]
+#[test_case("_B", "A/B"; "symlink")]
+#[test_case("_B/..", "A"; "dotdot after symlink")]
+fn test_assert_real_eq(actual: &str, expected: &str) {
+    let farm = LinkFarm::new();
+    farm.dir("A").dir("A/B").symlink_rel("_B", "A/B");
+
+    assert_real_eq(farm.absolute(actual), farm.absolute(expected));
+}
+
+#[test]
+fn test_assert_real_eq_real_parent() {
+    let farm = LinkFarm::new();
+    farm.dir("A")
+        .dir("A/B")
+        .file("A/a1")
+        .symlink_rel("A/B/_a1", "../a1");
+
+    let path = farm.absolute("A/B/_a1");
+    assert_real_eq(path.real_parent().unwrap(), farm.absolute("A"));
+}
+
+#[test]
+fn test_assert_real_eq_missing() {
+    let farm = LinkFarm::new();
+    farm.dir("A");
+
+    // no need to canonicalize where logically equal
+    assert_real_eq(farm.absolute("A/./X"), farm.absolute("A/X"));
+}
+
+#[test_case("A/B", "A"; "different")]
+#[test_case("A/X", "A/Y"; "missing")]
+#[should_panic]
+fn test_assert_real_eq_panics(actual: &str, expected: &str) {
+    let farm = LinkFarm::new();
+    farm.dir("A").dir("A/B");
+
+    assert_real_eq(farm.absolute(actual), farm.absolute(expected));
+}
+
+#[allow(dead_code)]
+mod helpers;
+use helpers::*;